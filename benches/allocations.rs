@@ -0,0 +1,116 @@
+//! Counts heap allocations per routed request. Matching static routes and routes
+//! with borrowed (or `Copy`) params must not allocate at all.
+//!
+//! Runs on stable: `cargo bench --bench allocations`
+
+extern crate http_router;
+
+use http_router::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const ITERATIONS: usize = 1000;
+
+// Average number of allocations per call of `f`
+fn allocations<F: FnMut()>(mut f: F) -> f64 {
+    // the first call compiles route patterns
+    f();
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    (ALLOCATIONS.load(Ordering::SeqCst) - before) as f64 / ITERATIONS as f64
+}
+
+fn get_users(_: &()) -> usize {
+    0
+}
+
+fn get_user(_: &(), user_id: u32) -> usize {
+    user_id as usize
+}
+
+fn get_file(_: &(), user: &str, path: &str) -> usize {
+    user.len() + path.len()
+}
+
+fn get_transaction(_: &(), user_id: u32, hash: String) -> usize {
+    user_id as usize + hash.len()
+}
+
+fn not_found(_: &()) -> usize {
+    404
+}
+
+fn main() {
+    let router = router!(
+        GET / => get_users,
+        GET /users => get_users,
+        GET /users/{user_id: u32} => get_user,
+        GET /users/{user_id: u32}/transactions/{hash: String} => get_transaction,
+        GET /files/{user: &str}/{*path: &str} => get_file,
+        _ => not_found,
+    );
+    let builder_router = Router::builder()
+        .route(Method::GET, "/", get_users)
+        .route(Method::GET, "/users", get_users)
+        .route(Method::GET, "/users/{user_id}", get_user)
+        .route(
+            Method::GET,
+            "/users/{user_id}/transactions/{hash}",
+            get_transaction,
+        )
+        .route(Method::GET, "/files/{user}/{*path}", get_file)
+        .fallback(not_found)
+        .build();
+
+    // (path, allocations expected with `router!`)
+    let cases = [
+        ("/", Some(0.0)),
+        ("/users", Some(0.0)),
+        ("/users/12", Some(0.0)),
+        ("/files/alice/docs/a.txt", Some(0.0)),
+        ("/not/found", Some(0.0)),
+        // `String` params are owned
+        ("/users/12/transactions/0x1", None),
+    ];
+    println!("{:<32} {:>10} {:>10}", "path", "router!", "builder");
+    let mut failed = false;
+    for &(path, expected) in cases.iter() {
+        let macro_count = allocations(|| {
+            router((), Method::GET, path);
+        });
+        let builder_count = allocations(|| {
+            builder_router.handle((), Method::GET, path);
+        });
+        println!("{:<32} {:>10} {:>10}", path, macro_count, builder_count);
+        if let Some(expected) = expected {
+            failed |= macro_count > expected;
+            failed |= !path.starts_with("/files") && builder_count > expected;
+        }
+    }
+    assert!(!failed, "Routing allocates more than expected");
+}
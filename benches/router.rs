@@ -5,23 +5,22 @@ extern crate rand;
 extern crate regex;
 extern crate test;
 
-use test::Bencher;
-#[macro_use]
 use http_router::*;
+use test::Bencher;
 
 #[bench]
 fn bench_router(b: &mut Bencher) {
-    let get_users = |_: ()| "get_users".to_string();
-    let post_users = |_: ()| "post_users".to_string();
-    let patch_users = |_: (), id: u32| format!("patch_users({})", id);
-    let delete_users = |_: (), id: u32| format!("delete_users({})", id);
-    let get_transactions = |_: (), id: u32| format!("get_transactions({})", id);
-    let post_transactions = |_: (), id: u32| format!("post_transactions({})", id);
+    let get_users = |_: &()| "get_users".to_string();
+    let post_users = |_: &()| "post_users".to_string();
+    let patch_users = |_: &(), id: u32| format!("patch_users({})", id);
+    let delete_users = |_: &(), id: u32| format!("delete_users({})", id);
+    let get_transactions = |_: &(), id: u32| format!("get_transactions({})", id);
+    let post_transactions = |_: &(), id: u32| format!("post_transactions({})", id);
     let patch_transactions =
-        |_: (), id: u32, hash: String| format!("patch_transactions({}, {})", id, hash);
+        |_: &(), id: u32, hash: String| format!("patch_transactions({}, {})", id, hash);
     let delete_transactions =
-        |_: (), id: u32, hash: String| format!("delete_transactions({}, {})", id, hash);
-    let fallback = |_: ()| "404".to_string();
+        |_: &(), id: u32, hash: String| format!("delete_transactions({}, {})", id, hash);
+    let fallback = |_: &()| "404".to_string();
 
     let router = router!(
         GET / => get_users,
@@ -69,8 +68,8 @@ fn bench_router(b: &mut Bencher) {
 
     b.iter(|| {
         let number = rand::random::<usize>() % test_cases.len();
-        let (method, path, expected) = test_cases[number];
-        let _ = router((), method.clone(), path);
+        let (method, path, _) = test_cases[number];
+        let _ = router((), method, path);
     });
 }
 
@@ -79,8 +78,8 @@ fn bench_plain_regex(b: &mut Bencher) {
     let re = regex::Regex::new(r#"/users/([\w-]+)/transactions/([\w-]+)"#).unwrap();
     b.iter(|| {
         // number of routes in router
-        for i in 0..9 {
-            for matches in re.captures("/users/234/transactions/dfgd") {}
+        for _ in 0..9 {
+            let _ = re.captures("/users/234/transactions/dfgd");
         }
     });
 }
@@ -1,50 +1,26 @@
-use failure::{Backtrace, Context, Fail};
 use std::fmt;
-use std::fmt::Display;
 
 #[derive(Debug)]
-pub struct Error {
-    inner: Context<ErrorKind>,
+pub enum Error {
+    /// Error parsing json to model
+    Json(serde_json::Error),
+    /// Record not found
+    NotFound(String),
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Fail)]
-pub enum ErrorKind {
-    #[fail(display = "Error parsing bytes to UTF8 string")]
-    Utf8,
-    #[fail(display = "Error parsing json to model")]
-    Json,
-    #[fail(display = "Error inside hyper framework")]
-    Hyper,
-    #[fail(display = "Record not found")]
-    NotFound,
-}
-
-impl Fail for Error {
-    fn cause(&self) -> Option<&Fail> {
-        self.inner.cause()
-    }
-
-    fn backtrace(&self) -> Option<&Backtrace> {
-        self.inner.backtrace()
-    }
-}
-
-impl Display for Error {
+impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Display::fmt(&self.inner, f)
-    }
-}
-
-impl From<ErrorKind> for Error {
-    fn from(kind: ErrorKind) -> Error {
-        Error {
-            inner: Context::new(kind),
+        match self {
+            Error::Json(e) => write!(f, "Error parsing json to model: {}", e),
+            Error::NotFound(what) => write!(f, "Record not found: {}", what),
         }
     }
 }
 
-impl From<Context<ErrorKind>> for Error {
-    fn from(inner: Context<ErrorKind>) -> Error {
-        Error { inner: inner }
+impl std::error::Error for Error {}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Json(e)
     }
 }
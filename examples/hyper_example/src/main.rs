@@ -1,77 +1,23 @@
-#[macro_use]
-extern crate http_router;
-extern crate hyper;
-extern crate serde;
-#[macro_use]
-extern crate serde_derive;
-#[macro_use]
-extern crate serde_json;
-extern crate futures;
-#[macro_use]
-extern crate failure;
-
 mod controller;
 mod error;
+mod middleware;
 mod repo;
 mod types;
 mod utils;
 
 use self::controller::*;
-use self::error::Error;
+use self::middleware::logging;
 use self::repo::Repo;
-use self::utils::read_body;
-use failure::{Compat, Fail};
-use futures::future;
-use hyper::rt::Future;
-use hyper::service::Service;
-use hyper::{Body, Request, Response, Server};
+use http_router::hyper::HyperService;
+use http_router::router;
+use hyper::server::conn::http1;
+use hyper_util::rt::TokioIo;
+use serde_json::json;
 use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
 
-type StdFuture = Box<Future<Item = Response<Body>, Error = Compat<Error>> + Send>;
-
-#[derive(Clone)]
-struct Application {
-    pub repo: Arc<Mutex<Repo>>,
-}
-
-impl Service for Application {
-    type ReqBody = Body;
-    type ResBody = Body;
-    type Error = Compat<Error>;
-    type Future = StdFuture;
-
-    fn call(&mut self, req: Request<Body>) -> StdFuture {
-        let repo = self.repo.clone();
-        let (req, body) = req.into_parts();
-        Box::new(
-            read_body(body)
-                .and_then(move |body| {
-                    let router = router!(
-                        GET / => get_users,
-
-                        GET /users => get_users,
-                        POST /users => post_users,
-                        PUT /users/{user_id: usize} => put_users,
-                        DELETE /users/{user_id: usize} => delete_users,
-
-                        GET /users/{user_id: usize}/transactions => get_transactions,
-                        POST /users/{user_id: usize}/transactions => post_transactions,
-                        PUT /users/{user_id: usize}/transactions/{hash: String} => put_transactions,
-                        DELETE /users/{user_id: usize}/transactions/{hash: String} => delete_transactions,
-
-                        _ => not_found,
-                    );
-
-                    let path = req.uri.path();
-                    let ctx = Context { repo, body };
-                    router(ctx, req.method.into(), path)
-                })
-                .map_err(|e| e.compat()),
-        )
-    }
-}
-
-fn main() {
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let addr = ([127, 0, 0, 1], 3000).into();
 
     let json = json!({
@@ -93,21 +39,44 @@ fn main() {
             },
         ],
     });
+    let repo: Repo = serde_json::from_value(json).expect("Failed to parse repo");
+    let state = State {
+        repo: Arc::new(Mutex::new(repo)),
+    };
 
-    hyper::rt::run(future::lazy(move || {
-        let repo: Repo = serde_json::from_value(json).expect("Failed to parse repo");
-        let repo = Arc::new(Mutex::new(repo));
-        let app = Application { repo };
-        let new_service = move || {
-            let res: Result<_, hyper::Error> = Ok(app.clone());
-            res
-        };
+    let router = router!(async
+        with [logging],
 
-        let server = Server::bind(&addr)
-            .serve(new_service)
-            .map_err(|e| eprintln!("server error: {}", e));
+        GET / => redirect(308, "/users"),
 
-        println!("Listening on http://{}", addr);
-        server
-    }));
+        GET /users => get_users produces "application/json",
+        GET /users => get_users_csv produces "text/csv",
+        POST /users => post_users consumes "application/json",
+        PUT /users/{user_id: usize} => put_users,
+        DELETE /users/{user_id: usize} => delete_users,
+
+        GET /users/{user_id: usize}/transactions => get_transactions,
+        POST /users/{user_id: usize}/transactions => post_transactions,
+        PUT /users/{user_id: usize}/transactions/{hash: String} => put_transactions,
+        DELETE /users/{user_id: usize}/transactions/{hash: String} => delete_transactions,
+
+        ! => rejected,
+        _ => not_found,
+    );
+    // Reads bodies up to 64 KiB and maps errors to responses
+    let service = HyperService::new(router, state)
+        .body_limit(64 * 1024)
+        .error_handler(utils::error_response);
+
+    let listener = TcpListener::bind::<std::net::SocketAddr>(addr).await?;
+    println!("Listening on http://{}", addr);
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let service = service.clone();
+        tokio::spawn(async move {
+            if let Err(e) = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await {
+                eprintln!("server error: {}", e);
+            }
+        });
+    }
 }
@@ -0,0 +1,24 @@
+use super::controller::Context;
+use super::types::ServerResult;
+use http_router::{BoxFuture, Next, RouteInfo};
+use std::time::Instant;
+
+// Logs every handled request with the route template, so that
+// requests to `/users/1` and `/users/2` show up as the same route
+pub fn logging(
+    context: &Context,
+    route: &RouteInfo,
+    next: Next<Context, BoxFuture<ServerResult>>,
+) -> BoxFuture<ServerResult> {
+    let started = Instant::now();
+    let route = format!("{} {}", route.method, route.path);
+    let response = next.run(context);
+    Box::pin(async move {
+        let result = response.await;
+        match result {
+            Ok(ref response) => println!("{} {} in {:?}", route, response.status(), started.elapsed()),
+            Err(ref e) => println!("{} failed in {:?}: {}", route, started.elapsed(), e),
+        }
+        result
+    })
+}
@@ -1,4 +1,5 @@
-use super::error::{Error, ErrorKind};
+use super::error::Error;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
@@ -24,11 +25,10 @@ impl Repo {
     }
 
     pub fn find_user(&mut self, id: usize) -> Result<&mut User, Error> {
-        self.users.iter_mut().find(|u| u.id == id).ok_or(
-            format_err!("User with id: {}", id)
-                .context(ErrorKind::NotFound)
-                .into(),
-        )
+        self.users
+            .iter_mut()
+            .find(|u| u.id == id)
+            .ok_or_else(|| Error::NotFound(format!("User with id: {}", id)))
     }
 
     pub fn create_user(&mut self, user: User) -> User {
@@ -41,7 +41,7 @@ impl Repo {
         self.create_user(user)
     }
 
-    pub fn delete_user(&mut self, id: usize) -> () {
+    pub fn delete_user(&mut self, id: usize) {
         self.users = self
             .users
             .clone()
@@ -1,5 +1,5 @@
 use super::error::Error;
-use futures::prelude::*;
-use hyper::{Body, Response};
+use http_router::hyper::ResponseBody;
+use hyper::Response;
 
-pub type ServerFuture = Box<Future<Item = Response<Body>, Error = Error> + Send>;
+pub type ServerResult = Result<Response<ResponseBody>, Error>;
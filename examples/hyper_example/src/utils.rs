@@ -1,48 +1,32 @@
-use super::error::{Error, ErrorKind};
-use super::types::ServerFuture;
-use failure::Fail;
-use futures::future;
-use futures::prelude::*;
-use hyper;
-use hyper::Response;
+use super::error::Error;
+use super::types::ServerResult;
+use http_router::hyper::{self as router_hyper, ResponseBody};
+use hyper::{Response, StatusCode};
 use serde::Serialize;
-use serde_json;
-use std::fmt::Debug;
 
-// Reads body of request and response in Future format
-pub fn read_body(body: hyper::Body) -> impl Future<Item = String, Error = Error> {
-    body.fold(Vec::new(), |mut acc, chunk| {
-        acc.extend_from_slice(&*chunk);
-        future::ok::<_, hyper::Error>(acc)
-    }).map_err(|e| e.context(ErrorKind::Hyper).into())
-        .and_then(|bytes| {
-            let bytes_clone = bytes.clone();
-            String::from_utf8(bytes).map_err(|e| {
-                e.context(format!("bytes: {:?}", &bytes_clone))
-                    .context(ErrorKind::Utf8)
-                    .into()
-            })
-        })
+pub fn response_with_model<M: Serialize>(model: &M) -> ServerResult {
+    let text = serde_json::to_string(model)?;
+    Ok(response_with_text("application/json", text))
 }
 
-pub fn response_with_model<M>(model: &M) -> ServerFuture
-where
-    M: Debug + Serialize,
-{
-    Box::new(
-        serde_json::to_string(&model)
-            .map_err(|e| {
-                e.context(format!("model: {:?}", &model))
-                    .context(ErrorKind::Json)
-                    .into()
-            })
-            .into_future()
-            .map(|text| {
-                Response::builder()
-                    .status(200)
-                    .header("Content-Type", "application/json")
-                    .body(text.into())
-                    .unwrap()
-            }),
-    )
+pub fn response_with_text(content_type: &str, text: String) -> Response<ResponseBody> {
+    Response::builder()
+        .status(200)
+        .header("Content-Type", content_type)
+        .body(ResponseBody::from(text))
+        .unwrap()
+}
+
+pub fn empty_response(status: StatusCode) -> Response<ResponseBody> {
+    Response::builder().status(status).body(ResponseBody::default()).unwrap()
+}
+
+// Maps errors of the app and of the router to responses
+pub fn error_response(error: router_hyper::Error<Error>) -> Response<ResponseBody> {
+    let status = match error {
+        router_hyper::Error::Handler(Error::Json(_)) => StatusCode::BAD_REQUEST,
+        router_hyper::Error::Handler(Error::NotFound(_)) => StatusCode::NOT_FOUND,
+        ref e => e.status(),
+    };
+    Response::builder().status(status).body(ResponseBody::from(error.to_string())).unwrap()
 }
@@ -0,0 +1,241 @@
+use date::{http_date, parse_date};
+
+#[cfg(feature = "with_http")]
+use handler::BoxFuture;
+#[cfg(feature = "with_http")]
+use http::{header::HeaderName, HeaderMap, HeaderValue, Response};
+#[cfg(feature = "with_http")]
+use middleware::Next;
+#[cfg(feature = "with_http")]
+use route::RouteInfo;
+#[cfg(feature = "with_http")]
+use std::future::Future;
+#[cfg(feature = "with_http")]
+use std::pin::Pin;
+#[cfg(feature = "with_http")]
+use std::task::{Context, Poll};
+
+/// Deprecation of a route, e.g. `GET /v1/users => get_users deprecated(sunset = "2027-01-01")`.
+///
+/// It is a part of the `RouteInfo` of the route, so middleware can announce it to clients
+/// (see `deprecation_headers`), and OpenAPI marks the operation as deprecated.
+/// Dates are calendar dates in UTC, e.g. `2027-01-01`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Deprecation {
+    /// Date since when the route is deprecated
+    pub since: Option<String>,
+    /// Date when the route is going to be removed
+    pub sunset: Option<String>,
+    /// Link to the documentation of the deprecation, e.g. a migration guide
+    pub link: Option<String>,
+}
+
+impl Deprecation {
+    /// Deprecation without dates and link
+    pub fn new() -> Self {
+        Deprecation::default()
+    }
+
+    /// Sets the date since when the route is deprecated
+    ///
+    /// ### Panics
+    /// If `date` is not a date, e.g. `2026-06-01`.
+    pub fn since(mut self, date: &str) -> Self {
+        assert_date(date);
+        self.since = Some(date.to_string());
+        self
+    }
+
+    /// Sets the date when the route is going to be removed
+    ///
+    /// ### Panics
+    /// If `date` is not a date, e.g. `2027-01-01`.
+    pub fn sunset(mut self, date: &str) -> Self {
+        assert_date(date);
+        self.sunset = Some(date.to_string());
+        self
+    }
+
+    /// Sets the link to the documentation of the deprecation
+    pub fn link(mut self, link: &str) -> Self {
+        self.link = Some(link.to_string());
+        self
+    }
+
+    /// Response headers announcing the deprecation: `Deprecation` (RFC 9745), `Sunset`
+    /// (RFC 8594) and `Link` with `rel="deprecation"`. The value of `Deprecation` is a date,
+    /// so the header is sent only if `since` is set
+    pub fn header_values(&self) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+        if let Some(since) = self.since.as_ref().and_then(|since| parse_date(since)) {
+            headers.push(("deprecation", format!("@{}", since)));
+        }
+        if let Some(sunset) = self.sunset.as_ref().and_then(|sunset| parse_date(sunset)) {
+            headers.push(("sunset", http_date(sunset)));
+        }
+        if let Some(ref link) = self.link {
+            headers.push(("link", format!("<{}>; rel=\"deprecation\"", link)));
+        }
+        headers
+    }
+
+    /// Same as `header_values`, as a `HeaderMap`
+    #[cfg(feature = "with_http")]
+    pub fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in self.header_values() {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.append(HeaderName::from_static(name), value);
+            }
+        }
+        headers
+    }
+}
+
+fn assert_date(date: &str) {
+    assert!(
+        parse_date(date).is_some(),
+        "Deprecation date should be `YYYY-MM-DD`, got `{}`",
+        date
+    );
+}
+
+/// Responses that headers can be added to, e.g. by `deprecation_headers`.
+/// Headers are appended, so values set by the handler, e.g. pagination links, are kept.
+///
+/// Implemented for `http::Response<B>`, and for `Result<T, E>` and `BoxFuture<T>` of such responses.
+#[cfg(feature = "with_http")]
+pub trait WithHeaders {
+    /// Adds `headers` to the response
+    fn with_headers(self, headers: HeaderMap) -> Self;
+}
+
+#[cfg(feature = "with_http")]
+impl<B> WithHeaders for Response<B> {
+    fn with_headers(mut self, headers: HeaderMap) -> Self {
+        let response_headers = self.headers_mut();
+        for (name, value) in headers.iter() {
+            response_headers.append(name, value.clone());
+        }
+        self
+    }
+}
+
+#[cfg(feature = "with_http")]
+impl<T: WithHeaders, E> WithHeaders for Result<T, E> {
+    fn with_headers(self, headers: HeaderMap) -> Self {
+        self.map(|response| response.with_headers(headers))
+    }
+}
+
+#[cfg(feature = "with_http")]
+impl<T: WithHeaders + Send + 'static> WithHeaders for BoxFuture<T> {
+    fn with_headers(self, headers: HeaderMap) -> Self {
+        Box::pin(HeadersFuture {
+            future: self,
+            headers: Some(headers),
+        })
+    }
+}
+
+// Adds headers to the response of a future once it is ready
+#[cfg(feature = "with_http")]
+struct HeadersFuture<T> {
+    future: BoxFuture<T>,
+    headers: Option<HeaderMap>,
+}
+
+#[cfg(feature = "with_http")]
+impl<T: WithHeaders> Future for HeadersFuture<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+        let this = self.get_mut();
+        match this.future.as_mut().poll(cx) {
+            Poll::Ready(response) => {
+                let headers = this
+                    .headers
+                    .take()
+                    .expect("Future is polled after completion");
+                Poll::Ready(response.with_headers(headers))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Middleware adding the headers of the `Deprecation` of deprecated routes to their responses:
+///
+/// ```rust
+/// let router = router!(
+///     with [deprecation_headers],
+///     GET /v1/users => get_users deprecated(sunset = "2027-01-01", link = "https://example.com/v2"),
+///     GET /v2/users => get_users,
+///     _ => not_found,
+/// );
+/// ```
+#[cfg(feature = "with_http")]
+pub fn deprecation_headers<C, R: WithHeaders>(
+    context: &C,
+    route: &RouteInfo,
+    next: Next<C, R>,
+) -> R {
+    let response = next.run(context);
+    match route.deprecation {
+        Some(ref deprecation) => response.with_headers(deprecation.headers()),
+        None => response,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_values() {
+        let deprecation = Deprecation::new()
+            .since("2026-06-01")
+            .sunset("2027-01-01")
+            .link("https://example.com/migration");
+        assert_eq!(
+            deprecation.header_values(),
+            vec![
+                ("deprecation", "@1780272000".to_string()),
+                ("sunset", "Fri, 01 Jan 2027 00:00:00 GMT".to_string()),
+                (
+                    "link",
+                    "<https://example.com/migration>; rel=\"deprecation\"".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            Deprecation::new().sunset("2027-01-01").header_values(),
+            vec![("sunset", "Fri, 01 Jan 2027 00:00:00 GMT".to_string())]
+        );
+        assert!(Deprecation::new().header_values().is_empty());
+    }
+
+    #[cfg(feature = "with_http")]
+    #[test]
+    fn test_with_headers() {
+        let mut response = Response::new(());
+        let next = HeaderValue::from_static("</users?page=2>; rel=\"next\"");
+        response.headers_mut().insert("link", next.clone());
+        let deprecation = Deprecation::new().link("https://example.com/migration");
+        let response = response.with_headers(deprecation.headers());
+        let links: Vec<_> = response.headers().get_all("link").iter().collect();
+        assert_eq!(
+            links,
+            vec![
+                next,
+                HeaderValue::from_static("<https://example.com/migration>; rel=\"deprecation\""),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "should be `YYYY-MM-DD`")]
+    fn test_invalid_date() {
+        Deprecation::new().sunset("next year");
+    }
+}
@@ -0,0 +1,385 @@
+use handler::BoxFuture;
+use method::Method;
+use param::ParamError;
+use pattern::Pattern;
+use std::fmt;
+use std::future::ready;
+use std::sync::Arc;
+
+#[cfg(feature = "with_http")]
+use http::{header::CONTENT_TYPE, HeaderValue, Response};
+
+// Converts the raw param at an index with the type of the handler param, see `Handler::check_param`
+pub(crate) type SharedParamCheck = Arc<dyn Fn(usize, &str) -> Result<(), ParamError> + Send + Sync>;
+
+/// Why the routes of a `Router` match a request or not, see `Router::explain`.
+///
+/// Displays as one line per route, e.g. to answer a debug endpoint:
+///
+/// ```text
+/// GET /users/x
+///   GET /users => get_users: path doesn't match
+///   GET /users/{user_id} => get_user: Invalid param `user_id` = `x`: invalid digit found in string
+///   DELETE /users/{user_id} => delete_user: method doesn't match
+/// no route matches
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// Method of the request
+    pub method: Method,
+    /// Path of the request
+    pub path: String,
+    /// One explanation per route, in the order routes are matched
+    pub routes: Vec<RouteExplanation>,
+}
+
+impl Explanation {
+    /// The first route that matches the request, i.e. the route that handles it
+    /// unless its host, guards or media types rule the request out
+    pub fn matched(&self) -> Option<&RouteExplanation> {
+        self.routes.iter().find(|route| route.matches())
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} {}", self.method, self.path)?;
+        for route in self.routes.iter() {
+            writeln!(f, "  {}", route)?;
+        }
+        match self.matched() {
+            Some(route) => write!(f, "matched {} {}", route.method, route.path),
+            None => write!(f, "no route matches"),
+        }
+    }
+}
+
+/// Why a route matches a request or not, see `Explanation`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteExplanation {
+    /// Methods of the route separated with `|`, e.g. `GET | HEAD`, or `ANY`
+    pub method: String,
+    /// Host template of routes that match on the host, e.g. `{tenant}.example.com`
+    pub host: Option<String>,
+    /// Path template, e.g. `/users/{user_id}`
+    pub path: String,
+    /// Name of the handler function
+    pub handler: &'static str,
+    /// Whether the route matches the request method
+    pub method_matches: bool,
+    /// Whether the path template matches the request path
+    pub path_matches: bool,
+    /// Error of the first path param that the handler can't convert, if the path matches
+    pub param_error: Option<ParamError>,
+    /// Whether the route has a host, guards or media types. They depend on the rest
+    /// of the request, so they are not checked
+    pub conditional: bool,
+}
+
+impl RouteExplanation {
+    /// Whether the method, the path and the params of the route match the request
+    pub fn matches(&self) -> bool {
+        self.method_matches && self.path_matches && self.param_error.is_none()
+    }
+}
+
+impl fmt::Display for RouteExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {}{} => {}: ",
+            self.method,
+            self.host.as_deref().unwrap_or(""),
+            self.path,
+            self.handler
+        )?;
+        match (self.method_matches, self.path_matches, &self.param_error) {
+            (false, false, _) => write!(f, "method and path don't match"),
+            (false, true, _) => write!(f, "method doesn't match"),
+            (true, false, _) => write!(f, "path doesn't match"),
+            (true, true, Some(error)) => write!(f, "{}", error),
+            (true, true, None) if self.conditional => {
+                write!(f, "matches, if its host, guards and media types allow")
+            }
+            (true, true, None) => write!(f, "matches"),
+        }
+    }
+}
+
+// What `Router::explain` needs to know about a route. The explain endpoint keeps
+// a copy of these, since its handler has no access to the router
+pub(crate) struct RouteMatcher {
+    pub methods: Option<Vec<Method>>,
+    pub host: Option<String>,
+    pub pattern: Pattern,
+    // number of handler params, the params of the path come last
+    pub param_count: usize,
+    pub handler: &'static str,
+    pub param_check: SharedParamCheck,
+    pub conditional: bool,
+}
+
+impl RouteMatcher {
+    fn explain(&self, method: Method, path: &str) -> RouteExplanation {
+        let names = self.pattern.params();
+        let captures = self.pattern.captures(path);
+        let path_matches = captures.is_some();
+        let offset = self.param_count - names.len();
+        let param_error = captures
+            .into_iter()
+            .flatten()
+            .zip(names.iter())
+            .enumerate()
+            .find_map(|(index, (value, name))| {
+                let error = (self.param_check)(offset + index, value).err()?;
+                Some(error.with_param(name, value))
+            });
+        RouteExplanation {
+            method: match self.methods {
+                Some(ref methods) => {
+                    let methods: Vec<&str> = methods.iter().map(Method::as_str).collect();
+                    methods.join(" | ")
+                }
+                None => "ANY".to_string(),
+            },
+            host: self.host.clone(),
+            path: self.pattern.template().to_string(),
+            handler: self.handler,
+            method_matches: self
+                .methods
+                .as_ref()
+                .is_none_or(|methods| methods.contains(&method)),
+            path_matches,
+            param_error,
+            conditional: self.conditional,
+        }
+    }
+}
+
+/// Converts a raw param with the type of a handler param of `router!`, see `explainer!`
+#[doc(hidden)]
+pub type ParamCheck = fn(&str) -> Result<(), ParamError>;
+
+/// Explains requests to the routes of `router!`, which expands into plain code with
+/// no route table to inspect. It is made by `explainer!` from the same routes as
+/// the router, and explains them the same way as `Router::explain`.
+///
+/// ```rust
+/// let explainer = explainer!(
+///     GET /users/{user_id: u32} => get_user,
+///     _ => not_found
+/// );
+/// println!("{}", explainer.explain(Method::GET, "/users/x"));
+/// ```
+#[derive(Default)]
+pub struct Explainer {
+    routes: Vec<RouteMatcher>,
+}
+
+impl Explainer {
+    /// Explains for every route whether it matches the method and the path of a request,
+    /// see `Router::explain`
+    pub fn explain(&self, method: Method, path: &str) -> Explanation {
+        explain(&self.routes, method, path)
+    }
+
+    // Params of the host come first, like in the handler
+    pub(crate) fn push(
+        &mut self,
+        methods: Option<Vec<Method>>,
+        host: Option<String>,
+        path: &str,
+        handler: &'static str,
+        checks: Vec<ParamCheck>,
+        conditional: bool,
+    ) {
+        self.routes.push(RouteMatcher {
+            methods,
+            host,
+            pattern: Pattern::new(path),
+            param_count: checks.len(),
+            handler,
+            param_check: Arc::new(move |index, value| match checks.get(index) {
+                Some(check) => check(value),
+                None => Ok(()),
+            }),
+            conditional,
+        });
+    }
+}
+
+pub(crate) fn explain(routes: &[RouteMatcher], method: Method, path: &str) -> Explanation {
+    Explanation {
+        method,
+        path: path.to_string(),
+        routes: routes
+            .iter()
+            .map(|route| route.explain(method, path))
+            .collect(),
+    }
+}
+
+/// Plain text of the explanation with status 200
+#[cfg(feature = "with_http")]
+impl<B: From<String>> From<Explanation> for Response<B> {
+    fn from(explanation: Explanation) -> Self {
+        let mut response = Response::new(B::from(explanation.to_string()));
+        response.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; charset=utf-8"),
+        );
+        response
+    }
+}
+
+impl<T: From<Explanation>, E> From<Explanation> for Result<T, E> {
+    fn from(explanation: Explanation) -> Self {
+        Ok(T::from(explanation))
+    }
+}
+
+impl<T: From<Explanation> + Send + 'static> From<Explanation> for BoxFuture<T> {
+    fn from(explanation: Explanation) -> Self {
+        Box::pin(ready(T::from(explanation)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deprecation::Deprecation;
+    use extract::Rejection;
+    use router::Router;
+
+    impl From<Explanation> for String {
+        fn from(explanation: Explanation) -> Self {
+            explanation.to_string()
+        }
+    }
+
+    fn get_user(_: &(), user_id: u32) -> String {
+        format!("user {}", user_id)
+    }
+
+    #[test]
+    fn test_endpoint() {
+        let router = Router::builder()
+            .route(Method::GET, "/users/{user_id}", get_user)
+            .route_methods(
+                &[Method::PUT, Method::PATCH],
+                "/users/{user_id}",
+                |_: &(), _, id: u32| format!("put {}", id),
+            )
+            .explain_endpoint("/_explain/")
+            .rejection(|_: &(), rejection: Rejection| rejection.to_string())
+            .fallback(|_: &()| "404".to_string())
+            .build();
+        assert_eq!(
+            router.handle((), Method::GET, "/_explain/GET/users/x"),
+            "GET /users/x\n  \
+             GET /users/{user_id} => get_user: Invalid param `user_id` = `x`: invalid digit found in string\n  \
+             PUT | PATCH /users/{user_id} => {{closure}}: method doesn't match\n  \
+             GET /_explain/{method}/{*path} => explain: path doesn't match\n\
+             no route matches"
+        );
+        assert_eq!(
+            router.handle((), Method::GET, "/_explain/PATCH/users/1"),
+            "PATCH /users/1\n  \
+             GET /users/{user_id} => get_user: method doesn't match\n  \
+             PUT | PATCH /users/{user_id} => {{closure}}: matches\n  \
+             GET /_explain/{method}/{*path} => explain: method and path don't match\n\
+             matched PUT | PATCH /users/{user_id}"
+        );
+        assert_eq!(
+            router.handle((), Method::GET, "/_explain/G T/users/1"),
+            "Invalid param `method` = `G T`: Invalid http method `G T`"
+        );
+        let explanation = router.explain(Method::GET, "/_explain/G T/users");
+        let error = explanation.routes[2].param_error.as_ref().unwrap();
+        assert_eq!((error.name(), error.value()), ("method", "G T"));
+    }
+
+    #[test]
+    fn test_explainer() {
+        fn get_users(_: &()) -> String {
+            "users".to_string()
+        }
+        fn head_user(_: &(), _: Method, user_id: u32) -> String {
+            format!("user {}", user_id)
+        }
+        fn get_file(_: &(), _: Method, path: String) -> String {
+            path
+        }
+        fn is_admin(_: &()) -> bool {
+            true
+        }
+        fn not_found(_: &()) -> String {
+            "404".to_string()
+        }
+
+        let explainer = explainer!(
+            GET /users => get_users if is_admin,
+            GET | HEAD /users/{user_id: u32} => head_user deprecated,
+            ANY /files/{*path} => get_file,
+            _ => not_found
+        );
+        let router = Router::builder()
+            .route(Method::GET, "/users", get_users)
+            .guard(is_admin)
+            .route_methods(&[Method::GET, Method::HEAD], "/users/{user_id}", head_user)
+            .deprecated(Deprecation::new())
+            .any("/files/{*path}", get_file)
+            .fallback(not_found)
+            .build();
+        for &(method, path) in [
+            (Method::GET, "/users"),
+            (Method::HEAD, "/users/x"),
+            (Method::POST, "/files/a/b"),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                explainer.explain(method, path),
+                router.explain(method, path)
+            );
+        }
+        let explanation = explainer.explain(Method::HEAD, "/users/x");
+        assert!(explanation.routes[0].conditional);
+        assert!(!explanation.routes[1].conditional);
+
+        let explainer = explainer!(
+            GET {tenant: u16}.example.com/users/{user_id: u32} => get_user,
+            _ => not_found
+        );
+        assert_eq!(
+            explainer.explain(Method::GET, "/users/x").to_string(),
+            "GET /users/x\n  \
+             GET {tenant}.example.com/users/{user_id} => get_user: Invalid param `user_id` = `x`: invalid digit found in string\n\
+             no route matches"
+        );
+        assert_eq!(
+            explainer.explain(Method::GET, "/users/1").to_string(),
+            "GET /users/1\n  \
+             GET {tenant}.example.com/users/{user_id} => get_user: matches, if its host, guards and media types allow\n\
+             matched GET /users/{user_id}"
+        );
+    }
+
+    #[cfg(feature = "with_http")]
+    #[test]
+    fn test_response() {
+        let router = Router::builder()
+            .route(Method::GET, "/users/{user_id}", get_user)
+            .fallback(|_: &()| "404".to_string())
+            .build();
+        let response: Response<String> = router.explain(Method::GET, "/users/1").into();
+        assert_eq!(
+            response.headers()[CONTENT_TYPE],
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(
+            response.body(),
+            "GET /users/1\n  GET /users/{user_id} => get_user: matches\nmatched GET /users/{user_id}"
+        );
+    }
+}
@@ -0,0 +1,367 @@
+use method::InvalidMethod;
+use param::ParamError;
+use std::error::Error;
+use std::fmt;
+
+#[cfg(feature = "with_http")]
+use method::Method;
+#[cfg(feature = "with_http")]
+use request::RequestContext;
+#[cfg(feature = "with_http")]
+use std::convert::TryFrom;
+#[cfg(feature = "with_http")]
+use std::marker::PhantomData;
+#[cfg(feature = "with_http")]
+use std::ops::Deref;
+
+#[cfg(feature = "with_serde")]
+use serde::de::DeserializeOwned;
+
+/// A handler argument extracted from the context, e.g. `Query<T>` or `Json<T>`.
+///
+/// Extractors come after path params in handler signatures (up to 4 per handler):
+///
+/// ```rust
+/// fn put_user(context: &Context, user_id: u64, user: Json<User>, request_id: Header<RequestId>) -> Response {
+///     ...
+/// }
+/// ```
+///
+/// If an extractor fails, the route is rejected with its `Rejection`.
+/// Use `Option<T>` or `Result<T, Rejection>` for optional arguments.
+pub trait FromRequest<C>: Sized {
+    /// Extracts the value from the context
+    fn from_request(context: &C) -> Result<Self, Rejection>;
+}
+
+impl<C, T: FromRequest<C>> FromRequest<C> for Option<T> {
+    fn from_request(context: &C) -> Result<Self, Rejection> {
+        Ok(T::from_request(context).ok())
+    }
+}
+
+impl<C, T: FromRequest<C>> FromRequest<C> for Result<T, Rejection> {
+    fn from_request(context: &C) -> Result<Self, Rejection> {
+        Ok(T::from_request(context))
+    }
+}
+
+// Tuples of extractors are extracted in order, failing with the first rejection.
+// Handlers use them to extract all their arguments at once.
+macro_rules! impl_from_request_tuple {
+    ($($ty:ident),*) => {
+        impl<C, $($ty: FromRequest<C>),*> FromRequest<C> for ($($ty,)*) {
+            #[allow(unused_variables)]
+            fn from_request(context: &C) -> Result<Self, Rejection> {
+                Ok(($($ty::from_request(context)?,)*))
+            }
+        }
+    };
+}
+
+impl_from_request_tuple!();
+impl_from_request_tuple!(E1);
+impl_from_request_tuple!(E1, E2);
+impl_from_request_tuple!(E1, E2, E3);
+impl_from_request_tuple!(E1, E2, E3, E4);
+
+/// Reason of a rejected request: a failed extraction of the matched route,
+/// or an invalid path param of a route that would match otherwise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    /// A path param can't be converted. Routes with invalid params count as non-matching,
+    /// so this is passed on only if no other route matches
+    InvalidParam(ParamError),
+    /// A required header is missing
+    MissingHeader(&'static str),
+    /// A header is not valid utf-8
+    InvalidHeader(&'static str),
+    /// The query string can't be deserialized
+    InvalidQuery(String),
+    /// The request body has an unexpected content type, e.g. not `application/json`
+    UnsupportedMediaType(String),
+    /// None of the routes produces a media type accepted by the `Accept` header
+    NotAcceptable(String),
+    /// The request body can't be deserialized
+    InvalidBody(String),
+    /// The request method can't be represented as `Method`
+    InvalidMethod(InvalidMethod),
+    /// The client didn't ask for an API version, see `ApiVersion`
+    MissingApiVersion,
+    /// Rejection of a user defined extractor
+    Custom { status: u16, message: String },
+}
+
+impl Rejection {
+    /// Http status of the response for this rejection
+    pub fn status(&self) -> u16 {
+        match *self {
+            Rejection::InvalidParam(_)
+            | Rejection::MissingHeader(_)
+            | Rejection::InvalidHeader(_)
+            | Rejection::InvalidQuery(_)
+            | Rejection::InvalidBody(_)
+            | Rejection::MissingApiVersion => 400,
+            Rejection::NotAcceptable(_) => 406,
+            Rejection::UnsupportedMediaType(_) => 415,
+            Rejection::InvalidMethod(_) => 501,
+            Rejection::Custom { status, .. } => status,
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Rejection::InvalidParam(ref e) => write!(f, "{}", e),
+            Rejection::MissingHeader(name) => write!(f, "Missing header `{}`", name),
+            Rejection::InvalidHeader(name) => write!(f, "Invalid header `{}`", name),
+            Rejection::InvalidQuery(ref e) => write!(f, "Invalid query: {}", e),
+            Rejection::UnsupportedMediaType(ref content_type) => {
+                write!(f, "Unsupported content type `{}`", content_type)
+            }
+            Rejection::NotAcceptable(ref accept) => {
+                write!(f, "No media type matches `{}`", accept)
+            }
+            Rejection::InvalidBody(ref e) => write!(f, "Invalid body: {}", e),
+            Rejection::InvalidMethod(ref e) => write!(f, "{}", e),
+            Rejection::MissingApiVersion => f.write_str("Missing API version"),
+            Rejection::Custom { ref message, .. } => f.write_str(message),
+        }
+    }
+}
+
+impl Error for Rejection {}
+
+/// Name of a header extracted with `Header<N>`
+///
+/// ```rust
+/// struct RequestId;
+///
+/// impl NamedHeader for RequestId {
+///     const NAME: &'static str = "x-request-id";
+/// }
+/// ```
+#[cfg(feature = "with_http")]
+pub trait NamedHeader {
+    /// Header name, e.g. `x-request-id`
+    const NAME: &'static str;
+}
+
+/// Value of a required header. Use `Option<Header<N>>` for optional ones.
+#[cfg(feature = "with_http")]
+pub struct Header<N> {
+    value: String,
+    name: PhantomData<N>,
+}
+
+// not derived, so that header names don't need to implement anything
+#[cfg(feature = "with_http")]
+impl<N> Clone for Header<N> {
+    fn clone(&self) -> Self {
+        Header {
+            value: self.value.clone(),
+            name: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "with_http")]
+impl<N> PartialEq for Header<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+#[cfg(feature = "with_http")]
+impl<N> Eq for Header<N> {}
+
+#[cfg(feature = "with_http")]
+impl<N: NamedHeader> fmt::Debug for Header<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Header({}: {:?})", N::NAME, self.value)
+    }
+}
+
+#[cfg(feature = "with_http")]
+impl<N> Header<N> {
+    /// Header value
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Converts into the header value
+    pub fn into_inner(self) -> String {
+        self.value
+    }
+}
+
+#[cfg(feature = "with_http")]
+impl<N> Deref for Header<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.value
+    }
+}
+
+#[cfg(feature = "with_http")]
+impl<S, B, N: NamedHeader> FromRequest<RequestContext<S, B>> for Header<N> {
+    fn from_request(context: &RequestContext<S, B>) -> Result<Self, Rejection> {
+        let value = context
+            .headers()
+            .get(N::NAME)
+            .ok_or(Rejection::MissingHeader(N::NAME))?;
+        let value = value
+            .to_str()
+            .map_err(|_| Rejection::InvalidHeader(N::NAME))?;
+        Ok(Header {
+            value: value.to_string(),
+            name: PhantomData,
+        })
+    }
+}
+
+/// Request body as is, e.g. `RawBody<Bytes>` for `HyperContext`
+#[cfg(feature = "with_http")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawBody<B>(pub B);
+
+#[cfg(feature = "with_http")]
+impl<S, B: Clone> FromRequest<RequestContext<S, B>> for RawBody<B> {
+    fn from_request(context: &RequestContext<S, B>) -> Result<Self, Rejection> {
+        Ok(RawBody(context.body().clone()))
+    }
+}
+
+/// Request method. Note that for handlers added with `RouterBuilder::route`, a `Method` argument
+/// is ambiguous with a path param. Use `route_methods` there, which passes the method explicitly.
+#[cfg(feature = "with_http")]
+impl<S, B> FromRequest<RequestContext<S, B>> for Method {
+    fn from_request(context: &RequestContext<S, B>) -> Result<Self, Rejection> {
+        Method::try_from(context.method()).map_err(Rejection::InvalidMethod)
+    }
+}
+
+/// Query string deserialized with `serde_urlencoded`, e.g. `Query<Pagination>` for `?page=2&limit=10`
+#[cfg(feature = "with_serde")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query<T>(pub T);
+
+#[cfg(feature = "with_serde")]
+impl<S, B, T: DeserializeOwned> FromRequest<RequestContext<S, B>> for Query<T> {
+    fn from_request(context: &RequestContext<S, B>) -> Result<Self, Rejection> {
+        serde_urlencoded::from_str(context.query().unwrap_or(""))
+            .map(Query)
+            .map_err(|e| Rejection::InvalidQuery(e.to_string()))
+    }
+}
+
+/// Json body of a request with `Content-Type: application/json`
+#[cfg(feature = "with_serde")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Json<T>(pub T);
+
+#[cfg(feature = "with_serde")]
+impl<S, B: AsRef<[u8]>, T: DeserializeOwned> FromRequest<RequestContext<S, B>> for Json<T> {
+    fn from_request(context: &RequestContext<S, B>) -> Result<Self, Rejection> {
+        let content_type = context
+            .headers()
+            .get("content-type")
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("");
+        let mime = content_type.split(';').next().unwrap_or("").trim();
+        if mime != "application/json" && !mime.ends_with("+json") {
+            return Err(Rejection::UnsupportedMediaType(content_type.to_string()));
+        }
+        serde_json::from_slice(context.body().as_ref())
+            .map(Json)
+            .map_err(|e| Rejection::InvalidBody(e.to_string()))
+    }
+}
+
+#[cfg(all(test, feature = "with_serde"))]
+mod tests {
+    use super::*;
+    use http::Request;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Pagination {
+        page: u32,
+        limit: Option<u32>,
+    }
+
+    struct RequestId;
+
+    impl NamedHeader for RequestId {
+        const NAME: &'static str = "x-request-id";
+    }
+
+    fn context(uri: &str, content_type: &str, body: &str) -> RequestContext<(), String> {
+        let request = Request::builder()
+            .method("PROPFIND")
+            .uri(uri)
+            .header("content-type", content_type)
+            .header("x-request-id", "42")
+            .body(body.to_string())
+            .unwrap();
+        RequestContext::new((), request)
+    }
+
+    #[test]
+    fn test_extractors() {
+        let context = context("/users?page=2", "application/json; charset=utf-8", "[1]");
+        assert_eq!(
+            Query::from_request(&context),
+            Ok(Query(Pagination {
+                page: 2,
+                limit: None
+            }))
+        );
+        assert_eq!(Json::from_request(&context), Ok(Json(vec![1])));
+        assert_eq!(
+            Header::<RequestId>::from_request(&context).unwrap().value(),
+            "42"
+        );
+        assert_eq!(
+            RawBody::from_request(&context),
+            Ok(RawBody("[1]".to_string()))
+        );
+        assert_eq!(
+            Method::from_request(&context),
+            Ok(Method::extension("PROPFIND"))
+        );
+    }
+
+    #[test]
+    fn test_rejections() {
+        let context = context("/users?page=x", "text/plain", "[1]");
+        let rejection = Query::<Pagination>::from_request(&context).unwrap_err();
+        assert_eq!(rejection.status(), 400);
+        let rejection = Json::<Vec<u32>>::from_request(&context).unwrap_err();
+        assert_eq!(
+            rejection,
+            Rejection::UnsupportedMediaType("text/plain".to_string())
+        );
+        assert_eq!(rejection.status(), 415);
+        let context = super::tests::context("/", "application/problem+json", "{");
+        match Json::<Vec<u32>>::from_request(&context) {
+            Err(Rejection::InvalidBody(_)) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        struct Missing;
+        impl NamedHeader for Missing {
+            const NAME: &'static str = "x-missing";
+        }
+        assert_eq!(
+            Header::<Missing>::from_request(&context),
+            Err(Rejection::MissingHeader("x-missing"))
+        );
+        assert_eq!(Option::<Header<Missing>>::from_request(&context), Ok(None));
+        assert_eq!(
+            Rejection::MissingHeader("x-missing").to_string(),
+            "Missing header `x-missing`"
+        );
+    }
+}
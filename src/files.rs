@@ -0,0 +1,887 @@
+use date::{days_from_civil, http_date, MONTHS};
+use handler::BoxFuture;
+use headers::RequestHeaders;
+use http::header::{
+    HeaderName, ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, LAST_MODIFIED,
+};
+use http::{HeaderValue, Response, StatusCode};
+use method::Method;
+use std::fs::{self, File, Metadata};
+use std::future::{ready, Future};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Default of `StaticFiles::max_size`
+const DEFAULT_MAX_SIZE: u64 = 16 * 1024 * 1024;
+
+// Default of `StaticFiles::threads`
+const DEFAULT_THREADS: usize = 4;
+
+// Request headers that `StaticFiles` reads
+const FILE_HEADERS: [&str; 4] = ["range", "if-range", "if-none-match", "if-modified-since"];
+
+/// Serves the files of a directory from a tail route, e.g. `GET | HEAD /assets/{*path}`
+/// (requires the `static_files` feature).
+///
+/// ```rust
+/// let assets = StaticFiles::new("./public").index("index.html");
+/// let router = Router::builder()
+///     .files("/assets", assets)
+///     .fallback(not_found)
+///     .build();
+/// ```
+///
+/// - paths are percent-decoded, and paths with `..` segments are forbidden (403).
+///   Files that resolve to a place outside of the directory, e.g. through symlinks, are forbidden too
+/// - `Content-Type` is detected by the file extension
+/// - responses have `ETag` and `Last-Modified`, and conditional requests get 304 Not Modified
+/// - a single `Range` gets 206 Partial Content, or 416 if it can't be satisfied,
+///   and `If-Range` is honoured. Requests with several ranges get the whole file
+/// - bodies are read into memory, up to `max_size` bytes (16 MiB by default). Larger files
+///   are served by ranges only: a range gets at most `max_size` bytes, and `GET` requests
+///   for the whole file are forbidden (403)
+///
+/// The file is read with blocking `std::fs` calls. Async routers should serve it with
+/// `serve_async` (or `RouterBuilder::files_async`), which does the I/O on a few worker threads.
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    root: PathBuf,
+    index: Option<String>,
+    fallback: Option<String>,
+    max_size: u64,
+    // shared by the clones, e.g. of the routes serving the files
+    workers: Arc<Workers>,
+}
+
+impl StaticFiles {
+    /// Serves the files under `root`
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        StaticFiles {
+            root: root.into(),
+            index: None,
+            fallback: None,
+            max_size: DEFAULT_MAX_SIZE,
+            workers: Arc::new(Workers::new(DEFAULT_THREADS)),
+        }
+    }
+
+    /// Serves `file` of a directory for requests to the directory, e.g. `index.html`.
+    /// Without an index, directories are not found.
+    pub fn index(mut self, file: &str) -> Self {
+        self.index = Some(file.to_string());
+        self
+    }
+
+    /// Serves `file`, relative to the root, for paths that don't exist,
+    /// e.g. `index.html` of a single page app that does its own routing
+    pub fn fallback(mut self, file: &str) -> Self {
+        self.fallback = Some(file.to_string());
+        self
+    }
+
+    /// Sets the most bytes a response reads into memory, 16 MiB by default.
+    /// Files that are larger are served by ranges of up to `max_size` bytes only.
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size = bytes;
+        self
+    }
+
+    /// Sets the number of threads that read files for `serve_async`, 4 by default.
+    /// The threads are started by the first request, and requests wait for a free one.
+    ///
+    /// ### Panics
+    /// If `threads` is 0.
+    pub fn threads(mut self, threads: usize) -> Self {
+        assert!(threads > 0, "StaticFiles need at least one thread");
+        self.workers = Arc::new(Workers::new(threads));
+        self
+    }
+
+    /// Serves the file at the raw `path` relative to the root, e.g. the tail param of a route.
+    /// `HEAD` requests get the headers only.
+    pub fn serve<C: RequestHeaders>(
+        &self,
+        context: &C,
+        method: Method,
+        path: &str,
+    ) -> FileResponse {
+        let relative = match relative_path(path) {
+            Some(relative) => relative,
+            None => return FileResponse::status(StatusCode::FORBIDDEN),
+        };
+        let found = match self.open(&self.root.join(relative)) {
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => match self.fallback {
+                Some(ref fallback) => self.open(&self.root.join(fallback)),
+                None => return FileResponse::status(StatusCode::NOT_FOUND),
+            },
+            found => found,
+        };
+        match found.and_then(|(file, path, metadata)| {
+            respond(context, method, file, &path, &metadata, self.max_size)
+        }) {
+            Ok(response) => response,
+            Err(error) => FileResponse::error(&error),
+        }
+    }
+
+    /// Same as `serve`, but the file is read on a worker thread (see `threads`),
+    /// so the future doesn't block the executor of an async router
+    pub fn serve_async<C, T>(&self, context: &C, method: Method, path: &str) -> BoxFuture<T>
+    where
+        C: RequestHeaders,
+        T: From<FileResponse> + Send + 'static,
+    {
+        let headers = FileHeaders(
+            FILE_HEADERS
+                .iter()
+                .filter_map(|name| Some((*name, context.header(name)?.to_string())))
+                .collect(),
+        );
+        let (files, path) = (self.clone(), path.to_string());
+        Box::pin(
+            self.workers
+                .run(move || T::from(files.serve(&headers, method, &path))),
+        )
+    }
+
+    // Opens a file under the root, or the index of a directory
+    fn open(&self, path: &Path) -> io::Result<(File, PathBuf, Metadata)> {
+        let root = fs::canonicalize(&self.root)?;
+        let mut path = fs::canonicalize(path)?;
+        if !path.starts_with(&root) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "Path is outside of the root",
+            ));
+        }
+        if path.is_dir() {
+            match self.index {
+                Some(ref index) => path.push(index),
+                None => return Err(io::ErrorKind::NotFound.into()),
+            }
+        }
+        let file = File::open(&path)?;
+        let metadata = file.metadata()?;
+        if !metadata.is_file() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Ok((file, path, metadata))
+    }
+}
+
+/// Response of `StaticFiles`.
+///
+/// Routers return it through `From<FileResponse>`, which is implemented for `http::Response<B>`
+/// with `B: From<Vec<u8>>` (e.g. hyper bodies), for `Result<T, E>` and for `BoxFuture<T>`
+/// (given `T: From<FileResponse>`).
+#[derive(Debug)]
+pub struct FileResponse {
+    response: Response<Vec<u8>>,
+}
+
+impl FileResponse {
+    fn status(status: StatusCode) -> Self {
+        let mut response = Response::new(Vec::new());
+        *response.status_mut() = status;
+        response
+            .headers_mut()
+            .insert(CONTENT_LENGTH, HeaderValue::from(0));
+        FileResponse { response }
+    }
+
+    fn error(error: &io::Error) -> Self {
+        FileResponse::status(match error.kind() {
+            io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+            io::ErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        })
+    }
+
+    /// The response, e.g. for tests
+    pub fn response(&self) -> &Response<Vec<u8>> {
+        &self.response
+    }
+
+    /// Converts into the response
+    pub fn into_response(self) -> Response<Vec<u8>> {
+        self.response
+    }
+}
+
+impl<B: From<Vec<u8>>> From<FileResponse> for Response<B> {
+    fn from(response: FileResponse) -> Self {
+        response.response.map(B::from)
+    }
+}
+
+impl<T: From<FileResponse>, E> From<FileResponse> for Result<T, E> {
+    fn from(response: FileResponse) -> Self {
+        Ok(T::from(response))
+    }
+}
+
+impl<T: From<FileResponse> + Send + 'static> From<FileResponse> for BoxFuture<T> {
+    fn from(response: FileResponse) -> Self {
+        Box::pin(ready(T::from(response)))
+    }
+}
+
+fn respond<C: RequestHeaders>(
+    context: &C,
+    method: Method,
+    mut file: File,
+    path: &Path,
+    metadata: &Metadata,
+    max_size: u64,
+) -> io::Result<FileResponse> {
+    let len = metadata.len();
+    let modified = metadata.modified().ok().map(unix_seconds);
+    let etag = format!("\"{:x}-{:x}\"", len, modified.unwrap_or(0));
+    let mut response = FileResponse::status(StatusCode::OK);
+    insert_header(&mut response, ETAG, &etag);
+    if let Some(modified) = modified {
+        insert_header(&mut response, LAST_MODIFIED, &http_date(modified));
+    }
+    if is_not_modified(context, &etag, modified) {
+        // a 304 has no body, and its `Content-Length` would describe the file
+        *response.response.status_mut() = StatusCode::NOT_MODIFIED;
+        response.response.headers_mut().remove(CONTENT_LENGTH);
+        return Ok(response);
+    }
+    insert_header(&mut response, CONTENT_TYPE, mime_type(path));
+    insert_header(&mut response, ACCEPT_RANGES, "bytes");
+    let range = match context.header("range") {
+        Some(range) if is_range_fresh(context, &etag, modified) => parse_range(range, len),
+        _ => None,
+    };
+    let (start, end) = match range {
+        // `HEAD` reads no body
+        None if len > max_size && method != Method::HEAD => {
+            return Ok(FileResponse::status(StatusCode::FORBIDDEN))
+        }
+        None if len == 0 => (0, 0),
+        None => (0, len - 1),
+        Some(Ok((start, end))) => {
+            // a shorter range than requested is still valid, `Content-Range` tells which
+            let end = end.min(start.saturating_add(max_size.max(1)) - 1);
+            *response.response.status_mut() = StatusCode::PARTIAL_CONTENT;
+            let content_range = format!("bytes {}-{}/{}", start, end, len);
+            insert_header(&mut response, CONTENT_RANGE, &content_range);
+            (start, end)
+        }
+        Some(Err(())) => {
+            let mut response = FileResponse::status(StatusCode::RANGE_NOT_SATISFIABLE);
+            insert_header(&mut response, CONTENT_RANGE, &format!("bytes */{}", len));
+            return Ok(response);
+        }
+    };
+    let count = if len == 0 { 0 } else { end - start + 1 };
+    insert_header(&mut response, CONTENT_LENGTH, &count.to_string());
+    if method != Method::HEAD {
+        let body = response.response.body_mut();
+        file.seek(SeekFrom::Start(start))?;
+        file.take(count).read_to_end(body)?;
+    }
+    Ok(response)
+}
+
+// Headers of a request that `serve_async` moves to the thread that reads the file
+struct FileHeaders(Vec<(&'static str, String)>);
+
+impl RequestHeaders for FileHeaders {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|header| header.0 == name)
+            .map(|header| header.1.as_str())
+    }
+}
+
+// Result of a blocking call on a worker thread, and the waker of the task that awaits it
+type BlockingState<T> = Arc<Mutex<(Option<T>, Option<Waker>)>>;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// Threads reading files for `serve_async`. They are started by the first request
+// and stop once all of the clones of `StaticFiles` are dropped
+#[derive(Debug)]
+struct Workers {
+    threads: usize,
+    // `None` if no thread could be spawned
+    jobs: OnceLock<Option<Mutex<Sender<Job>>>>,
+}
+
+impl Workers {
+    fn new(threads: usize) -> Self {
+        Workers {
+            threads,
+            jobs: OnceLock::new(),
+        }
+    }
+
+    // Runs `serve` on a worker thread, a panic or a failure to start the threads answer 500
+    fn run<T, F>(&self, serve: F) -> Blocking<T>
+    where
+        T: From<FileResponse> + Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let state: BlockingState<T> = Arc::new(Mutex::new((None, None)));
+        let shared = state.clone();
+        let job: Job = Box::new(move || {
+            let response = panic::catch_unwind(AssertUnwindSafe(serve))
+                .unwrap_or_else(|_| FileResponse::status(StatusCode::INTERNAL_SERVER_ERROR).into());
+            let mut state = shared.lock().unwrap_or_else(|error| error.into_inner());
+            state.0 = Some(response);
+            if let Some(waker) = state.1.take() {
+                waker.wake();
+            }
+        });
+        let sent = match *self.jobs.get_or_init(|| self.start()) {
+            Some(ref jobs) => jobs
+                .lock()
+                .unwrap_or_else(|error| error.into_inner())
+                .send(job)
+                .is_ok(),
+            None => false,
+        };
+        if !sent {
+            let response = FileResponse::status(StatusCode::INTERNAL_SERVER_ERROR).into();
+            state.lock().unwrap_or_else(|error| error.into_inner()).0 = Some(response);
+        }
+        Blocking(state)
+    }
+
+    fn start(&self) -> Option<Mutex<Sender<Job>>> {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let mut spawned = 0;
+        for _ in 0..self.threads {
+            let receiver = receiver.clone();
+            let thread = thread::Builder::new()
+                .name("http_router-files".to_string())
+                .spawn(move || loop {
+                    let job = receiver
+                        .lock()
+                        .unwrap_or_else(|error| error.into_inner())
+                        .recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                });
+            spawned += thread.is_ok() as usize;
+        }
+        if spawned == 0 {
+            return None;
+        }
+        Some(Mutex::new(sender))
+    }
+}
+
+struct Blocking<T>(BlockingState<T>);
+
+impl<T> Future for Blocking<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+        let mut state = self.0.lock().unwrap_or_else(|error| error.into_inner());
+        match state.0.take() {
+            Some(response) => Poll::Ready(response),
+            None => {
+                state.1 = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn insert_header(response: &mut FileResponse, name: HeaderName, value: &str) {
+    if let Ok(value) = HeaderValue::from_str(value) {
+        response.response.headers_mut().insert(name, value);
+    }
+}
+
+// Percent-decoded path relative to the root, `None` if it could escape the root
+fn relative_path(path: &str) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for segment in path.split('/') {
+        let segment = percent_decode(segment)?;
+        match segment.as_str() {
+            "" | "." => continue,
+            ".." => return None,
+            // encoded separators, and drive letters or separators on windows
+            _ if segment.contains(['/', '\\', ':', '\0']) => return None,
+            _ => relative.push(segment),
+        }
+    }
+    Some(relative)
+}
+
+// `None` if the encoding is invalid or the result is not utf-8
+fn percent_decode(segment: &str) -> Option<String> {
+    if !segment.contains('%') {
+        return Some(segment.to_string());
+    }
+    let mut bytes = Vec::with_capacity(segment.len());
+    let mut iter = segment.bytes();
+    while let Some(byte) = iter.next() {
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+        let high = (iter.next()? as char).to_digit(16)?;
+        let low = (iter.next()? as char).to_digit(16)?;
+        bytes.push((high * 16 + low) as u8);
+    }
+    String::from_utf8(bytes).ok()
+}
+
+// Conditional request that the client's copy satisfies. `If-Modified-Since`
+// is ignored if `If-None-Match` is present
+fn is_not_modified<C: RequestHeaders>(context: &C, etag: &str, modified: Option<u64>) -> bool {
+    if let Some(if_none_match) = context.header("if-none-match") {
+        let etag = etag.trim_start_matches("W/");
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag);
+    }
+    match (
+        context
+            .header("if-modified-since")
+            .and_then(parse_http_date),
+        modified,
+    ) {
+        (Some(since), Some(modified)) => modified <= since,
+        _ => false,
+    }
+}
+
+// Whether the `Range` applies: there is no `If-Range`, or it has the current
+// entity tag or a date the file wasn't modified since
+fn is_range_fresh<C: RequestHeaders>(context: &C, etag: &str, modified: Option<u64>) -> bool {
+    let if_range = match context.header("if-range") {
+        Some(if_range) => if_range.trim(),
+        None => return true,
+    };
+    if if_range.starts_with('"') {
+        return if_range == etag;
+    }
+    match (parse_http_date(if_range), modified) {
+        (Some(date), Some(modified)) => modified <= date,
+        _ => false,
+    }
+}
+
+// Inclusive range of bytes of a file with `len` bytes: `None` if the header is invalid
+// or has several ranges, so the whole file is served, `Err` if it can't be satisfied
+fn parse_range(range: &str, len: u64) -> Option<Result<(u64, u64), ()>> {
+    let range = range.trim();
+    if !range.starts_with("bytes=") || range.contains(',') {
+        return None;
+    }
+    let mut bounds = range["bytes=".len()..].splitn(2, '-');
+    let start = bounds.next()?.trim();
+    let end = bounds.next()?.trim();
+    let (start, end) = match (start, end) {
+        ("", "") => return None,
+        ("", suffix) => match suffix.parse::<u64>().ok()? {
+            0 => return Some(Err(())),
+            suffix => (len.saturating_sub(suffix), len.saturating_sub(1)),
+        },
+        (start, "") => (start.parse().ok()?, len.saturating_sub(1)),
+        (start, end) => {
+            let (start, end): (u64, u64) = (start.parse().ok()?, end.parse().ok()?);
+            if end < start {
+                return None;
+            }
+            (start, end.min(len.saturating_sub(1)))
+        }
+    };
+    match start < len {
+        true => Some(Ok((start, end))),
+        false => Some(Err(())),
+    }
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref().unwrap_or("") {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "xml" => "application/xml",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_secs()
+}
+
+// Seconds since the epoch of a date in the IMF-fixdate format, the only one clients should send
+fn parse_http_date(date: &str) -> Option<u64> {
+    let mut parts = date.split_whitespace();
+    let _weekday = parts.next()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|name| *name == month)? as u32 + 1;
+    let year: i64 = parts.next()?.parse().ok()?;
+    let mut time = parts.next()?.splitn(3, ':');
+    let hours: u64 = time.next()?.parse().ok()?;
+    let minutes: u64 = time.next()?.parse().ok()?;
+    let seconds: u64 = time.next()?.parse().ok()?;
+    if parts.next()? != "GMT" || day == 0 || day > 31 || hours > 23 || minutes > 59 || seconds > 60
+    {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    if days < 0 {
+        return None;
+    }
+    Some(days as u64 * 86400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use router::Router;
+    use std::process;
+
+    #[derive(Clone)]
+    struct Headers(Vec<(&'static str, String)>);
+
+    impl RequestHeaders for Headers {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.0
+                .iter()
+                .find(|header| header.0.eq_ignore_ascii_case(name))
+                .map(|header| header.1.as_str())
+        }
+    }
+
+    // Temporary directory with the files of a test, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("http_router_{}_{}", name, process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(path.join("docs")).unwrap();
+            fs::write(path.join("index.html"), "<h1>Home</h1>").unwrap();
+            fs::write(path.join("docs/a b.txt"), "0123456789").unwrap();
+            fs::write(path.join("docs/style.CSS"), "body {}").unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn get(
+        files: &StaticFiles,
+        path: &str,
+        headers: Vec<(&'static str, String)>,
+    ) -> Response<Vec<u8>> {
+        files
+            .serve(&Headers(headers), Method::GET, path)
+            .into_response()
+    }
+
+    #[test]
+    fn test_serve() {
+        let dir = TempDir::new("serve");
+        let files = StaticFiles::new(&dir.0);
+        let response = get(&files, "docs/a%20b.txt", vec![]);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), b"0123456789");
+        assert_eq!(
+            response.headers()[CONTENT_TYPE],
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(response.headers()[CONTENT_LENGTH], "10");
+        assert_eq!(response.headers()[ACCEPT_RANGES], "bytes");
+        assert!(response.headers().contains_key(LAST_MODIFIED));
+        assert_eq!(
+            get(&files, "/docs/./style.CSS", vec![]).headers()[CONTENT_TYPE],
+            "text/css; charset=utf-8"
+        );
+
+        let head = files
+            .serve(&Headers(vec![]), Method::HEAD, "index.html")
+            .into_response();
+        assert_eq!(head.status(), StatusCode::OK);
+        assert_eq!(head.headers()[CONTENT_LENGTH], "13");
+        assert!(head.body().is_empty());
+
+        for path in &[
+            "../secret",
+            "docs/../../secret",
+            "docs/%2e%2e/index.html",
+            "docs%2F..%2Findex.html",
+            "%zz",
+        ] {
+            assert_eq!(
+                get(&files, path, vec![]).status(),
+                StatusCode::FORBIDDEN,
+                "{}",
+                path
+            );
+        }
+        assert_eq!(
+            get(&files, "missing.txt", vec![]).status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(get(&files, "docs", vec![]).status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_index_and_fallback() {
+        let dir = TempDir::new("index");
+        let files = StaticFiles::new(&dir.0).index("index.html");
+        assert_eq!(get(&files, "", vec![]).body(), b"<h1>Home</h1>");
+        assert_eq!(get(&files, "docs/", vec![]).status(), StatusCode::NOT_FOUND);
+
+        let files = StaticFiles::new(&dir.0).fallback("index.html");
+        let response = get(&files, "users/12", vec![]);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+        assert_eq!(
+            get(&files, "../users", vec![]).status(),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[test]
+    fn test_conditional_requests() {
+        let dir = TempDir::new("conditional");
+        let files = StaticFiles::new(&dir.0);
+        let response = get(&files, "index.html", vec![]);
+        let etag = response.headers()[ETAG].to_str().unwrap().to_string();
+        let modified = response.headers()[LAST_MODIFIED]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let response = get(
+            &files,
+            "index.html",
+            vec![("if-none-match", format!("\"x\", W/{}", etag))],
+        );
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(response.body().is_empty());
+        assert!(!response.headers().contains_key(CONTENT_LENGTH));
+        let response = get(
+            &files,
+            "index.html",
+            vec![("if-none-match", "\"x\"".to_string())],
+        );
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = get(&files, "index.html", vec![("if-modified-since", modified)]);
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        let since = "Thu, 01 Jan 1970 00:00:00 GMT".to_string();
+        let response = get(&files, "index.html", vec![("if-modified-since", since)]);
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_ranges() {
+        let dir = TempDir::new("ranges");
+        let files = StaticFiles::new(&dir.0);
+        let range = |range: &str| get(&files, "docs/a b.txt", vec![("range", range.to_string())]);
+
+        let response = range("bytes=2-4");
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.body(), b"234");
+        assert_eq!(response.headers()[CONTENT_RANGE], "bytes 2-4/10");
+        assert_eq!(response.headers()[CONTENT_LENGTH], "3");
+        assert_eq!(range("bytes=7-").body(), b"789");
+        assert_eq!(range("bytes=-2").body(), b"89");
+        assert_eq!(range("bytes=8-100").body(), b"89");
+        assert_eq!(range("bytes=0-1, 4-5").status(), StatusCode::OK);
+        assert_eq!(range("items=0-1").status(), StatusCode::OK);
+        let response = range("bytes=10-");
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[CONTENT_RANGE], "bytes */10");
+
+        let etag = range("").headers()[ETAG].to_str().unwrap().to_string();
+        let if_range = |if_range: String| {
+            let headers = vec![("range", "bytes=0-0".to_string()), ("if-range", if_range)];
+            get(&files, "docs/a b.txt", headers).status()
+        };
+        assert_eq!(if_range(etag), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(if_range("\"stale\"".to_string()), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_max_size() {
+        let dir = TempDir::new("max_size");
+        let files = StaticFiles::new(&dir.0).max_size(4);
+        let range = |range: &str| get(&files, "docs/a b.txt", vec![("range", range.to_string())]);
+        assert_eq!(range("").status(), StatusCode::FORBIDDEN);
+        let response = range("bytes=2-");
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.body(), b"2345");
+        assert_eq!(response.headers()[CONTENT_RANGE], "bytes 2-5/10");
+        assert_eq!(response.headers()[CONTENT_LENGTH], "4");
+        assert_eq!(range("bytes=-3").body(), b"789");
+        assert_eq!(
+            get(&files, "docs/style.CSS", vec![]).status(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            get(&files, "index.html", vec![]).status(),
+            StatusCode::FORBIDDEN
+        );
+        let response = files.serve(&Headers(vec![]), Method::HEAD, "index.html");
+        assert_eq!(response.response().status(), StatusCode::OK);
+        assert_eq!(response.response().headers()[CONTENT_LENGTH], "13");
+        assert!(response.response().body().is_empty());
+        let files = StaticFiles::new(&dir.0).max_size(13);
+        assert_eq!(get(&files, "index.html", vec![]).status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_serve_async() {
+        let dir = TempDir::new("serve_async");
+        let files = StaticFiles::new(&dir.0);
+        let headers = Headers(vec![("Range", "bytes=0-2".to_string())]);
+        let response: FileResponse =
+            futures::executor::block_on(files.serve_async(&headers, Method::GET, "docs/a b.txt"));
+        assert_eq!(response.response().status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.response().body(), b"012");
+
+        let router = Router::builder()
+            .files_async("/assets", files)
+            .fallback_async(|_: Headers| ready(Response::new(b"404".to_vec())))
+            .build();
+        let handle =
+            |path| futures::executor::block_on(router.handle(Headers(vec![]), Method::GET, path));
+        assert_eq!(handle("/assets/index.html").body(), b"<h1>Home</h1>");
+        assert_eq!(handle("/assets/missing").status(), StatusCode::NOT_FOUND);
+        assert_eq!(handle("/other").body(), b"404");
+
+        // more requests than threads wait for a free one
+        let files = StaticFiles::new(&dir.0).threads(2);
+        let responses: Vec<BoxFuture<FileResponse>> = (0..8)
+            .map(|_| files.serve_async(&Headers(vec![]), Method::GET, "index.html"))
+            .collect();
+        for response in responses {
+            let response = futures::executor::block_on(response);
+            assert_eq!(response.response().body(), b"<h1>Home</h1>");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "StaticFiles need at least one thread")]
+    fn test_no_threads() {
+        let _ = StaticFiles::new(".").threads(0);
+    }
+
+    #[test]
+    fn test_router() {
+        let dir = TempDir::new("router");
+        let not_found = |_: &Headers| {
+            let mut response = Response::new(Vec::new());
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            response
+        };
+        let router = Router::builder()
+            .files("/assets/", StaticFiles::new(&dir.0).index("index.html"))
+            .fallback(not_found)
+            .build();
+        let handle = |method, path| router.handle(Headers(vec![]), method, path);
+        assert_eq!(
+            handle(Method::GET, "/assets/docs/a%20b.txt").body(),
+            b"0123456789"
+        );
+        assert_eq!(handle(Method::GET, "/assets/").body(), b"<h1>Home</h1>");
+        assert_eq!(
+            handle(Method::HEAD, "/assets/index.html").status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            handle(Method::GET, "/assets/../Cargo.toml").status(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            handle(Method::GET, "/assets").status(),
+            StatusCode::NOT_FOUND
+        );
+        let routes = router.routes();
+        assert_eq!(routes[0].path, "/assets/{*path}");
+        assert_eq!(routes[0].handler, "files");
+
+        // mounted under a prefix with params, e.g. assets of a tenant
+        let assets = Router::builder()
+            .files("/assets", StaticFiles::new(&dir.0))
+            .fallback(not_found)
+            .build();
+        let router = Router::builder()
+            .mount("/tenants/{tenant}", assets)
+            .files("/{version}/static", StaticFiles::new(&dir.0))
+            .fallback(not_found)
+            .build();
+        let handle = |path| router.handle(Headers(vec![]), Method::GET, path);
+        assert_eq!(
+            handle("/tenants/acme/assets/index.html").body(),
+            b"<h1>Home</h1>"
+        );
+        assert_eq!(handle("/v1/static/index.html").body(), b"<h1>Home</h1>");
+        let routes = router.routes();
+        assert_eq!(routes[0].path, "/tenants/{tenant}/assets/{*path}");
+        assert_eq!(routes[0].params[0].ty, "&str");
+    }
+
+    #[test]
+    fn test_parse_http_date() {
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(784111777)
+        );
+        assert_eq!(
+            parse_http_date("Tue, 29 Feb 2000 00:00:00 GMT"),
+            Some(951782400)
+        );
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+    }
+}
@@ -0,0 +1,55 @@
+use extract::Rejection;
+
+/// Outcome of a route guard, e.g. `GET /admin/users => list_users if is_admin`.
+///
+/// Guards run once the path of a route matches, before its params are converted,
+/// so they only see the context. Guard functions return `Guard`, `bool`
+/// (`false` skips the route) or `Result<(), Rejection>`:
+///
+/// ```rust
+/// fn is_admin(context: &Context) -> Guard {
+///     match context.user() {
+///         Some(user) if user.is_admin => Guard::Allow,
+///         Some(_) => Guard::reject(403, "Admins only"),
+///         None => Guard::reject(401, "Not logged in"),
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Guard {
+    /// The route handles the request
+    Allow,
+    /// The route counts as non-matching, later routes are tried
+    Skip,
+    /// Matching stops and the rejection is passed to the rejection handler,
+    /// or to the fallback if there is none
+    Reject(Rejection),
+}
+
+impl Guard {
+    /// Rejects the request with `status`, e.g. `Guard::reject(403, "Admins only")`
+    pub fn reject<M: Into<String>>(status: u16, message: M) -> Self {
+        Guard::Reject(Rejection::Custom {
+            status,
+            message: message.into(),
+        })
+    }
+}
+
+impl From<bool> for Guard {
+    fn from(allow: bool) -> Self {
+        match allow {
+            true => Guard::Allow,
+            false => Guard::Skip,
+        }
+    }
+}
+
+impl From<Result<(), Rejection>> for Guard {
+    fn from(result: Result<(), Rejection>) -> Self {
+        match result {
+            Ok(()) => Guard::Allow,
+            Err(rejection) => Guard::Reject(rejection),
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use extract::{FromRequest, Rejection};
+use method::Method;
+use middleware::{Chain, Next};
+use param::{FromParam, ParamError, ParamOf};
+use std::any::type_name;
+use std::future::Future;
+use std::pin::Pin;
+
+/// Boxed future, the uniform return type of async routers
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// A function that can be used as a route handler in the `Router` builder.
+///
+/// Implemented for functions and closures taking a context reference followed
+/// by up to 7 typed params and up to 4 extractors, i.e. the same signatures
+/// that `router!` accepts for single method routes.
+/// `Args` is a tuple of param types, their `FromParam` kinds and extractor types,
+/// and only serves to tell the impls apart.
+///
+/// Note that a type which is both `FromParam` and `FromRequest` (e.g. `Method`) makes
+/// the handler ambiguous, use `route_methods` to get the request method.
+pub trait Handler<C, R, Args>: Send + Sync + 'static {
+    /// Type names of the params this handler takes
+    fn param_types(&self) -> Vec<&'static str>;
+
+    /// Converts the raw param at `index` without calling the handler, see `Router::explain`
+    fn check_param(&self, index: usize, param: &str) -> Result<(), ParamError>;
+
+    /// Converts raw params, extracts the rest of the arguments and calls the handler
+    /// through the middleware `chain` of the route.
+    /// Returns `None` if the number of params doesn't match, `Rejection::InvalidParam`
+    /// if a param can't be converted and the rejection of the first failed extractor, if any.
+    fn call(
+        &self,
+        context: &C,
+        params: &[&str],
+        chain: Chain<C, R>,
+    ) -> Option<Result<R, Rejection>>;
+}
+
+/// A handler of a route with several methods (or any method) in the `Router` builder.
+///
+/// Same as `Handler`, but takes the request method right after the context reference,
+/// i.e. the same signatures that `router!` accepts for `GET | HEAD` and `ANY` routes.
+pub trait MethodHandler<C, R, Args>: Send + Sync + 'static {
+    /// Type names of the params this handler takes, not including the method
+    fn param_types(&self) -> Vec<&'static str>;
+
+    /// Converts the raw param at `index` without calling the handler, see `Router::explain`
+    fn check_param(&self, index: usize, param: &str) -> Result<(), ParamError>;
+
+    /// Converts raw params, extracts the rest of the arguments and calls the handler.
+    /// See `Handler::call` for details.
+    fn call(
+        &self,
+        context: &C,
+        method: Method,
+        params: &[&str],
+        chain: Chain<C, R>,
+    ) -> Option<Result<R, Rejection>>;
+}
+
+/// An async handler of a route in the `Router` builder.
+///
+/// Implemented for functions taking the context by value followed by up to
+/// 7 typed params and up to 4 extractors, and returning a future, e.g. `async fn` handlers.
+/// The context is cloned only when the params and extractors of a route succeed.
+pub trait AsyncHandler<C, R, Args>: Send + Sync + 'static {
+    /// Type names of the params this handler takes
+    fn param_types(&self) -> Vec<&'static str>;
+
+    /// Converts the raw param at `index` without calling the handler, see `Router::explain`
+    fn check_param(&self, index: usize, param: &str) -> Result<(), ParamError>;
+
+    /// Converts raw params, extracts the rest of the arguments and calls the handler.
+    /// See `Handler::call` for details.
+    fn call(
+        &self,
+        context: &C,
+        params: &[&str],
+        chain: Chain<C, BoxFuture<R>>,
+    ) -> Option<Result<BoxFuture<R>, Rejection>>;
+}
+
+/// Handler called by `router!` with params already parsed.
+/// This is an implementation detail and *should not* be used directly!
+#[doc(hidden)]
+pub trait MacroHandler<C, P, E, R> {
+    fn call_with_params(&self, context: &C, params: P, chain: Chain<C, R>) -> Result<R, Rejection>;
+}
+
+/// Async handler called by `router!(async ...)` with params already parsed.
+/// This is an implementation detail and *should not* be used directly!
+#[doc(hidden)]
+pub trait AsyncMacroHandler<C, P, E, R> {
+    fn call_with_params(
+        &self,
+        context: &C,
+        params: P,
+        chain: Chain<C, BoxFuture<R>>,
+    ) -> Result<BoxFuture<R>, Rejection>;
+}
+
+// Parsed param or early return of the rejection
+macro_rules! parse_param {
+    ($ty:ident, $kind:ident, $params:ident, $idx:tt) => {
+        match <<$ty as ParamOf<'_, $kind>>::Param as FromParam<$kind>>::from_param($params[$idx]) {
+            Ok(value) => value,
+            Err(e) => {
+                let error = e.at($idx, $params[$idx]);
+                return Some(Err(Rejection::InvalidParam(error)));
+            }
+        }
+    };
+}
+
+// Converts the param at `$index` with the type of the handler param at that position
+macro_rules! check_param {
+    ($index:ident, $param:ident, [$($ty:ident $kind:ident : $idx:tt),*]) => {{
+        $(if $index == $idx {
+            return <<$ty as ParamOf<'_, $kind>>::Param as FromParam<$kind>>::from_param($param)
+                .map(drop)
+                .map_err(|e| e.at($idx, $param));
+        })*
+        Ok(())
+    }};
+}
+
+// Calls the handler as the last step of the middleware chain. Its arguments are
+// moved into `Next`, which can run only once
+macro_rules! call_through {
+    ($chain:ident, $context:ident, ($($arg:ident),*), |$handler_context:ident| $call:expr) => {{
+        let mut args = Some(($($arg),*));
+        $chain($context, Next::new(&mut |$handler_context| {
+            let ($($arg),*) = args.take().expect("Next is run once");
+            $call
+        }))
+    }};
+}
+
+// `$ty` are path params and `$ext` are extractors, so that every arity of
+// params gets impls for every arity of extractors
+macro_rules! impl_handler {
+    ($count:expr; [$($ty:ident $kind:ident : $idx:tt),*]; [$($ext:ident : $ext_idx:tt),*]) => {
+        impl<C, R, F, $($ty,)* $($kind,)* $($ext),*> Handler<C, R, (($($ty,)*), ($($kind,)*), ($($ext,)*))> for F
+        where
+            F: Fn(&C, $($ty,)* $($ext),*) -> R + Send + Sync + 'static,
+            F: for<'p> Fn(&C, $(<$ty as ParamOf<'p, $kind>>::Param,)* $($ext),*) -> R,
+            $($ty: for<'p> ParamOf<'p, $kind>,)*
+            $($ext: FromRequest<C>,)*
+        {
+            fn param_types(&self) -> Vec<&'static str> {
+                vec![$(type_name::<$ty>()),*]
+            }
+
+            #[allow(unused_variables)]
+            fn check_param(&self, index: usize, param: &str) -> Result<(), ParamError> {
+                check_param!(index, param, [$($ty $kind: $idx),*])
+            }
+
+            #[allow(unused_variables)]
+            fn call(&self, context: &C, params: &[&str], chain: Chain<C, R>) -> Option<Result<R, Rejection>> {
+                if params.len() != $count {
+                    return None;
+                }
+                let args = ($(parse_param!($ty, $kind, params, $idx),)*);
+                Some(<($($ext,)*)>::from_request(context).map(|extracted| {
+                    call_through!(chain, context, (args, extracted), |context| {
+                        self(context, $(args.$idx,)* $(extracted.$ext_idx),*)
+                    })
+                }))
+            }
+        }
+
+        impl<C, R, F, $($ty,)* $($kind,)* $($ext),*> MethodHandler<C, R, (($($ty,)*), ($($kind,)*), ($($ext,)*))> for F
+        where
+            F: Fn(&C, Method, $($ty,)* $($ext),*) -> R + Send + Sync + 'static,
+            F: for<'p> Fn(&C, Method, $(<$ty as ParamOf<'p, $kind>>::Param,)* $($ext),*) -> R,
+            $($ty: for<'p> ParamOf<'p, $kind>,)*
+            $($ext: FromRequest<C>,)*
+        {
+            fn param_types(&self) -> Vec<&'static str> {
+                vec![$(type_name::<$ty>()),*]
+            }
+
+            #[allow(unused_variables)]
+            fn check_param(&self, index: usize, param: &str) -> Result<(), ParamError> {
+                check_param!(index, param, [$($ty $kind: $idx),*])
+            }
+
+            #[allow(unused_variables)]
+            fn call(&self, context: &C, method: Method, params: &[&str], chain: Chain<C, R>) -> Option<Result<R, Rejection>> {
+                if params.len() != $count {
+                    return None;
+                }
+                let args = ($(parse_param!($ty, $kind, params, $idx),)*);
+                Some(<($($ext,)*)>::from_request(context).map(|extracted| {
+                    call_through!(chain, context, (args, extracted), |context| {
+                        self(context, method, $(args.$idx,)* $(extracted.$ext_idx),*)
+                    })
+                }))
+            }
+        }
+
+        impl<C, R, F, Fut, $($ty,)* $($kind,)* $($ext),*> AsyncHandler<C, R, (($($ty,)*), ($($kind,)*), ($($ext,)*))> for F
+        where
+            C: Clone,
+            F: Fn(C, $($ty,)* $($ext),*) -> Fut + Send + Sync + 'static,
+            F: for<'p> Fn(C, $(<$ty as ParamOf<'p, $kind>>::Param,)* $($ext),*) -> Fut,
+            Fut: Future<Output = R> + Send + 'static,
+            $($ty: for<'p> ParamOf<'p, $kind>,)*
+            $($ext: FromRequest<C>,)*
+        {
+            fn param_types(&self) -> Vec<&'static str> {
+                vec![$(type_name::<$ty>()),*]
+            }
+
+            #[allow(unused_variables)]
+            fn check_param(&self, index: usize, param: &str) -> Result<(), ParamError> {
+                check_param!(index, param, [$($ty $kind: $idx),*])
+            }
+
+            #[allow(unused_variables)]
+            fn call(&self, context: &C, params: &[&str], chain: Chain<C, BoxFuture<R>>) -> Option<Result<BoxFuture<R>, Rejection>> {
+                if params.len() != $count {
+                    return None;
+                }
+                let args = ($(parse_param!($ty, $kind, params, $idx),)*);
+                Some(<($($ext,)*)>::from_request(context).map(|extracted| {
+                    call_through!(chain, context, (args, extracted), |context| {
+                        Box::pin(self(context.clone(), $(args.$idx,)* $(extracted.$ext_idx),*)) as BoxFuture<R>
+                    })
+                }))
+            }
+        }
+
+        impl<C, R, F, $($ty,)* $($ext),*> MacroHandler<C, ($($ty,)*), ($($ext,)*), R> for F
+        where
+            F: Fn(&C, $($ty,)* $($ext),*) -> R,
+            $($ext: FromRequest<C>,)*
+        {
+            #[allow(unused_variables)]
+            fn call_with_params(&self, context: &C, params: ($($ty,)*), chain: Chain<C, R>) -> Result<R, Rejection> {
+                <($($ext,)*)>::from_request(context).map(|extracted| {
+                    call_through!(chain, context, (params, extracted), |context| {
+                        self(context, $(params.$idx,)* $(extracted.$ext_idx),*)
+                    })
+                })
+            }
+        }
+
+        impl<C, R, F, Fut, $($ty,)* $($ext),*> AsyncMacroHandler<C, ($($ty,)*), ($($ext,)*), R> for F
+        where
+            C: Clone,
+            F: Fn(C, $($ty,)* $($ext),*) -> Fut,
+            Fut: Future<Output = R> + Send + 'static,
+            $($ext: FromRequest<C>,)*
+        {
+            #[allow(unused_variables)]
+            fn call_with_params(&self, context: &C, params: ($($ty,)*), chain: Chain<C, BoxFuture<R>>) -> Result<BoxFuture<R>, Rejection> {
+                <($($ext,)*)>::from_request(context).map(|extracted| {
+                    call_through!(chain, context, (params, extracted), |context| {
+                        Box::pin(self(context.clone(), $(params.$idx,)* $(extracted.$ext_idx),*)) as BoxFuture<R>
+                    })
+                })
+            }
+        }
+    };
+}
+
+macro_rules! impl_handlers {
+    ($count:expr; $($ty:ident $kind:ident : $idx:tt),*) => {
+        impl_handler!($count; [$($ty $kind: $idx),*]; []);
+        impl_handler!($count; [$($ty $kind: $idx),*]; [E1: 0]);
+        impl_handler!($count; [$($ty $kind: $idx),*]; [E1: 0, E2: 1]);
+        impl_handler!($count; [$($ty $kind: $idx),*]; [E1: 0, E2: 1, E3: 2]);
+        impl_handler!($count; [$($ty $kind: $idx),*]; [E1: 0, E2: 1, E3: 2, E4: 3]);
+    };
+}
+
+impl_handlers!(0;);
+impl_handlers!(1; T1 K1: 0);
+impl_handlers!(2; T1 K1: 0, T2 K2: 1);
+impl_handlers!(3; T1 K1: 0, T2 K2: 1, T3 K3: 2);
+impl_handlers!(4; T1 K1: 0, T2 K2: 1, T3 K3: 2, T4 K4: 3);
+impl_handlers!(5; T1 K1: 0, T2 K2: 1, T3 K3: 2, T4 K4: 3, T5 K5: 4);
+impl_handlers!(6; T1 K1: 0, T2 K2: 1, T3 K3: 2, T4 K4: 3, T5 K5: 4, T6 K6: 5);
+impl_handlers!(7; T1 K1: 0, T2 K2: 1, T3 K3: 2, T4 K4: 3, T5 K5: 4, T6 K6: 5, T7 K7: 6);
@@ -0,0 +1,126 @@
+use extract::{FromRequest, Rejection};
+
+#[cfg(feature = "with_http")]
+use request::RequestContext;
+
+/// Header sent by clients that ask for an API version explicitly, e.g. `X-API-Version: 2`
+pub const API_VERSION_HEADER: &str = "x-api-version";
+
+/// A context that can read request headers, required by routes with header
+/// conditions, e.g. `GET /users => get_users_v2 where version = 2`.
+///
+/// Implemented for `RequestContext`. Routes without header conditions don't need it.
+pub trait RequestHeaders {
+    /// Value of a header, `None` if it is missing or is not valid utf-8.
+    /// Header names are case-insensitive.
+    fn header(&self, name: &str) -> Option<&str>;
+}
+
+#[cfg(feature = "with_http")]
+impl<S, B> RequestHeaders for RequestContext<S, B> {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers().get(name)?.to_str().ok()
+    }
+}
+
+// Header condition of a route: the header is present and its value
+// (ignoring surrounding whitespace) equals `value`
+pub(crate) fn header_matches<C: RequestHeaders>(context: &C, name: &str, value: &str) -> bool {
+    context.header(name).map(str::trim) == Some(value)
+}
+
+/// API version requested by the client.
+///
+/// Taken from the `X-API-Version` header (`2` or `v2`), or else from the first media type
+/// in `Accept` that has one, either as a vendor suffix (`application/vnd.acme.v2+json`)
+/// or as a param (`application/json; version=2`).
+///
+/// Routes declare the version they serve with `where version = 2`, and handlers
+/// get the negotiated version as an extractor. Use `Option<ApiVersion>` in handlers of
+/// unversioned routes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ApiVersion(pub u32);
+
+impl ApiVersion {
+    /// Negotiates the version of a request, see `ApiVersion` for the rules
+    pub fn negotiate<C: RequestHeaders>(context: &C) -> Option<ApiVersion> {
+        if let Some(version) = context.header(API_VERSION_HEADER) {
+            let version = version.trim();
+            let version = version.trim_start_matches(['v', 'V']);
+            return version.parse().ok().map(ApiVersion);
+        }
+        context
+            .header("accept")?
+            .split(',')
+            .filter_map(media_type_version)
+            .next()
+            .map(ApiVersion)
+    }
+}
+
+impl<C: RequestHeaders> FromRequest<C> for ApiVersion {
+    fn from_request(context: &C) -> Result<Self, Rejection> {
+        ApiVersion::negotiate(context).ok_or(Rejection::MissingApiVersion)
+    }
+}
+
+// `application/vnd.acme.v2+json` or `application/json; version=2` -> 2
+fn media_type_version(media_type: &str) -> Option<u32> {
+    let mut parts = media_type.split(';');
+    let mime = parts.next()?.trim();
+    for param in parts {
+        let mut param = param.splitn(2, '=');
+        if param.next()?.trim().eq_ignore_ascii_case("version") {
+            return param.next()?.trim().trim_matches('"').parse().ok();
+        }
+    }
+    let subtype = mime.split('/').nth(1)?;
+    let subtype = subtype.split('+').next()?;
+    subtype
+        .split('.')
+        .filter_map(|part| part.strip_prefix('v')?.parse().ok())
+        .next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Headers(Vec<(&'static str, &'static str)>);
+
+    impl RequestHeaders for Headers {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.0
+                .iter()
+                .find(|header| header.0.eq_ignore_ascii_case(name))
+                .map(|header| header.1)
+        }
+    }
+
+    #[test]
+    fn test_api_version() {
+        let version = |headers| ApiVersion::negotiate(&Headers(headers));
+        assert_eq!(version(vec![("X-API-Version", "v2")]), Some(ApiVersion(2)));
+        assert_eq!(
+            version(vec![("accept", "application/vnd.acme.v3+json")]),
+            Some(ApiVersion(3))
+        );
+        assert_eq!(
+            version(vec![(
+                "accept",
+                "text/html, application/json; version=\"4\""
+            )]),
+            Some(ApiVersion(4))
+        );
+        assert_eq!(version(vec![("accept", "application/json")]), None);
+        assert_eq!(version(vec![("x-api-version", "latest")]), None);
+        assert_eq!(
+            ApiVersion::from_request(&Headers(vec![])),
+            Err(Rejection::MissingApiVersion)
+        );
+        let headers = Headers(vec![("x-tenant", " acme ")]);
+        assert!(header_matches(&headers, "X-Tenant", "acme"));
+        assert!(!header_matches(&headers, "x-tenant", "other"));
+        assert!(!header_matches(&headers, "x-missing", "acme"));
+    }
+}
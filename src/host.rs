@@ -0,0 +1,198 @@
+use std::str::Split;
+
+#[cfg(feature = "with_http")]
+use request::RequestContext;
+
+/// A context that knows the host of the request, required by routes with a host
+/// pattern, e.g. `GET {tenant: String}.example.com/users => get_users`.
+///
+/// Implemented for `RequestContext`, which takes the host from the request uri
+/// or from the `Host` header. Routes without a host don't need it.
+pub trait RequestHost {
+    /// Host of the request without the port, e.g. `acme.example.com`
+    fn host(&self) -> Option<&str>;
+}
+
+#[cfg(feature = "with_http")]
+impl<S, B> RequestHost for RequestContext<S, B> {
+    fn host(&self) -> Option<&str> {
+        match self.uri().host() {
+            Some(host) => Some(host),
+            None => {
+                let host = self.headers().get("host")?.to_str().ok()?;
+                Some(strip_port(host))
+            }
+        }
+    }
+}
+
+// `example.com:8080` -> `example.com`, IPv6 addresses are kept in brackets
+#[cfg(feature = "with_http")]
+fn strip_port(host: &str) -> &str {
+    match host.rfind(':') {
+        Some(index) if !host[index..].contains(']') => &host[..index],
+        _ => host,
+    }
+}
+
+/// Compiled host template, e.g. `{tenant}.example.com`.
+///
+/// Params are whole labels and may be written either as `{tenant}` or as
+/// `{tenant: String}`, same as in `Pattern`. Static labels are matched case-insensitively.
+/// Matching doesn't allocate: param values are borrowed from the host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostPattern {
+    template: String,
+    params: Vec<String>,
+    labels: Vec<Label>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Label {
+    Static(String),
+    Param,
+}
+
+impl HostPattern {
+    /// Compiles a host template into a pattern
+    ///
+    /// ### Panics
+    /// If the template has a wildcard param or an empty label.
+    pub fn new(template: &str) -> Self {
+        let mut normalized = String::new();
+        let mut params = Vec::new();
+        let mut labels = Vec::new();
+        for label in template.trim().trim_end_matches('.').split('.') {
+            let label = label.trim();
+            assert!(!label.is_empty(), "Empty label in host `{}`", template);
+            if !normalized.is_empty() {
+                normalized.push('.');
+            }
+            if label.starts_with('{') && label.ends_with('}') {
+                let inner = label[1..label.len() - 1].trim();
+                assert!(
+                    !inner.starts_with('*'),
+                    "Wildcard params are not supported in host `{}`",
+                    template
+                );
+                let name = inner.split(':').next().unwrap_or("").trim();
+                normalized.push('{');
+                normalized.push_str(name);
+                normalized.push('}');
+                params.push(name.to_string());
+                labels.push(Label::Param);
+            } else {
+                // tokens of `router!` hosts are stringified with spaces, e.g. `my - app`
+                let label: String = label.split_whitespace().collect();
+                normalized.push_str(&label);
+                labels.push(Label::Static(label));
+            }
+        }
+        HostPattern {
+            template: normalized,
+            params,
+            labels,
+        }
+    }
+
+    /// Normalized host template, e.g. `{tenant}.example.com`
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// Names of params in the order they appear in the host
+    pub fn params(&self) -> &[String] {
+        &self.params
+    }
+
+    /// Returns an iterator over raw param values if the host matches the pattern
+    pub fn captures<'p, 'a>(&'p self, host: &'a str) -> Option<HostCaptures<'p, 'a>> {
+        let host = host.trim_end_matches('.');
+        let mut count = 0;
+        for (index, value) in host.split('.').enumerate() {
+            let matches = match self.labels.get(index)? {
+                Label::Static(ref label) => label.eq_ignore_ascii_case(value),
+                Label::Param => !value.is_empty(),
+            };
+            if !matches {
+                return None;
+            }
+            count += 1;
+        }
+        if count != self.labels.len() {
+            return None;
+        }
+        Some(HostCaptures {
+            labels: self.labels.iter(),
+            values: host.split('.'),
+        })
+    }
+}
+
+/// Raw param values of a matched host, see `HostPattern::captures`
+#[derive(Debug, Clone)]
+pub struct HostCaptures<'p, 'a> {
+    labels: ::std::slice::Iter<'p, Label>,
+    values: Split<'a, char>,
+}
+
+impl<'p, 'a> Iterator for HostCaptures<'p, 'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        loop {
+            let label = self.labels.next()?;
+            let value = self.values.next()?;
+            if let Label::Param = *label {
+                return Some(value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_pattern() {
+        let pattern = HostPattern::new("{ tenant : String }.api.example.com");
+        assert_eq!(pattern.template(), "{tenant}.api.example.com");
+        assert_eq!(pattern.params(), &["tenant".to_string()]);
+        let captures: Vec<_> = pattern.captures("acme.API.example.com.").unwrap().collect();
+        assert_eq!(captures, vec!["acme"]);
+        assert!(pattern.captures("api.example.com").is_none());
+        assert!(pattern.captures("a.b.api.example.com").is_none());
+        assert!(pattern.captures(".api.example.com").is_none());
+        assert!(pattern.captures("acme.api.example.org").is_none());
+
+        let pattern = HostPattern::new("{region}.my - app.{env}.com");
+        assert_eq!(pattern.template(), "{region}.my-app.{env}.com");
+        let captures: Vec<_> = pattern.captures("eu.my-app.dev.com").unwrap().collect();
+        assert_eq!(captures, vec!["eu", "dev"]);
+    }
+
+    #[cfg(feature = "with_http")]
+    #[test]
+    fn test_request_host() {
+        use http::Request;
+
+        let request = Request::get("/users")
+            .header("host", "acme.example.com:8080")
+            .body(())
+            .unwrap();
+        assert_eq!(
+            RequestContext::new((), request).host(),
+            Some("acme.example.com")
+        );
+        let request = Request::get("http://acme.example.com/users")
+            .body(())
+            .unwrap();
+        assert_eq!(
+            RequestContext::new((), request).host(),
+            Some("acme.example.com")
+        );
+        assert_eq!(strip_port("[::1]"), "[::1]");
+        assert_eq!(strip_port("[::1]:80"), "[::1]");
+    }
+}
@@ -0,0 +1,377 @@
+//! Ready to use hyper 1.x service, enabled with the `with_hyper` feature.
+//!
+//! `HyperService` buffers request bodies (up to a limit), routes requests with an
+//! async router and maps all errors to responses, so it can be passed to any hyper
+//! 1.x server connection as is:
+//!
+//! ```rust
+//! async fn get_user(context: HyperContext<AppState>, user_id: u64) -> Result<Response<ResponseBody>, Error> {
+//!     ...
+//! }
+//!
+//! let router = router!(async
+//!     GET /users/{user_id: u64} => get_user,
+//!     _ => not_found,
+//! );
+//! let service = HyperService::new(router, state).body_limit(64 * 1024);
+//! http1::Builder::new().serve_connection(io, service).await?;
+//! ```
+//!
+//! Requests rejected by extractors (e.g. `Json<T>` with a wrong content type) can be
+//! answered with their status by `reject`:
+//!
+//! ```rust
+//! let router = router!(async
+//!     PUT /users/{user_id: u64} => put_user, // takes `user: Json<User>` after `user_id`
+//!     ! => reject,
+//!     _ => not_found,
+//! );
+//! ```
+//!
+//! Routers made with `Router::builder()` can be used as well:
+//!
+//! ```rust
+//! let router = Arc::new(router);
+//! let service = HyperService::new(move |context, method, path| router.handle(context, method, path), state);
+//! ```
+use bytes::Bytes;
+use extract::Rejection;
+use futures_util::future::{self, FutureExt, TryFutureExt};
+use handler::BoxFuture;
+use http::{Request, Response, StatusCode};
+use http_body::Body;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper_lib::service::Service;
+use method::{InvalidMethod, Method};
+use request::RequestContext;
+use std::convert::{Infallible, TryFrom};
+use std::error::Error as StdError;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+
+/// Body of responses returned by handlers
+pub type ResponseBody = Full<Bytes>;
+
+/// Context of handlers, with the request body buffered into `Bytes`
+pub type HyperContext<S> = RequestContext<S, Bytes>;
+
+/// Default limit of a request body, 2 MiB
+pub const DEFAULT_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+type BoxError = Box<dyn StdError + Send + Sync>;
+type ErrorHandler<E> = Arc<dyn Fn(Error<E>) -> Response<ResponseBody> + Send + Sync>;
+type BoxedRouter<S, E> = Arc<
+    dyn Fn(HyperContext<S>, Method, &str) -> BoxFuture<Result<Response<ResponseBody>, E>>
+        + Send
+        + Sync,
+>;
+
+/// Error of serving a request, mapped to a response by the error handler of `HyperService`
+#[derive(Debug)]
+pub enum Error<E> {
+    /// The request method can't be represented as `Method`
+    InvalidMethod(InvalidMethod),
+    /// The request body is larger than the limit
+    PayloadTooLarge(usize),
+    /// The request body can't be read
+    Body(BoxError),
+    /// Error returned by a handler
+    Handler(E),
+}
+
+impl<E> Error<E> {
+    /// Status of the response for this error
+    pub fn status(&self) -> StatusCode {
+        match *self {
+            Error::InvalidMethod(_) => StatusCode::NOT_IMPLEMENTED,
+            Error::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Error::Body(_) => StatusCode::BAD_REQUEST,
+            Error::Handler(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidMethod(ref e) => write!(f, "{}", e),
+            Error::PayloadTooLarge(limit) => {
+                write!(f, "Request body is larger than {} bytes", limit)
+            }
+            Error::Body(ref e) => write!(f, "Failed to read request body: {}", e),
+            Error::Handler(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> StdError for Error<E> {}
+
+/// Default error handler: an empty response with the status of the error.
+/// Details of the error are not exposed to clients.
+pub fn error_response<E>(error: Error<E>) -> Response<ResponseBody> {
+    let mut response = Response::new(ResponseBody::default());
+    *response.status_mut() = error.status();
+    response
+}
+
+/// Response for a rejected request, with the status of the rejection and its message as the body
+pub fn rejection_response(rejection: Rejection) -> Response<ResponseBody> {
+    let status = StatusCode::from_u16(rejection.status()).unwrap_or(StatusCode::BAD_REQUEST);
+    let mut response = Response::new(ResponseBody::from(rejection.to_string()));
+    *response.status_mut() = status;
+    response
+}
+
+/// Async rejection handler answering with `rejection_response`, i.e. `! => reject` in `router!(async ...)`
+pub fn reject<S, E>(
+    _: HyperContext<S>,
+    rejection: Rejection,
+) -> future::Ready<Result<Response<ResponseBody>, E>> {
+    future::ok(rejection_response(rejection))
+}
+
+/// Hyper 1.x `Service` routing requests with an async router.
+///
+/// The router is a closure returned by `router!(async ...)` (or any function with the
+/// same signature) whose handlers take `HyperContext<S>` and return
+/// `Result<Response<ResponseBody>, E>`.
+pub struct HyperService<S, E> {
+    router: BoxedRouter<S, E>,
+    state: S,
+    body_limit: usize,
+    error_handler: ErrorHandler<E>,
+}
+
+impl<S, E: 'static> HyperService<S, E> {
+    /// Creates a service. `state` is cloned for every request.
+    pub fn new<F, Fut>(router: F, state: S) -> Self
+    where
+        F: Fn(HyperContext<S>, Method, &str) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<ResponseBody>, E>> + Send + 'static,
+    {
+        HyperService {
+            router: Arc::new(move |context, method, path: &str| {
+                Box::pin(router(context, method, path)) as BoxFuture<_>
+            }),
+            state,
+            body_limit: DEFAULT_BODY_LIMIT,
+            error_handler: Arc::new(error_response),
+        }
+    }
+
+    /// Sets the max size of a request body in bytes, `DEFAULT_BODY_LIMIT` by default.
+    /// Larger requests are answered with `413 Payload Too Large`.
+    pub fn body_limit(mut self, limit: usize) -> Self {
+        self.body_limit = limit;
+        self
+    }
+
+    /// Sets the function mapping errors to responses, `error_response` by default
+    pub fn error_handler<H>(mut self, handler: H) -> Self
+    where
+        H: Fn(Error<E>) -> Response<ResponseBody> + Send + Sync + 'static,
+    {
+        self.error_handler = Arc::new(handler);
+        self
+    }
+}
+
+impl<S: Clone, E> Clone for HyperService<S, E> {
+    fn clone(&self) -> Self {
+        HyperService {
+            router: self.router.clone(),
+            state: self.state.clone(),
+            body_limit: self.body_limit,
+            error_handler: self.error_handler.clone(),
+        }
+    }
+}
+
+impl<S, E, B> Service<Request<B>> for HyperService<S, E>
+where
+    S: Clone + Send + 'static,
+    E: Send + 'static,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Response = Response<ResponseBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Result<Response<ResponseBody>, Infallible>>;
+
+    fn call(&self, request: Request<B>) -> Self::Future {
+        let error_handler = self.error_handler.clone();
+        // fail early, without reading the body
+        let method = match Method::try_from(request.method()) {
+            Ok(method) => method,
+            Err(e) => return Box::pin(future::ok(error_handler(Error::InvalidMethod(e)))),
+        };
+        let limit = self.body_limit;
+        if request.body().size_hint().lower() > limit as u64 {
+            return Box::pin(future::ok(error_handler(Error::PayloadTooLarge(limit))));
+        }
+
+        let router = self.router.clone();
+        let state = self.state.clone();
+        let (parts, body) = request.into_parts();
+        let response = Limited::new(body, limit)
+            .collect()
+            .map_err(move |e| body_error(e, limit))
+            .and_then(move |body| {
+                let path = parts.uri.path().to_string();
+                let request = Request::from_parts(parts, body.to_bytes());
+                router(RequestContext::new(state, request), method, &path).map_err(Error::Handler)
+            })
+            .map(move |result| Ok(result.unwrap_or_else(|e| error_handler(e))));
+        Box::pin(response)
+    }
+}
+
+fn body_error<E>(error: BoxError, limit: usize) -> Error<E> {
+    if error.downcast_ref::<LengthLimitError>().is_some() {
+        Error::PayloadTooLarge(limit)
+    } else {
+        Error::Body(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    type Context = HyperContext<&'static str>;
+    type HandlerResult = Result<Response<ResponseBody>, String>;
+
+    fn post_user(context: Context, user_id: u32) -> impl Future<Output = HandlerResult> {
+        let text = format!(
+            "{} {} saved in {}",
+            user_id,
+            String::from_utf8_lossy(context.body()),
+            context.state()
+        );
+        future::ok(Response::new(ResponseBody::from(text)))
+    }
+
+    struct Source;
+
+    impl ::extract::NamedHeader for Source {
+        const NAME: &'static str = "x-source";
+    }
+
+    fn put_user(
+        context: Context,
+        user_id: u32,
+        source: ::extract::Header<Source>,
+    ) -> impl Future<Output = HandlerResult> {
+        let text = format!("{} {} from {}", user_id, context.state(), source.value());
+        future::ok(Response::new(ResponseBody::from(text)))
+    }
+
+    fn delete_user(_: Context, user_id: u32) -> impl Future<Output = HandlerResult> {
+        future::err(format!("user {} is locked", user_id))
+    }
+
+    fn not_found(_: Context) -> impl Future<Output = HandlerResult> {
+        let mut response = Response::new(ResponseBody::default());
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        future::ok(response)
+    }
+
+    fn request(method: &str, uri: &str, body: &'static str) -> Request<Full<Bytes>> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Full::new(Bytes::from(body)))
+            .unwrap()
+    }
+
+    fn call<S>(service: &S, request: Request<Full<Bytes>>) -> (StatusCode, String)
+    where
+        S: Service<Request<Full<Bytes>>, Response = Response<ResponseBody>, Error = Infallible>,
+    {
+        let response = block_on(service.call(request)).unwrap();
+        let status = response.status();
+        let body = block_on(response.into_body().collect()).unwrap().to_bytes();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn test_service() {
+        let router = router!(async
+            POST /users/{user_id: u32} => post_user,
+            PUT /users/{user_id: u32} => put_user,
+            DELETE /users/{user_id: u32} => delete_user,
+            ! => reject,
+            _ => not_found,
+        );
+        let service = HyperService::new(router, "db").body_limit(5);
+        assert_eq!(
+            call(&service, request("POST", "/users/1", "alice")),
+            (StatusCode::OK, "1 alice saved in db".to_string())
+        );
+        assert_eq!(
+            call(&service, request("GET", "/users/1", "")),
+            (StatusCode::NOT_FOUND, String::new())
+        );
+        assert_eq!(
+            call(&service, request("POST", "/users/1", "alice!")).0,
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+        assert_eq!(
+            call(&service, request(&"X".repeat(32), "/users/1", "")).0,
+            StatusCode::NOT_IMPLEMENTED
+        );
+        assert_eq!(
+            call(&service, request("DELETE", "/users/1", "")),
+            (StatusCode::INTERNAL_SERVER_ERROR, String::new())
+        );
+        assert_eq!(
+            call(&service, request("PUT", "/users/1", "")),
+            (
+                StatusCode::BAD_REQUEST,
+                "Missing header `x-source`".to_string()
+            )
+        );
+        let mut put = request("PUT", "/users/1", "");
+        put.headers_mut().insert("x-source", "cli".parse().unwrap());
+        assert_eq!(
+            call(&service, put),
+            (StatusCode::OK, "1 db from cli".to_string())
+        );
+    }
+
+    #[test]
+    fn test_error_handler() {
+        let router = Arc::new(
+            ::router::Router::builder()
+                .route_async(Method::DELETE, "/users/{user_id}", delete_user)
+                .fallback_async(not_found)
+                .build(),
+        );
+        let service = HyperService::new(
+            move |context, method, path| router.handle(context, method, path),
+            "db",
+        )
+        .error_handler(|error: Error<String>| {
+            let mut response = Response::new(ResponseBody::from(error.to_string()));
+            *response.status_mut() = error.status();
+            response
+        });
+        assert_eq!(
+            call(&service, request("DELETE", "/users/7", "")),
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "user 7 is locked".to_string()
+            )
+        );
+        assert_eq!(
+            call(&service.clone().body_limit(1), request("DELETE", "/", "ab")),
+            (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "Request body is larger than 1 bytes".to_string()
+            )
+        );
+    }
+}
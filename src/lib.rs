@@ -3,9 +3,10 @@
 //! ### Key features:
 //! - Very expressive routes with fully typed parameters
 //! - Can be used with any http lib
-//! - Few dependencies (only `regex` and `lazy_static`)
+//! - No dependencies besides the http libs of the enabled features
+//! - Matching doesn't allocate, params can be borrowed from the path
 //!
-//! ### Getting started (for Hyper >= 0.12)
+//! ### Getting started (for Hyper 1.x)
 //!
 //! In your Cargo.toml
 //!
@@ -20,13 +21,12 @@
 //! extern crate http_router;
 //! ```
 //!
-//! In your struct than implements Hyper `Service`:
+//! Build a router and serve it with `http_router::hyper::HyperService`:
 //!
 //! ```rust
 //! // Each handler must have the same return type
-//! // A good candidate might be a Box<Future<Item = hyper::Response, Error = Error>>
 //! // The cost of this macro is next to zero, so it's ok to call it on each request
-//! let router = router!(
+//! let router = router!(async
 //!     GET / => get_users,
 //!
 //!     GET /users => get_users,
@@ -42,11 +42,10 @@
 //!     _ => not_found,
 //! );
 //!
-//! let path = req.uri.path();
-//! let ctx = Context { ... };
-//! // This will return a value of the matched handler's return type
-//! // E.g. the aforementioned Box<Future<Item = hyper::Response, Error = Error>>
-//! router(ctx, req.method.into(), path)
+//! // Buffers request bodies (up to the limit), routes requests and maps errors to responses,
+//! // e.g. `501 Not Implemented` for methods that can't be represented as `Method`
+//! let service = HyperService::new(router, state).body_limit(64 * 1024);
+//! http1::Builder::new().serve_connection(io, service).await
 //! ```
 //!
 //! A file with handlers implementation
@@ -56,41 +55,42 @@
 //! // If a param's type doesn't match (e.g. you supplied `sdf` as a user id, that must be `usize`)
 //! // then this route counts as non-matching
 //!
-//! type ServerFuture = Box<Future<Item = hyper::Response, Error = Error>>;
+//! type Context = HyperContext<State>;
+//! type ServerResult = Result<Response<ResponseBody>, Error>;
 //!
-//! pub fn get_users(context: &Context) -> ServerFuture {
+//! pub async fn get_users(context: Context) -> ServerResult {
 //!     ...
 //! }
 //!
-//! pub fn post_users(context: &Context) -> ServerFuture {
+//! pub async fn post_users(context: Context) -> ServerResult {
 //!     ...
 //! }
 //!
-//! pub fn put_users(context: &Context, user_id: usize) -> ServerFuture {
+//! pub async fn put_users(context: Context, user_id: usize) -> ServerResult {
 //!     ...
 //! }
 //!
-//! pub fn delete_users(context: &Context, id: usize) -> ServerFuture {
+//! pub async fn delete_users(context: Context, id: usize) -> ServerResult {
 //!     ...
 //! }
 //!
-//! pub fn get_transactions(context: &Context, user_id: usize) -> ServerFuture {
+//! pub async fn get_transactions(context: Context, user_id: usize) -> ServerResult {
 //!     ...
 //! }
 //!
-//! pub fn post_transactions(context: &Context, user_id: usize) -> ServerFuture {
+//! pub async fn post_transactions(context: Context, user_id: usize) -> ServerResult {
 //!     ...
 //! }
 //!
-//! pub fn put_transactions(context: &Context, user_id: usize, hash: String) -> ServerFuture {
+//! pub async fn put_transactions(context: Context, user_id: usize, hash: String) -> ServerResult {
 //!     ...
 //! }
 //!
-//! pub fn delete_transactions(context: &Context, user_id: usize, hash: String) -> ServerFuture {
+//! pub async fn delete_transactions(context: Context, user_id: usize, hash: String) -> ServerResult {
 //!     ...
 //! }
 //!
-//! pub fn not_found(_context: &Context) -> ServerFuture {
+//! pub async fn not_found(_context: Context) -> ServerResult {
 //!     ...
 //! }
 //!
@@ -98,9 +98,61 @@
 //!
 //! See [examples folder](https://github.com/alleycat-at-git/http_router/tree/master/examples/hyper_example) for a complete Hyper example
 //!
+//! A route can match several methods (`GET | HEAD`) or any method (`ANY`). Handlers of such routes get the request method right after the context. The last segment of a path can be a wildcard param that matches the rest of the path:
+//!
+//! ```rust
+//! let router = router!(
+//!     GET | HEAD /health => health,        // fn health(context: &Context, method: Method)
+//!     ANY /proxy/{*rest} => proxy,         // fn proxy(context: &Context, method: Method, rest: String)
+//!     _ => not_found,
+//! );
+//! ```
+//!
+//! Routes sharing a prefix can be grouped with `scope` (scopes can be nested):
+//!
+//! ```rust
+//! let router = router!(
+//!     GET /users => get_users,
+//!     scope /users/{user_id: usize} {
+//!         GET /transactions => get_transactions,
+//!         POST /transactions => post_transactions,
+//!     },
+//!     _ => not_found,
+//! );
+//! ```
+//!
+//! ### Async handlers
+//!
+//! With `async` before the routes, handlers take the context by value and return futures, so `async fn` handlers can be used directly. The context is cloned for the matched route, so it should be cheap to clone (e.g. keep shared state in an `Arc`). The router returns one `BoxFuture` type for all handlers:
+//!
+//! ```rust
+//! async fn get_user(context: RequestContext<AppState, Bytes>, user_id: u64) -> Result<Response<Full<Bytes>>, Error> {
+//!     ...
+//! }
+//!
+//! let router = router!(async
+//!     GET /users/{user_id: u64} => get_user,
+//!     _ => not_found,
+//! );
+//!
+//! // hyper 1.x
+//! let service = service_fn(move |request| {
+//!     let state = state.clone();
+//!     async move {
+//!         let request = buffer_body(request).await?;
+//!         match http_router::dispatch(router, state, request) {
+//!             Ok(response) => response.await,
+//!             Err(_) => Ok(not_implemented()),
+//!         }
+//!     }
+//! });
+//! ```
+//!
+//! `Router::builder()` has the same support with `route_async` and `fallback_async`.
+//!
 //! ### Using with other http libs
 //!
-//! By default this crate is configured to be used with `hyper >=0.12`. If you want to use it with other libs, you might want to opt out of default features for this crate. So in your Cargo.toml:
+//! By default this crate is configured to be used with `hyper 1.x`. If you want to use it with other libs, you might want to opt out of default features for this crate. So in your Cargo.toml:
 //!
 //! ```toml
 //! [dependencies]
@@ -111,228 +163,776 @@
 //!
 //! `context` is a param of your user-defined type. e.g. `Context`. It will be passed as a first argument to all of your handlers. You can put there any values like database interfaces and http clients as you like.
 //!
-//! `method` is a param of type Method defined in `http_router` lib. It is one of `GET`, `POST`, etc.
+//! `method` is a param of type Method defined in `http_router` lib. It is one of `GET`, `POST`, etc. Non-standard methods (e.g. WebDAV `PROPFIND`) are represented as `Method::Extension` and can be used in routes just like the standard ones: `PROPFIND /files/{*path} => propfind`.
 //!
 //! `path` is a `&str` which is the current route for a request.
 //!
 //! Once you define these 3 params, you can use the `router!` macro for routing.
 //!
+//! ### Using with the `http` crate
+//!
+//! With the `with_http` feature (enabled by `with_hyper`) a router can take an `http::Request<B>` directly. Method and path are taken from the request, and handlers get a `RequestContext` that holds your state together with the request parts and body:
+//!
+//! ```rust
+//! fn get_users(context: &RequestContext<AppState, Body>) -> Response<Body> {
+//!     let state = context.state();
+//!     let query = context.query();
+//!     let headers = context.headers();
+//!     ...
+//! }
+//!
+//! let router = router!(
+//!     GET /users => get_users,
+//!     _ => not_found,
+//! );
+//! // `Err` means that the request method can't be represented as `Method`
+//! let response = http_router::dispatch(router, state, request)
+//!     .unwrap_or_else(|_| not_implemented());
+//! ```
+//!
+//! Routers made with `Router::builder()` have the same `handle_request(state, request)` method. `Method` converts to and from `http::Method` (which is also `hyper::Method`).
+//!
+//! ### Extractors
+//!
+//! Besides path params, handlers can take arguments extracted from the context with the `FromRequest` trait. Extractors come after path params (up to 4 per handler). With the `with_http` feature there are `Header<N>`, `RawBody<B>` and `Method`, and the `with_serde` feature adds `Query<T>` and `Json<T>`:
+//!
+//! ```rust
+//! struct RequestId;
+//!
+//! impl NamedHeader for RequestId {
+//!     const NAME: &'static str = "x-request-id";
+//! }
+//!
+//! fn put_user(context: &Context, user_id: u64, user: Json<User>, request_id: Option<Header<RequestId>>) -> Response<Body> {
+//!     ...
+//! }
+//!
+//! fn rejected(context: &Context, rejection: Rejection) -> Response<Body> {
+//!     // e.g. 415 for `Json<T>` with a wrong content type
+//!     error_response(rejection.status(), rejection.to_string())
+//! }
+//!
+//! let router = router!(
+//!     PUT /users/{user_id: u64} => put_user,
+//!     ! => rejected,
+//!     _ => not_found,
+//! );
+//! ```
+//!
+//! If an extractor fails, the request is passed to the rejection handler (`!`), or to the fallback if there is none. Use `Option<T>` or `Result<T, Rejection>` for optional arguments. `Router::builder()` has the same support with `rejection` and `rejection_async`.
+//!
+//! ### Hosts
+//!
+//! Routes can also match on the host of the request, e.g. to serve many tenants from one process. Params of the host are whole labels and are passed to the handler before the params of the path. Routes without a host match any host:
+//!
+//! ```rust
+//! let router = router!(
+//!     GET admin.example.com/ => admin_home,
+//!     GET {tenant: String}.example.com/users => get_users,               // fn get_users(context: &Context, tenant: String)
+//!     GET {tenant: String}.example.com/users/{user_id: u64} => get_user, // fn get_user(context: &Context, tenant: String, user_id: u64)
+//!     GET /health => health,
+//!     _ => not_found,
+//! );
+//! ```
+//!
+//! The host is taken from the context with the `RequestHost` trait, which is implemented for `RequestContext` (it uses the request uri or the `Host` header). `Router::builder()` has `host` for the route added last.
+//!
+//! ### Guards
+//!
+//! A route can have a guard, which runs once the path matches and before the params are converted. It takes the context and returns a `Guard`: `Allow`, `Skip` (the route counts as non-matching and later routes are tried) or `Reject` (matching stops and the rejection goes to the `!` handler). Guards may also return `bool` or `Result<(), Rejection>`:
+//!
+//! ```rust
+//! fn is_admin(context: &Context) -> Guard {
+//!     match context.user() {
+//!         Some(user) if user.is_admin => Guard::Allow,
+//!         Some(_) => Guard::reject(403, "Admins only"),
+//!         None => Guard::reject(401, "Not logged in"),
+//!     }
+//! }
+//!
+//! fn is_beta_tester(context: &Context) -> bool {
+//!     ...
+//! }
+//!
+//! let router = router!(
+//!     GET /admin/users => list_users if is_admin,
+//!     GET /users/{user_id: u64} => get_user_beta if is_beta_tester,
+//!     GET /users/{user_id: u64} => get_user,
+//!     ! => rejected,
+//!     _ => not_found,
+//! );
+//! ```
+//!
+//! `Router::builder()` has `guard` for the route added last.
+//!
+//! ### Headers and versions
+//!
+//! `where` adds header conditions to a route: `where "x-tenant" = "acme"` requires a header value, and `where version = 2` requires the API version the client asked for, either in `X-API-Version: 2` or in `Accept` (`application/vnd.acme.v2+json` or `application/json; version=2`). A request that doesn't meet the conditions skips the route, so list versioned routes before the unversioned fallback. The context has to implement `RequestHeaders` (`RequestContext` does), and handlers can take the negotiated `ApiVersion` as an extractor:
+//!
+//! ```rust
+//! let router = router!(
+//!     GET /users => get_users_v2 where version = 2,
+//!     GET /users => get_acme_users where "x-tenant" = "acme",
+//!     GET /users => get_users, // fn get_users(context: &Context, version: Option<ApiVersion>)
+//!     _ => not_found,
+//! );
+//! ```
+//!
+//! `Router::builder()` has `header` and `version` for the route added last.
+//!
+//! ### Content negotiation
+//!
+//! `consumes` and `produces` declare the media types of a route. `consumes "application/json"` (or a range, e.g. `text/*`) has to match the `Content-Type` of the request, and `produces "text/csv"` has to be acceptable by its `Accept` header. Routes with the same path can serve different formats: the client's quality values decide, so with `Accept: text/csv;q=0.5, application/json` the JSON route wins even if it comes later. Routes without `produces` match any `Accept`, so they should come last. If no route fits, the request is rejected with `Rejection::UnsupportedMediaType` (415) or `Rejection::NotAcceptable` (406). Media types are checked before the guards of a route. The context has to implement `RequestHeaders`:
+//!
+//! ```rust
+//! let router = router!(
+//!     GET /users => get_users_csv produces "text/csv",
+//!     GET /users => get_users_msgpack produces "application/msgpack",
+//!     GET /users => get_users produces "application/json",
+//!     POST /users => post_users consumes "application/json",
+//!     ! => rejected,
+//!     _ => not_found,
+//! );
+//! ```
+//!
+//! `Router::builder()` has `consumes` and `produces` for the route added last.
+//!
+//! ### Redirects and aliases
+//!
+//! `redirect(status, target)` in place of a handler makes a redirect route. Params of the path are substituted into the target by name, and invalid typed params don't match, same as for other routes. The router returns the `Redirect` through `From<Redirect>`, which is implemented for `http::Response<B>` as well as for `Result` and `BoxFuture` of such types, so routers returning responses get a response with the `Location` header. Targets start with `/` or a scheme (e.g. `https:`), so that params can't turn them into another host.
+//!
+//! A route can also have aliases, i.e. other paths handled by the same handler with the same conditions and middleware:
+//!
+//! ```rust
+//! let router = router!(
+//!     GET / | /index | /home => get_home,
+//!     GET /users/{id: u32} | /people/{id: u32} => get_user,
+//!     GET /old/users/{id: u32} => redirect(308, "/users/{id}"),
+//!     _ => not_found,
+//! );
+//! ```
+//!
+//! Both show up in `routes!` as separate routes, redirects with `handler: "redirect"` and the target in `RouteInfo::redirect`. `Router::builder()` has `redirect` and `alias`. Once a builder router is mounted, the targets of its redirects that start with `/` are relative to the prefix.
+//!
+//! ### Deprecation
+//!
+//! `deprecated(since = "2026-06-01", sunset = "2027-01-01", link = "...")` marks a route for retirement (all of the fields are optional, and so are the parens). The deprecation is a part of the `RouteInfo` of the route, so middleware sees it, and the built-in `deprecation_headers` middleware (for `http` responses) adds the `Deprecation` (RFC 9745, whose value is the `since` date, so it needs one), `Sunset` and `Link` headers to the responses of deprecated routes, keeping the headers set by the handler:
+//!
+//! ```rust
+//! let router = router!(
+//!     with [deprecation_headers],
+//!     GET /v1/users => get_users deprecated(sunset = "2027-01-01", link = "https://example.com/migration"),
+//!     GET /v2/users => get_users,
+//!     _ => not_found,
+//! );
+//! ```
+//!
+//! Dates are calendar dates in UTC. `routes!` lists the deprecation in `RouteInfo::deprecation`, and OpenAPI marks the operation as `deprecated`. `Router::builder()` has `deprecated(Deprecation::new().sunset("2027-01-01"))` for the route added last.
+//!
+//! ### Middleware
+//!
+//! Middleware runs around the handler of the matched route, e.g. for auth, logging or timing. It is a function taking the context, the `RouteInfo` of the route (its template, params and handler name) and `Next`, the rest of the chain. `next.params()` has the raw values of the params, in the order of `RouteInfo::params`. It runs once the params are parsed and the extractors succeed, and can return early without calling the handler:
+//!
+//! ```rust
+//! fn auth(context: &Context, route: &RouteInfo, next: Next<Context, Response<Body>>) -> Response<Body> {
+//!     match context.user() {
+//!         Some(_) => next.run(context),
+//!         None => unauthorized(),
+//!     }
+//! }
+//!
+//! let router = router!(
+//!     with [logging],                     // all routes below
+//!     GET /users => get_users,
+//!     GET /users/{user_id: u64} => get_user with [cache],
+//!     scope /admin with [auth] {          // all routes of the scope
+//!         DELETE /users/{user_id: u64} => delete_user,
+//!     },
+//!     _ => not_found,
+//! );
+//! ```
+//!
+//! Outer middleware runs first. In async routers middleware returns the `BoxFuture` of the rest of the chain, so it can wrap it. `Router::builder()` has `middleware` for all routes of a router (scopes are mounted routers) and `with` for the route added last.
+//!
+//! ### Runtime builder
+//!
+//! If routes are not known at compile time (e.g. they come from plugins or config), use `Router::builder()` instead of the macro. It uses the same matcher and the same handler signatures as `router!`:
+//!
+//! ```rust
+//! let router = Router::builder()
+//!     .route(Method::GET, "/users", get_users)
+//!     .route(Method::PUT, "/users/{user_id}", put_users)
+//!     .fallback(not_found)
+//!     .build();
+//!
+//! router.handle(ctx, Method::PUT, "/users/12")
+//! ```
+//!
+//! Params are parsed with the `FromParam` trait, which is implemented for all `FromStr` types and for `&str` and `Cow<str>`, which borrow the param from the path. Custom types (ids, hashes, slugs) can implement it with their own error messages. A route whose params can't be parsed counts as non-matching, and if no other route matches, the `ParamError` is passed to the rejection handler as `Rejection::InvalidParam`, so it can be answered with `400 Bad Request` instead of `404 Not Found`.
+//!
+//! Routers can be nested. Params of the prefix are passed to the nested handlers before their own params, and the fallback of the nested router handles unmatched paths under the prefix. A handler that takes more params than its path has would never be called, so `build` rejects it, and such nested routers are mounted unbuilt:
+//!
+//! ```rust
+//! // `users_router` is a `RouterBuilder`, its handlers take `tenant_id: u64` as their first param
+//! let router = Router::builder()
+//!     .mount("/tenants/{tenant_id}", users_router)
+//!     .fallback(not_found)
+//!     .build();
+//! ```
+//!
+//! Such a nested router is tested on its own by building it with the prefix that declares the params of its handlers:
+//!
+//! ```rust
+//! let router = users_router.build_under("/tenants/{tenant_id}");
+//! router.handle(ctx, Method::GET, "/tenants/1/users/2")
+//! ```
+//!
+//! Only builder routers can be nested: there is no `mount!` macro, and routers made by `router!` can't be mounted into others. In `router!`, routes sharing a prefix are grouped into scopes instead.
+//!
+//! ### Metrics
+//!
+//! Routers can record every request with a `Metrics` hook, labelled by the template of the matched route (`/users/{user_id}`) rather than by the path, whose cardinality is unbounded. Each request has an `Outcome`: `handled`, `bad_param`, `rejected`, `method_not_allowed` (the path matches a route, but not the method) or `fallback`. The built-in `MetricsRegistry` keeps request counters and latency histograms in memory and renders them in the Prometheus text format:
+//!
+//! ```rust
+//! let metrics = Arc::new(MetricsRegistry::new());
+//! let router = Router::builder()
+//!     .route(Method::GET, "/users/{user_id}", get_user)
+//!     .metrics(metrics.clone())                 // `metrics_async` for async routers
+//!     .fallback(not_found)
+//!     .build();
+//!
+//! println!("{}", metrics.render());
+//! // http_requests_total{method="GET",route="/users/{user_id}",outcome="handled"} 1
+//! // http_request_duration_seconds_bucket{method="GET",route="/users/{user_id}",outcome="handled",le="0.005"} 1
+//! // ...
+//! ```
+//!
+//! With `metrics_async` the latency lasts until the future of the request completes.
+//!
+//! `router!` takes the hook as its first item, and async macro routers record a request once its future completes too:
+//!
+//! ```rust
+//! let router = router!(
+//!     metrics(metrics.clone()),
+//!     GET /users/{user_id: u64} => get_user,
+//!     _ => not_found,
+//! );
+//! ```
+//!
+//! ### Tracing
+//!
+//! With the `tracing` feature each request is handled in a `dispatch` span with the fields `http.method`, `http.route` (the template of the matched route), `handler` and `params`, and the router emits `debug` events for the routes it skips: a guard skipped it, a param failed to parse or the media types don't fit. Values of params such as tokens can be hidden from traces:
+//!
+//! ```rust
+//! let router = Router::builder()
+//!     .route(Method::GET, "/invites/{invite_id}/{token}", get_invite)
+//!     .redact("token")
+//!     .fallback(not_found)
+//!     .build();
+//! // dispatch{http.method=GET http.route=/invites/{invite_id}/{token} handler=get_invite params="invite_id=1, token=[redacted]"}
+//! ```
+//!
+//! Futures of async routers run in the span too, so it lasts until the response is ready. `router!` records the same fields and events, and takes the params to hide as its first item, after `metrics(..)` if any:
+//!
+//! ```rust
+//! let router = router!(
+//!     redact(token),
+//!     GET /invites/{invite_id: u64}/{token: String} => get_invite,
+//!     _ => not_found,
+//! );
+//! ```
+//!
+//! Nothing is formatted unless a subscriber is interested in the span.
+//!
+//! ### Explaining routes
+//!
+//! When a request unexpectedly hits the fallback, `Router::explain` tells for every route whether its method and path match the request, and which param its handler can't convert. Handlers are not called, and routes with a host, guards or media types are marked as conditional, since those depend on the rest of the request:
+//!
+//! ```rust
+//! let explanation = router.explain(Method::GET, "/users/x");
+//! assert!(explanation.matched().is_none());
+//! println!("{}", explanation);
+//! // GET /users/x
+//! //   GET /users => get_users: path doesn't match
+//! //   GET /users/{user_id} => get_user: Invalid param `user_id` = `x`: invalid digit found in string
+//! // no route matches
+//! ```
+//!
+//! `router!` expands into plain code with no route table to inspect, so its routes are explained by an `Explainer`. The `explainer!` macro takes the same input as `router!` and `routes!`:
+//!
+//! ```rust
+//! let explainer = explainer!(
+//!     GET /users => get_users,
+//!     GET /users/{user_id: u32} => get_user,
+//!     _ => not_found
+//! );
+//! println!("{}", explainer.explain(Method::GET, "/users/x"));
+//! ```
+//!
+//! The explanation of a builder router can also be served by an opt-in debug endpoint, e.g. `GET /_explain/GET/users/x`. Routers return it with `From<Explanation>`, which is implemented for `http::Response<B>` as plain text:
+//!
+//! ```rust
+//! let router = Router::builder()
+//!     .route(Method::GET, "/users", get_users)
+//!     .route(Method::GET, "/users/{user_id}", get_user)
+//!     .explain_endpoint("/_explain")
+//!     .fallback(not_found)
+//!     .build();
+//! ```
+//!
+//! ### Tower
+//!
+//! With the `with_tower` feature a router can be turned into a `tower::Service<http::Request<B>>` and composed with tower middleware (timeouts, concurrency limits, tracing). Handlers return futures of `Result<T, E>`, and `E` must implement `From<InvalidMethod>`. A route can also be handled by a `Service` itself, with route params available as `PathParams` in request extensions:
+//!
+//! ```rust
+//! fn get_users(context: &RequestContext<AppState, Bytes>) -> BoxFuture<Result<Response<Body>, Error>> {
+//!     ...
+//! }
+//!
+//! let router = Router::builder()
+//!     .route(Method::GET, "/users", get_users)
+//!     .service(Method::GET, "/users/{user_id}", ServiceBuilder::new().timeout(timeout).service(get_user))
+//!     .fallback(not_found)
+//!     .build();
+//!
+//! let service = ServiceBuilder::new()
+//!     .concurrency_limit(100)
+//!     .service(router.into_service(state));
+//! ```
+//!
+//! `router.into_layer(state)` returns a `tower::Layer` instead, which puts the router in front of another service. Requests that no route matches go to the inner service in place of the fallback:
+//!
+//! ```rust
+//! let service = ServiceBuilder::new()
+//!     .layer(router.into_layer(state))
+//!     .service(legacy_service);
+//! ```
+//!
+//! ### OpenAPI
+//!
+//! With the `openapi` feature enabled, the `routes!` macro takes the same route definitions as `router!` and returns their descriptions, which can be rendered into an OpenAPI 3 `paths` skeleton:
+//!
+//! ```rust
+//! let routes = routes!(
+//!     GET /users => get_users,
+//!     PUT /users/{user_id: usize} => put_users,
+//!     _ => not_found,
+//! );
+//! let doc = http_router::openapi::Document::new("Users API", "1.0.0").routes(&routes);
+//! println!("{}", doc.to_json()); // or doc.to_yaml()
+//! ```
+//!
+//! Routes with the same path and method, e.g. variants with other media types or versions, become one operation listing the `consumes` and `produces` media types of all of them.
+//!
+//! ### Static files
+//!
+//! With the `static_files` feature, `StaticFiles` serves the files of a directory from a tail route. Paths are percent-decoded, and paths with `..` (or files that resolve outside of the directory) are forbidden. `Content-Type` is detected by the extension, responses have `ETag` and `Last-Modified` for conditional requests, and single `Range` requests get partial content. A directory can serve its index, and a single page app can fall back to its `index.html` for paths that don't exist:
+//!
+//! ```rust
+//! let app = StaticFiles::new("./dist").index("index.html").fallback("index.html");
+//!
+//! let router = Router::builder()
+//!     .files("/assets", StaticFiles::new("./public"))
+//!     .files("/", app)
+//!     .fallback(not_found)
+//!     .build();
+//! ```
+//!
+//! With `router!`, a handler of a tail route can serve the files itself, given the context implements `RequestHeaders`:
+//!
+//! ```rust
+//! fn get_asset(context: &Context, method: Method, path: &str) -> Response<Body> {
+//!     context.state().assets.serve(context, method, path).into()
+//! }
+//!
+//! let router = router!(
+//!     GET | HEAD /assets/{*path: &str} => get_asset,
+//!     _ => not_found,
+//! );
+//! ```
+//!
+//! Bodies are read into memory, up to `max_size` bytes (16 MiB by default): larger files are served by ranges only, and `GET` requests for the whole file are forbidden. The files are read with blocking `std::fs` calls, so async routers should use `files_async` (or `serve_async` in a handler), which reads the files on a bounded pool of threads (4 by default, see `StaticFiles::threads`):
+//!
+//! ```rust
+//! let router = Router::builder()
+//!     .files_async("/assets", StaticFiles::new("./public").max_size(64 * 1024 * 1024))
+//!     .fallback_async(not_found)
+//!     .build();
+//! ```
+//!
 //! ### Benchmarks
 //!
 //! Right now the router with 10 routes takes approx 50 microseconds per route
 //!
+//! Matching doesn't allocate: patterns are compiled once per route and params are borrowed from the path. Handlers can take `&str` or `Cow<str>` params to avoid allocating `String`s, so static routes and routes with borrowed (or `Copy`) params don't allocate at all. This is verified by `cargo bench --bench allocations`, which counts allocations per request.
+//!
 
-extern crate regex;
-#[macro_use]
-extern crate lazy_static;
 #[cfg(feature = "with_hyper")]
-extern crate hyper;
+extern crate bytes;
+#[cfg(test)]
+extern crate futures;
+#[cfg(feature = "with_hyper")]
+extern crate futures_util;
+#[cfg(feature = "with_http")]
+extern crate http;
+#[cfg(feature = "with_hyper")]
+extern crate http_body;
+#[cfg(feature = "with_hyper")]
+extern crate http_body_util;
+#[cfg(feature = "with_hyper")]
+extern crate hyper as hyper_lib;
+#[cfg(feature = "with_serde")]
+extern crate serde;
+#[cfg(any(feature = "openapi", feature = "with_serde"))]
+extern crate serde_json;
+#[cfg(feature = "with_serde")]
+extern crate serde_urlencoded;
+#[cfg(feature = "with_tower")]
+extern crate tower;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+#[cfg(all(test, feature = "tracing"))]
+extern crate tracing_core;
 
+#[macro_use]
+mod macros;
+mod date;
+mod deprecation;
+mod explain;
+mod extract;
+#[cfg(feature = "static_files")]
+mod files;
+mod guard;
+mod handler;
+mod headers;
+mod host;
+#[cfg(feature = "with_hyper")]
+pub mod hyper;
+mod media;
 mod method;
+mod metrics;
+mod middleware;
+#[cfg(feature = "openapi")]
+pub mod openapi;
+mod param;
+mod pattern;
+mod redirect;
+#[cfg(feature = "with_http")]
+mod request;
+mod route;
+mod router;
+#[cfg(feature = "with_tower")]
+mod service;
+#[cfg(feature = "tracing")]
+mod trace;
 
-pub use self::method::Method;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+pub use self::deprecation::Deprecation;
+#[cfg(feature = "with_http")]
+pub use self::deprecation::{deprecation_headers, WithHeaders};
+pub use self::explain::{Explainer, Explanation, ParamCheck, RouteExplanation};
+pub use self::extract::{FromRequest, Rejection};
+#[cfg(feature = "with_http")]
+pub use self::extract::{Header, NamedHeader, RawBody};
+#[cfg(feature = "with_serde")]
+pub use self::extract::{Json, Query};
+#[cfg(feature = "static_files")]
+pub use self::files::{FileResponse, StaticFiles};
+pub use self::guard::Guard;
+pub use self::handler::{AsyncHandler, BoxFuture, Handler, MethodHandler};
+#[doc(hidden)]
+pub use self::handler::{AsyncMacroHandler, MacroHandler};
+pub use self::headers::{ApiVersion, RequestHeaders, API_VERSION_HEADER};
+pub use self::host::{HostCaptures, HostPattern, RequestHost};
+#[doc(hidden)]
+pub use self::media::Negotiation;
+pub use self::method::{ExtensionMethod, InvalidMethod, Method};
+pub use self::metrics::{Metrics, MetricsRegistry, Outcome, DEFAULT_BUCKETS};
+pub use self::middleware::{Chain, Middleware, Next};
+pub use self::param::{FromParam, ParamError};
+pub use self::pattern::{Captures, Pattern};
+pub use self::redirect::Redirect;
+#[doc(hidden)]
+pub use self::redirect::RedirectTarget;
+#[cfg(feature = "with_http")]
+pub use self::request::{dispatch, RequestContext};
+pub use self::route::{ParamInfo, RouteInfo};
+pub use self::router::{Mountable, Router, RouterBuilder};
+#[cfg(feature = "with_tower")]
+pub use self::service::{PathParams, Routed, RouterLayer, RouterService};
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_box_future<F>(future: F) -> BoxFuture<F::Output>
+where
+    F: ::std::future::Future + Send + 'static,
+{
+    Box::pin(future)
+}
 
-lazy_static! {
-    static ref REGEXES: Arc<Mutex<HashMap<String, regex::Regex>>> =
-        { Arc::new(Mutex::new(HashMap::new())) };
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_call<C, P, E, R, H>(
+    handler: &H,
+    context: &C,
+    params: P,
+    chain: Chain<C, R>,
+) -> Result<R, Rejection>
+where
+    H: MacroHandler<C, P, E, R>,
+{
+    handler.call_with_params(context, params, chain)
 }
 
 /// This is an implementation detail and *should not* be called directly!
 #[doc(hidden)]
-pub fn __http_router_create_regex(s: &str) -> regex::Regex {
-    let mut _result: Option<regex::Regex> = None;
-    {
-        let regexes = REGEXES.lock().expect("Failed to obtain mutex lock");
-        _result = regexes.get(s).cloned();
-    };
-    _result.unwrap_or_else(|| {
-        let re = regex::Regex::new(s).unwrap();
-        let mut regexes = REGEXES.lock().expect("Failed to obtain mutex lock");
-        regexes.insert(s.to_string(), re.clone());
-        re
-    })
+pub fn __http_router_call_async<C, P, E, R, H>(
+    handler: &H,
+    context: &C,
+    params: P,
+    chain: Chain<C, BoxFuture<R>>,
+) -> Result<BoxFuture<R>, Rejection>
+where
+    H: AsyncMacroHandler<C, P, E, R>,
+{
+    handler.call_with_params(context, params, chain)
 }
 
-/// This macro returns a closure that takes 3 params. See crate doc for more details.
-///
-/// ### Limitations:
-/// - Home route is optional and should come first
-/// - Fallback route (`_`) is required and should come last
-///
-/// ### Performace
-/// Macro routers itself has almost no cost, so you can call it
-/// everywhere as many times as you like. The closure that it returns
-/// have some cost (approx 50 microseconds per one call).
-///
-/// ### Thread safety
-/// The closure returned by this macro is thread-safe.
-#[macro_export]
-macro_rules! router {
-    // convert params from string
-    (@parse_type $value:expr, $ty:ty) => {{
-        let maybe_val = $value.parse::<$ty>();
-        if maybe_val.is_err() { return None };
-        maybe_val.unwrap()
-    }};
-
-    // call handler with params
-    (@call_pure $context:expr, $handler:ident, $params:expr, $({$id:ident : $ty:ty : $idx:expr}),*) => {{
-        $handler(&$context, $({
-            let value = $params[$idx];
-            router!(@parse_type value, $ty)
-        }),*)
-    }};
-
-    // Extract params from route, 0 params case
-    (@call, $context:expr, $handler:ident, $params:expr, $($p:ident)*) => {{
-        $handler(&$context)
-    }};
-
-    // Extract params from route, 1 params case
-    (@call, $context:expr, $handler:ident, $params:expr, $($p:ident)* {$id1:ident : $ty1:ty} $($p1:ident)*) => {{
-        router!(@call_pure $context, $handler, $params, {$id1 : $ty1 : 0})
-    }};
-
-    // Extract params from route, 2 params case
-    (@call, $context:expr, $handler:ident, $params:expr, $($p:ident)* {$id1:ident : $ty1:ty} $($p1:ident)* {$id2:ident : $ty2:ty} $($p2:ident)*) => {{
-        router!(@call_pure $context, $handler, $params, {$id1 : $ty1 : 0}, {$id2 : $ty2 : 1})
-    }};
-
-    // Extract params from route, 3 params case
-    (@call, $context:expr, $handler:ident, $params:expr, $($p:ident)* {$id1:ident : $ty1:ty} $($p1:ident)* {$id2:ident : $ty2:ty} $($p2:ident)* {$id3:ident : $ty3:ty} $($p3:ident)*) => {{
-        router!(@call_pure $context, $handler, $params, {$id1 : $ty1 : 0}, {$id2 : $ty2 : 1}, {$id3 : $ty3 : 2})
-    }};
-
-    // Extract params from route, 4 params case
-    (@call, $context:expr, $handler:ident, $params:expr, $($p:ident)* {$id1:ident : $ty1:ty} $($p1:ident)* {$id2:ident : $ty2:ty} $($p2:ident)* {$id3:ident : $ty3:ty} $($p3:ident)* {$id4:ident : $ty4:ty} $($p4:ident)*) => {{
-        router!(@call_pure $context, $handler, $params, {$id1 : $ty1 : 0}, {$id2 : $ty2 : 1}, {$id3 : $ty3 : 2}, {$id4 : $ty4 : 3})
-    }};
-
-    // Extract params from route, 5 params case
-    (@call, $context:expr, $handler:ident, $params:expr, $($p:ident)* {$id1:ident : $ty1:ty} $($p1:ident)* {$id2:ident : $ty2:ty} $($p2:ident)* {$id3:ident : $ty3:ty} $($p3:ident)* {$id4:ident : $ty4:ty} $($p4:ident)* {$id5:ident : $ty5:ty} $($p5:ident)*) => {{
-        router!(@call_pure $context, $handler, $params, {$id1 : $ty1 : 0}, {$id2 : $ty2 : 1}, {$id3 : $ty3 : 2}, {$id4 : $ty4 : 3}, {$id5 : $ty5 : 4})
-    }};
-
-    // Extract params from route, 6 params case
-    (@call, $context:expr, $handler:ident, $params:expr, $($p:ident)* {$id1:ident : $ty1:ty} $($p1:ident)* {$id2:ident : $ty2:ty} $($p2:ident)* {$id3:ident : $ty3:ty} $($p3:ident)* {$id4:ident : $ty4:ty} $($p4:ident)* {$id5:ident : $ty5:ty} $($p5:ident)* {$id6:ident : $ty6:ty} $($p6:ident)*) => {{
-        router!(@call_pure $context, $handler, $params, {$id1 : $ty1 : 0}, {$id2 : $ty2 : 1}, {$id3 : $ty3 : 2}, {$id4 : $ty4 : 3}, {$id5 : $ty5 : 4}, {$id6 : $ty6 : 5})
-    }};
-
-    // Extract params from route, 7 params case
-    (@call, $context:expr, $handler:ident, $params:expr, $($p:ident)* {$id1:ident : $ty1:ty} $($p1:ident)* {$id2:ident : $ty2:ty} $($p2:ident)* {$id3:ident : $ty3:ty} $($p3:ident)* {$id4:ident : $ty4:ty} $($p4:ident)* {$id5:ident : $ty5:ty} $($p5:ident)* {$id6:ident : $ty6:ty} $($p6:ident)* {$id7:ident : $ty7:ty} $($p7:ident)*) => {{
-        router!(@call_pure $context, $handler, $params, {$id1 : $ty1 : 0}, {$id2 : $ty2 : 1}, {$id3 : $ty3 : 2}, {$id4 : $ty4 : 3}, {$id5 : $ty5 : 4}, {$id6 : $ty6 : 5}, {$id6 : $ty6 : 6})
-    }};
-
-    // Test a particular route for match and forward to @call if there is match
-    (@one_route_with_method $context:expr, $method:expr, $path:expr, $default:expr, $expected_method: expr, $handler:ident, $($path_segment:tt)*) => {{
-        if $method != $expected_method { return None };
-        let mut s = "^".to_string();
-        $(
-            s.push('/');
-            let path_segment = stringify!($path_segment);
-            if path_segment.starts_with('{') {
-                s.push_str(r#"([^/?#]+)"#);
-            } else {
-                s.push_str(path_segment);
-            }
-        )*
-        // handle home case
-        if s.len() == 1 { s.push('/') }
-        s.push('$');
-        let re = $crate::__http_router_create_regex(&s);
-        if let Some(captures) = re.captures($path) {
-            let _matches: Vec<&str> = captures.iter().skip(1).filter(|x| x.is_some()).map(|x| x.unwrap().as_str()).collect();
-            Some(router!(@call, $context, $handler, _matches, $($path_segment)*))
-        } else {
-            None
-        }
-    }};
-
-    // Transform GET token to Method::GET
-    (@one_route $context:expr, $method:expr, $path:expr, $default:expr, GET, $handler:ident, $($path_segment:tt)*) => {
-        router!(@one_route_with_method $context, $method, $path, $default, $crate::Method::GET, $handler, $($path_segment)*)
-    };
-
-    // Transform POST token to Method::POST
-    (@one_route $context:expr, $method:expr, $path:expr, $default:expr, POST, $handler:ident, $($path_segment:tt)*) => {
-        router!(@one_route_with_method $context, $method, $path, $default, $crate::Method::POST, $handler, $($path_segment)*)
-    };
-    // Transform PUT token to Method::PUT
-    (@one_route $context:expr, $method:expr, $path:expr, $default:expr, PUT, $handler:ident, $($path_segment:tt)*) => {
-        router!(@one_route_with_method $context, $method, $path, $default, $crate::Method::PUT, $handler, $($path_segment)*)
-    };
-    // Transform PATCH token to Method::PATCH
-    (@one_route $context:expr, $method:expr, $path:expr, $default:expr, PATCH, $handler:ident, $($path_segment:tt)*) => {
-        router!(@one_route_with_method $context, $method, $path, $default, $crate::Method::PATCH, $handler, $($path_segment)*)
-    };
-    // Transform DELETE token to Method::DELETE
-    (@one_route $context:expr, $method:expr, $path:expr, $default:expr, DELETE, $handler:ident, $($path_segment:tt)*) => {
-        router!(@one_route_with_method $context, $method, $path, $default, $crate::Method::DELETE, $handler, $($path_segment)*)
-    };
-    // Transform OPTIONS token to Method::OPTIONS
-    (@one_route $context:expr, $method:expr, $path:expr, $default:expr, OPTIONS, $handler:ident, $($path_segment:tt)*) => {
-        router!(@one_route_with_method $context, $method, $path, $default, $crate::Method::OPTIONS, $handler, $($path_segment)*)
-    };
-
-    // Transform HEAD token to Method::HEAD
-    (@one_route $context:expr, $method:expr, $path:expr, $default:expr, HEAD, $handler:ident, $($path_segment:tt)*) => {
-        router!(@one_route_with_method $context, $method, $path, $default, $crate::Method::HEAD, $handler, $($path_segment)*)
-    };
-
-    // Transform TRACE token to Method::TRACE
-    (@one_route $context:expr, $method:expr, $path:expr, $default:expr, TRACE, $handler:ident, $($path_segment:tt)*) => {
-        router!(@one_route_with_method $context, $method, $path, $default, $crate::Method::TRACE, $handler, $($path_segment)*)
-    };
-
-    // Transform CONNECT token to Method::CONNECT
-    (@one_route $context:expr, $method:expr, $path:expr, $default:expr, CONNECT, $handler:ident, $($path_segment:tt)*) => {
-        router!(@one_route_with_method $context, $method, $path, $default, $crate::Method::CONNECT, $handler, $($path_segment)*)
-    };
-
-    // Entry pattern
-    ($($method_token:ident $(/$path_segment:tt)+ => $handler:ident,)* _ => $default:ident $(,)*) => {{
-        move |context, method: $crate::Method, path: &str| {
-            let mut result = None;
-            $(
-                if result.is_none() {
-                    // we use closure here so that we could make early return from macros inside of it
-                    let closure = || {
-                        router!(@one_route context, method, path, $default, $method_token, $handler, $($path_segment)*)
-                    };
-                    result = closure();
-                }
-            )*
-            result.unwrap_or_else(|| $default(&context))
-        }
-    }};
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_host<C: RequestHost>(context: &C) -> Option<&str> {
+    context.host()
+}
 
-    // Entry pattern - with home first
-    ($home_method_token:ident / => $home_handler:ident, $($method_token:ident $(/$path_segment:tt)+ => $handler:ident,)* _ => $default:ident $(,)*) => {{
-        move |context, method: $crate::Method, path: &str| {
-            let closure = || {
-                router!(@one_route context, method, path, $default, $home_method_token, $home_handler,)
-            };
-            let mut result = closure();
-            $(
-                if result.is_none() {
-                    // we use closure here so that we could make early return from macros inside of it
-                    let closure = || {
-                        router!(@one_route context, method, path, $default, $method_token, $handler, $($path_segment)*)
-                    };
-                    result = closure();
-                }
-            )*
-            result.unwrap_or_else(|| $default(&context))
-        }
-    }};
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_header_matches<C: RequestHeaders>(
+    context: &C,
+    name: &str,
+    value: &str,
+) -> bool {
+    headers::header_matches(context, name, value)
+}
+
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_version_matches<C: RequestHeaders>(context: &C, version: u32) -> bool {
+    ApiVersion::negotiate(context) == Some(ApiVersion(version))
+}
+
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_consumes<C: RequestHeaders>(
+    context: &C,
+    negotiation: &Negotiation,
+    media_type: &str,
+) -> bool {
+    negotiation.consumes(context.header("content-type"), media_type)
+}
+
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_produces<C: RequestHeaders>(
+    context: &C,
+    negotiation: &Negotiation,
+    media_type: &str,
+) -> bool {
+    negotiation.produces(context.header("accept"), media_type)
+}
+
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_redirect<C, R: From<Redirect>>(
+    context: &C,
+    chain: Chain<C, R>,
+    redirect: Redirect,
+) -> R {
+    middleware::through_chain(context, chain, redirect)
+}
+
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_route_info(
+    routes: &[RouteInfo],
+    method: Method,
+) -> ::std::borrow::Cow<'_, RouteInfo> {
+    route::info_for(routes, method)
+}
+
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_explain_route(
+    explainer: &mut Explainer,
+    methods: Option<Vec<Method>>,
+    host: Option<String>,
+    path: &str,
+    handler: &'static str,
+    checks: Vec<ParamCheck>,
+    conditional: bool,
+) {
+    explainer.push(methods, host, path, handler, checks, conditional)
+}
+
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_values<'b, 'a, I>(buffer: &'b mut [&'a str; 8], values: I) -> &'b [&'a str]
+where
+    I: Iterator<Item = &'a str>,
+{
+    // handlers take at most 7 params
+    let mut len = 0;
+    for (slot, value) in buffer.iter_mut().zip(values) {
+        *slot = value;
+        len += 1;
+    }
+    &buffer[..len]
+}
+
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_is_final<R>(result: &Option<Result<R, Rejection>>) -> bool {
+    !matches!(*result, None | Some(Err(Rejection::InvalidParam(_))))
+}
+
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_outcome<R>(result: &Option<Result<R, Rejection>>) -> Option<Outcome> {
+    match *result {
+        Some(Ok(_)) => Some(Outcome::Handled),
+        Some(Err(Rejection::InvalidParam(_))) => Some(Outcome::BadParam),
+        Some(Err(_)) => Some(Outcome::Rejected),
+        None => None,
+    }
+}
+
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_record<M: Metrics, R>(
+    metrics: &M,
+    method: Method,
+    route: Option<&'static str>,
+    outcome: Outcome,
+    started: ::std::time::Instant,
+    output: R,
+) -> R {
+    metrics.record(method, route, outcome, started.elapsed());
+    output
+}
+
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_record_async<M, R>(
+    metrics: &::std::sync::Arc<M>,
+    method: Method,
+    route: Option<&'static str>,
+    outcome: Outcome,
+    started: ::std::time::Instant,
+    output: BoxFuture<R>,
+) -> BoxFuture<R>
+where
+    M: Metrics + 'static,
+    R: 'static,
+{
+    let metrics = metrics.clone();
+    let done = move || metrics.record(method, route, outcome, started.elapsed());
+    metrics::on_ready(output, Box::new(done))
+}
+
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_trace_route<'a, I>(
+    template: &'static str,
+    handler: &'static str,
+    names: &[&'static str],
+    values: I,
+    redacted: &[&'static str],
+) where
+    I: Iterator<Item = &'a str>,
+{
+    #[cfg(feature = "tracing")]
+    trace::record_route(
+        template,
+        handler,
+        || names.iter().map(|name| name.to_string()).collect(),
+        values,
+        redacted,
+    );
+    #[cfg(not(feature = "tracing"))]
+    let _ = (template, handler, names, values, redacted);
+}
+
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_trace_guard(template: &'static str) {
+    #[cfg(feature = "tracing")]
+    trace::guard_skipped(template);
+    #[cfg(not(feature = "tracing"))]
+    let _ = template;
+}
+
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_trace_media(template: &'static str) {
+    #[cfg(feature = "tracing")]
+    trace::media_skipped(template);
+    #[cfg(not(feature = "tracing"))]
+    let _ = template;
+}
+
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_trace_result<R>(
+    template: &'static str,
+    result: &Option<Result<R, Rejection>>,
+) {
+    #[cfg(feature = "tracing")]
+    trace::route_result(template, result);
+    #[cfg(not(feature = "tracing"))]
+    let _ = (template, result);
+}
+
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub fn __http_router_trace_fallback() {
+    #[cfg(feature = "tracing")]
+    trace::fallback();
+}
+
+/// This is an implementation detail and *should not* be called directly!
+#[doc(hidden)]
+pub struct __HttpRouterSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl __HttpRouterSpan {
+    pub fn new(method: Method) -> Self {
+        #[cfg(not(feature = "tracing"))]
+        let _ = method;
+        __HttpRouterSpan {
+            #[cfg(feature = "tracing")]
+            span: trace::dispatch_span(method),
+        }
+    }
+
+    pub fn in_scope<T, F: FnOnce() -> T>(&self, f: F) -> T {
+        #[cfg(feature = "tracing")]
+        {
+            self.span.in_scope(f)
+        }
+        #[cfg(not(feature = "tracing"))]
+        f()
+    }
+
+    pub fn finish<R>(self, output: R) -> R {
+        output
+    }
 
-    // Entry pattern - default only
-    (_ => $default:ident $(,)*) => {
-        |context, _method: $crate::Method, _path: &str| {
-            $default(&context)
+    pub fn finish_async<T: 'static>(self, future: BoxFuture<T>) -> BoxFuture<T> {
+        #[cfg(feature = "tracing")]
+        {
+            trace::instrument(future, self.span)
         }
+        #[cfg(not(feature = "tracing"))]
+        future
     }
 }
 
@@ -404,9 +1004,9 @@ mod tests {
             (Method::GET, "/u", "404"),
             (Method::POST, "/", "404"),
         ];
-        for test_case in test_cases.into_iter() {
-            let (method, path, expected) = test_case.clone();
-            assert_eq!(router((), method.clone(), path), expected.to_string());
+        for test_case in test_cases.iter() {
+            let (method, path, expected) = *test_case;
+            assert_eq!(router((), method, path), expected.to_string());
         }
 
         let mut threads: Vec<thread::JoinHandle<_>> = Vec::new();
@@ -416,7 +1016,7 @@ mod tests {
                     let number = rand::random::<usize>() % test_cases.len();
                     let test_case = test_cases[number];
                     let (method, path, expected) = test_case;
-                    assert_eq!(router((), method.clone(), path), expected.to_string());
+                    assert_eq!(router((), method, path), expected.to_string());
                 }
             });
             threads.push(handle);
@@ -494,7 +1094,7 @@ mod tests {
     #[test]
     fn test_params_number() {
         let zero = |_: &()| String::new();
-        let one = |_: &(), p1: String| format!("{}", &p1);
+        let one = |_: &(), p1: String| p1;
         let two = |_: &(), p1: String, p2: String| format!("{}{}", &p1, &p2);
         let three = |_: &(), p1: String, p2: String, p3: String| format!("{}{}{}", &p1, &p2, &p3);
         let four = |_: &(), p1: String, p2: String, p3: String, p4: String| {
@@ -569,6 +1169,1051 @@ mod tests {
             "id1id2id3id4id5id6id7"
         );
     }
+
+    #[test]
+    fn test_methods() {
+        let health = |_: &(), method: Method| format!("health({:?})", method);
+        let get_file = |_: &(), path: String| format!("get_file({})", path);
+        let proxy = |_: &(), method: Method, id: u32, rest: String| {
+            format!("proxy({:?}, {}, {})", method, id, rest)
+        };
+        let fallback = |_: &()| "404".to_string();
+        let router = router!(
+            GET | HEAD /health => health,
+            GET /files/{*path} => get_file,
+            ANY /proxy/{id: u32}/{*rest} => proxy,
+            _ => fallback,
+        );
+        assert_eq!(router((), Method::GET, "/health"), "health(GET)");
+        assert_eq!(router((), Method::HEAD, "/health"), "health(HEAD)");
+        assert_eq!(router((), Method::POST, "/health"), "404");
+        assert_eq!(
+            router((), Method::GET, "/files/css/main.css"),
+            "get_file(css/main.css)"
+        );
+        assert_eq!(
+            router((), Method::PATCH, "/proxy/1/users/2"),
+            "proxy(PATCH, 1, users/2)"
+        );
+        assert_eq!(router((), Method::PATCH, "/proxy/x/users/2"), "404");
+
+        let routes = routes!(
+            GET | HEAD /health => health,
+            ANY /proxy/{id: u32}/{*rest} => proxy,
+            _ => fallback,
+        );
+        assert_eq!(routes.len(), 11);
+        assert_eq!(routes[0].method, Method::GET);
+        assert_eq!(routes[1].method, Method::HEAD);
+        assert_eq!(routes[2].path, "/proxy/{id}/{*rest}");
+        assert_eq!(routes[2].params[1].name, "rest");
+        assert_eq!(routes[2].params[1].ty, "String");
+    }
+
+    #[test]
+    fn test_extension_methods() {
+        let propfind = |_: &(), path: String| format!("propfind({})", path);
+        let lock = |_: &(), method: Method| format!("{:?}", method);
+        let fallback = |_: &()| "404".to_string();
+        let router = router!(
+            PROPFIND /files/{*path} => propfind,
+            LOCK | UNLOCK /files => lock,
+            _ => fallback,
+        );
+        assert_eq!(
+            router((), Method::extension("PROPFIND"), "/files/a/b"),
+            "propfind(a/b)"
+        );
+        assert_eq!(
+            router((), Method::extension("UNLOCK"), "/files"),
+            "Extension(UNLOCK)"
+        );
+        assert_eq!(router((), Method::GET, "/files/a/b"), "404");
+        assert_eq!(router((), Method::extension("MKCOL"), "/files"), "404");
+
+        let routes = routes!(
+            PROPFIND /files/{*path} => propfind,
+            _ => fallback,
+        );
+        assert_eq!(routes[0].method, Method::extension("PROPFIND"));
+    }
+
+    #[test]
+    fn test_scopes() {
+        let get_users = |_: &()| "get_users".to_string();
+        let get_user = |_: &(), id: u32| format!("get_user({})", id);
+        let get_transactions = |_: &(), id: u32| format!("get_transactions({})", id);
+        let get_transaction =
+            |_: &(), id: u32, hash: String| format!("get_transaction({}, {})", id, hash);
+        let get_home = |_: &()| "get_home".to_string();
+        let fallback = |_: &()| "404".to_string();
+        let router = router!(
+            scope /users {
+                GET / => get_users,
+                scope /{user_id: u32} {
+                    GET / => get_user,
+                    GET /transactions => get_transactions,
+                    GET /transactions/{hash: String} => get_transaction
+                }
+            }
+            GET / => get_home,
+            _ => fallback,
+        );
+        assert_eq!(router((), Method::GET, "/users"), "get_users");
+        assert_eq!(router((), Method::GET, "/users/12"), "get_user(12)");
+        assert_eq!(
+            router((), Method::GET, "/users/12/transactions"),
+            "get_transactions(12)"
+        );
+        assert_eq!(
+            router((), Method::GET, "/users/12/transactions/0x1"),
+            "get_transaction(12, 0x1)"
+        );
+        assert_eq!(router((), Method::GET, "/"), "get_home");
+        assert_eq!(router((), Method::GET, "/users/x"), "404");
+
+        let routes = routes!(
+            scope /users/{user_id: u32} {
+                GET /transactions => get_transactions,
+            },
+            GET / => get_home,
+            _ => fallback,
+        );
+        assert_eq!(routes[0].path, "/users/{user_id}/transactions");
+        assert_eq!(routes[0].params[0].name, "user_id");
+        assert_eq!(routes[1].path, "/");
+    }
+
+    #[test]
+    fn test_async() {
+        use futures::executor::block_on;
+        use std::future::{ready, Future};
+
+        #[derive(Clone)]
+        struct Context {
+            tenant: &'static str,
+        }
+
+        // same as `async fn get_user(context: Context, user_id: u32) -> String`
+        fn get_user(context: Context, user_id: u32) -> impl Future<Output = String> {
+            ready(format!("user {} of {}", user_id, context.tenant))
+        }
+        let get_users = |context: Context| ready(format!("users of {}", context.tenant));
+        let head = |_: Context, method: Method| ready(format!("{} /", method));
+        let not_found = |_: Context| ready("404".to_string());
+
+        let router = router!(async
+            GET | HEAD / => head,
+            GET /users => get_users,
+            GET /users/{user_id: u32} => get_user,
+            _ => not_found,
+        );
+        let context = Context { tenant: "acme" };
+        assert_eq!(
+            block_on(router(context.clone(), Method::HEAD, "/")),
+            "HEAD /"
+        );
+        assert_eq!(
+            block_on(router(context.clone(), Method::GET, "/users")),
+            "users of acme"
+        );
+        assert_eq!(
+            block_on(router(context.clone(), Method::GET, "/users/12")),
+            "user 12 of acme"
+        );
+        assert_eq!(
+            block_on(router(context.clone(), Method::GET, "/users/x")),
+            "404"
+        );
+        let empty = router!(async _ => not_found);
+        assert_eq!(block_on(empty(context, Method::GET, "/")), "404");
+        assert_eq!(
+            routes!(async GET /users => get_users, _ => not_found)[0].path,
+            "/users"
+        );
+    }
+
+    #[test]
+    fn test_extractors() {
+        struct Context {
+            token: Option<&'static str>,
+        }
+
+        struct User(&'static str);
+
+        impl FromRequest<Context> for User {
+            fn from_request(context: &Context) -> Result<Self, Rejection> {
+                context.token.map(User).ok_or(Rejection::Custom {
+                    status: 401,
+                    message: "Unauthorized".to_string(),
+                })
+            }
+        }
+
+        fn get_profile(_: &Context, user: User) -> String {
+            format!("profile of {}", user.0)
+        }
+        fn get_post(_: &Context, post_id: u32, user: Option<User>) -> String {
+            format!(
+                "post {} for {}",
+                post_id,
+                user.map_or("guest", |user| user.0)
+            )
+        }
+        fn rejected(_: &Context, rejection: Rejection) -> String {
+            format!("{} {}", rejection.status(), rejection)
+        }
+        fn not_found(_: &Context) -> String {
+            "404".to_string()
+        }
+
+        let router = router!(
+            GET /profile => get_profile,
+            GET /posts/{post_id: u32} => get_post,
+            ! => rejected,
+            _ => not_found,
+        );
+        let alice = || Context {
+            token: Some("alice"),
+        };
+        let guest = || Context { token: None };
+        assert_eq!(router(alice(), Method::GET, "/profile"), "profile of alice");
+        assert_eq!(router(guest(), Method::GET, "/profile"), "401 Unauthorized");
+        assert_eq!(router(guest(), Method::GET, "/posts/1"), "post 1 for guest");
+        assert_eq!(
+            router(guest(), Method::GET, "/posts/x"),
+            "400 Invalid param `post_id` = `x`: invalid digit found in string"
+        );
+        assert_eq!(router(guest(), Method::GET, "/comments/1"), "404");
+
+        let router = router!(
+            GET /profile => get_profile,
+            _ => not_found,
+        );
+        assert_eq!(router(guest(), Method::GET, "/profile"), "404");
+    }
+
+    #[test]
+    fn test_param_errors() {
+        #[derive(Debug)]
+        struct Hash(u64);
+
+        impl<'a> FromParam<'a> for Hash {
+            fn from_param(param: &'a str) -> Result<Self, ParamError> {
+                if !param.starts_with("0x") {
+                    return Err(ParamError::new("should start with `0x`"));
+                }
+                u64::from_str_radix(&param[2..], 16)
+                    .map(Hash)
+                    .map_err(ParamError::new)
+            }
+        }
+
+        fn get_block(_: &(), hash: Hash) -> String {
+            format!("block {}", hash.0)
+        }
+        fn get_block_by_number(_: &(), number: u64) -> String {
+            format!("block #{}", number)
+        }
+        fn get_file(_: &(), user: &str, path: &str) -> String {
+            format!("{} of {}", path, user)
+        }
+        fn rejected(_: &(), rejection: Rejection) -> String {
+            match rejection {
+                Rejection::InvalidParam(error) => {
+                    format!("{}: {} ({})", error.name(), error.message(), error.value())
+                }
+                rejection => rejection.to_string(),
+            }
+        }
+        fn not_found(_: &()) -> String {
+            "404".to_string()
+        }
+
+        let router = router!(
+            GET /blocks/{hash: Hash} => get_block,
+            GET /blocks/{number: u64} => get_block_by_number,
+            GET /files/{user: &str}/{*path: &str} => get_file,
+            ! => rejected,
+            _ => not_found,
+        );
+        assert_eq!(router((), Method::GET, "/blocks/0x1f"), "block 31");
+        assert_eq!(router((), Method::GET, "/blocks/12"), "block #12");
+        assert_eq!(
+            router((), Method::GET, "/blocks/0xz"),
+            "hash: invalid digit found in string (0xz)"
+        );
+        assert_eq!(
+            router((), Method::GET, "/blocks/z"),
+            "hash: should start with `0x` (z)"
+        );
+        assert_eq!(
+            router((), Method::GET, "/files/alice/docs/a.txt"),
+            "docs/a.txt of alice"
+        );
+        assert_eq!(router((), Method::POST, "/blocks/z"), "404");
+    }
+
+    #[test]
+    fn test_hosts() {
+        struct Context(&'static str);
+
+        impl RequestHost for Context {
+            fn host(&self) -> Option<&str> {
+                Some(self.0)
+            }
+        }
+
+        fn get_users(_: &Context, tenant: String) -> String {
+            format!("users of {}", tenant)
+        }
+        fn get_user(_: &Context, tenant: &str, env: &str, id: u32) -> String {
+            format!("user {} of {} ({})", id, tenant, env)
+        }
+        fn get_home(_: &Context) -> String {
+            "home".to_string()
+        }
+        fn get_admin_home(_: &Context) -> String {
+            "admin".to_string()
+        }
+        fn not_found(_: &Context) -> String {
+            "404".to_string()
+        }
+
+        let router = router!(
+            GET admin.example.com/ => get_admin_home,
+            GET / => get_home,
+            GET {tenant: String}.example.com/users => get_users,
+            scope /api {
+                GET {tenant: &str}.my-app.{env: &str}.com/users/{user_id: u32} => get_user,
+            },
+            _ => not_found,
+        );
+        let handle = |host, path| router(Context(host), Method::GET, path);
+        assert_eq!(handle("admin.example.com", "/"), "admin");
+        assert_eq!(handle("example.com", "/"), "home");
+        assert_eq!(handle("acme.example.com", "/users"), "users of acme");
+        assert_eq!(handle("example.com", "/users"), "404");
+        assert_eq!(
+            handle("acme.my-app.dev.com", "/api/users/1"),
+            "user 1 of acme (dev)"
+        );
+        assert_eq!(handle("acme.my-app.dev.com", "/api/users/x"), "404");
+
+        let routes = routes!(
+            GET {tenant: String}.example.com/users/{user_id: u32} => get_user,
+            _ => not_found,
+        );
+        assert_eq!(routes[0].host, Some("{tenant}.example.com".to_string()));
+        assert_eq!(routes[0].path, "/users/{user_id}");
+        assert_eq!(routes[0].params[0].name, "tenant");
+        assert_eq!(routes[0].params[1].name, "user_id");
+    }
+
+    #[test]
+    fn test_guards() {
+        fn is_admin(user: &&'static str) -> Guard {
+            match *user {
+                "admin" => Guard::Allow,
+                "" => Guard::reject(401, "Not logged in"),
+                _ => Guard::reject(403, "Admins only"),
+            }
+        }
+        fn is_beta(user: &&'static str) -> bool {
+            user.starts_with("beta")
+        }
+        fn logging(user: &&'static str, _: &RouteInfo, next: Next<&'static str, String>) -> String {
+            format!("[{}]", next.run(user))
+        }
+        fn list_users(_: &&'static str) -> String {
+            "list_users".to_string()
+        }
+        fn get_user(_: &&'static str, id: u32) -> String {
+            format!("get_user({})", id)
+        }
+        fn get_user_beta(_: &&'static str, id: u32) -> String {
+            format!("get_user_beta({})", id)
+        }
+        fn rejected(_: &&'static str, rejection: Rejection) -> String {
+            format!("{} {}", rejection.status(), rejection)
+        }
+        fn not_found(_: &&'static str) -> String {
+            "404".to_string()
+        }
+
+        let router = router!(
+            GET /admin/users => list_users if is_admin with [logging],
+            GET /users/{user_id: u32} => get_user_beta if is_beta,
+            GET /users/{user_id: u32} => get_user,
+            ! => rejected,
+            _ => not_found,
+        );
+        assert_eq!(router("admin", Method::GET, "/admin/users"), "[list_users]");
+        assert_eq!(
+            router("bob", Method::GET, "/admin/users"),
+            "403 Admins only"
+        );
+        assert_eq!(router("", Method::GET, "/admin/users"), "401 Not logged in");
+        assert_eq!(router("", Method::POST, "/admin/users"), "404");
+        assert_eq!(
+            router("beta-bob", Method::GET, "/users/1"),
+            "get_user_beta(1)"
+        );
+        assert_eq!(router("bob", Method::GET, "/users/1"), "get_user(1)");
+    }
+
+    #[test]
+    fn test_content_negotiation() {
+        use std::future::{ready, Future};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Clone)]
+        struct Context(Vec<(&'static str, &'static str)>);
+
+        impl RequestHeaders for Context {
+            fn header(&self, name: &str) -> Option<&str> {
+                self.0
+                    .iter()
+                    .find(|header| header.0.eq_ignore_ascii_case(name))
+                    .map(|header| header.1)
+            }
+        }
+
+        fn get_users_csv(_: &Context) -> String {
+            "users csv".to_string()
+        }
+        fn get_users_msgpack(_: &Context) -> String {
+            "users msgpack".to_string()
+        }
+        fn get_users(_: &Context) -> String {
+            "users json".to_string()
+        }
+        fn post_users(_: &Context) -> String {
+            "created".to_string()
+        }
+        fn rejected(_: &Context, rejection: Rejection) -> String {
+            format!("{} {}", rejection.status(), rejection)
+        }
+        fn not_found(_: &Context) -> String {
+            "404".to_string()
+        }
+
+        let router = router!(
+            GET /users => get_users_csv produces "text/csv",
+            GET /users => get_users_msgpack produces "application/msgpack",
+            GET /users => get_users produces "application/json",
+            POST /users => post_users consumes "application/json" produces "application/json",
+            ! => rejected,
+            _ => not_found,
+        );
+        let handle = |method, headers| router(Context(headers), method, "/users");
+        assert_eq!(handle(Method::GET, vec![]), "users csv");
+        assert_eq!(
+            handle(
+                Method::GET,
+                vec![(
+                    "accept",
+                    "text/csv;q=0.2, application/msgpack;q=0.5, */*;q=0.1"
+                )]
+            ),
+            "users msgpack"
+        );
+        assert_eq!(
+            handle(Method::GET, vec![("accept", "application/*, text/csv;q=0")]),
+            "users msgpack"
+        );
+        assert_eq!(
+            handle(Method::GET, vec![("accept", "text/html")]),
+            "406 No media type matches `text/html`"
+        );
+        assert_eq!(
+            handle(
+                Method::POST,
+                vec![("content-type", "application/json"), ("accept", "*/*")]
+            ),
+            "created"
+        );
+        assert_eq!(
+            handle(Method::POST, vec![("content-type", "text/csv")]),
+            "415 Unsupported content type `text/csv`"
+        );
+
+        // routes without media types are not tried again
+        static GUARDED: AtomicUsize = AtomicUsize::new(0);
+        fn skip(_: &Context) -> Guard {
+            GUARDED.fetch_add(1, Ordering::SeqCst);
+            Guard::Skip
+        }
+        let router = router!(
+            GET /users => get_users if skip,
+            GET /users => get_users_csv produces "text/csv",
+            GET /users => get_users produces "application/json",
+            _ => not_found,
+        );
+        let headers = vec![("accept", "application/json;q=0.5, application/xml")];
+        assert_eq!(
+            router(Context(headers), Method::GET, "/users"),
+            "users json"
+        );
+        assert_eq!(GUARDED.load(Ordering::SeqCst), 1);
+
+        // media types are checked before guards, by both kinds of routers
+        fn forbidden(_: &Context) -> Guard {
+            Guard::reject(403, "Forbidden")
+        }
+        let router = router!(
+            GET /admin => get_users if forbidden produces "text/csv",
+            ! => rejected,
+            _ => not_found,
+        );
+        let builder = Router::builder()
+            .route(Method::GET, "/admin", get_users)
+            .guard(forbidden)
+            .produces("text/csv")
+            .rejection(rejected)
+            .fallback(not_found)
+            .build();
+        let headers = vec![("accept", "application/json")];
+        let not_acceptable = "406 No media type matches `application/json`";
+        assert_eq!(
+            router(Context(headers.clone()), Method::GET, "/admin"),
+            not_acceptable
+        );
+        assert_eq!(
+            builder.handle(Context(headers), Method::GET, "/admin"),
+            not_acceptable
+        );
+        let headers = vec![("accept", "text/csv")];
+        assert_eq!(
+            router(Context(headers.clone()), Method::GET, "/admin"),
+            "403 Forbidden"
+        );
+        assert_eq!(
+            builder.handle(Context(headers), Method::GET, "/admin"),
+            "403 Forbidden"
+        );
+
+        fn get_users_async(_: Context) -> impl Future<Output = String> {
+            ready("users json".to_string())
+        }
+        fn get_users_csv_async(_: Context) -> impl Future<Output = String> {
+            ready("users csv".to_string())
+        }
+        fn not_found_async(_: Context) -> impl Future<Output = String> {
+            ready("404".to_string())
+        }
+
+        let router = router!(async
+            GET /users => get_users_csv_async produces "text/csv",
+            GET /users => get_users_async produces "application/json",
+            _ => not_found_async,
+        );
+        let handle = |accept| {
+            futures::executor::block_on(router(
+                Context(vec![("accept", accept)]),
+                Method::GET,
+                "/users",
+            ))
+        };
+        assert_eq!(handle("text/csv;q=0.9, application/json"), "users json");
+        assert_eq!(handle("text/csv, application/json;q=0.9"), "users csv");
+        assert_eq!(handle("text/html"), "404");
+    }
+
+    #[test]
+    fn test_redirects() {
+        use futures::executor::block_on;
+        use std::future::{ready, Future};
+
+        fn get_home(_: &()) -> String {
+            "home".to_string()
+        }
+        fn get_user(_: &(), id: u32) -> String {
+            format!("user {}", id)
+        }
+        fn get_file(_: &(), user: &str, path: &str) -> String {
+            format!("file {} of {}", path, user)
+        }
+        fn logging(_: &(), route: &RouteInfo, next: Next<(), String>) -> String {
+            format!("[{} {}]", route.handler, next.run(&()))
+        }
+        fn not_found(_: &()) -> String {
+            "404".to_string()
+        }
+
+        let router = router!(
+            GET / | /index | /home => get_home,
+            GET /users/{id: u32} | /people/{id: u32} => get_user,
+            GET /old/users/{id: u32} => redirect(308, "/users/{id}"),
+            scope /v1 with [logging] {
+                GET /users/{id: u32}/files/{*path} | /u/{id: u32}/f/{*path} => redirect(301, "/files/{id}/{path}"),
+            },
+            GET /files/{user: &str}/{*path: &str} => get_file,
+            _ => not_found,
+        );
+        assert_eq!(router((), Method::GET, "/"), "home");
+        assert_eq!(router((), Method::GET, "/home"), "home");
+        assert_eq!(router((), Method::GET, "/index"), "home");
+        assert_eq!(router((), Method::GET, "/people/7"), "user 7");
+        assert_eq!(router((), Method::GET, "/old/users/7"), "308 /users/7");
+        assert_eq!(router((), Method::GET, "/old/users/x"), "404");
+        assert_eq!(
+            router((), Method::GET, "/v1/u/7/f/docs/a.txt"),
+            "[redirect 301 /files/7/docs/a.txt]"
+        );
+        assert_eq!(
+            router((), Method::GET, "/files/7/docs/a.txt"),
+            "file docs/a.txt of 7"
+        );
+
+        let routes = routes!(
+            GET /users/{id: u32} | /people/{id: u32} => get_user,
+            GET /old/users/{id: u32} => redirect(308, "/users/{id}"),
+            _ => not_found,
+        );
+        assert_eq!(routes.len(), 3);
+        assert_eq!(routes[1].path, "/people/{id}");
+        assert_eq!(routes[1].handler, "get_user");
+        assert_eq!(routes[2].handler, "redirect");
+        assert_eq!(routes[2].redirect, Some(Redirect::new(308, "/users/{id}")));
+
+        fn get_user_async(_: (), id: u32) -> impl Future<Output = String> {
+            ready(format!("user {}", id))
+        }
+        fn not_found_async(_: ()) -> impl Future<Output = String> {
+            ready("404".to_string())
+        }
+
+        let router = router!(async
+            GET /old/users/{id: u32} => redirect(307, "/users/{id}"),
+            GET /users/{id: u32} => get_user_async,
+            _ => not_found_async,
+        );
+        assert_eq!(
+            block_on(router((), Method::GET, "/old/users/3")),
+            "307 /users/3"
+        );
+        assert_eq!(block_on(router((), Method::GET, "/users/3")), "user 3");
+    }
+
+    #[cfg(feature = "with_http")]
+    #[test]
+    fn test_deprecation() {
+        extern crate http;
+
+        use self::http::Response;
+        use futures::executor::block_on;
+        use std::future::{ready, Future};
+
+        fn get_users(_: &()) -> Response<String> {
+            Response::new("users".to_string())
+        }
+        fn not_found(_: &()) -> Response<String> {
+            Response::new("404".to_string())
+        }
+
+        let router = router!(
+            with [deprecation_headers],
+            GET /v1/users | /v1/people => get_users deprecated(since = "2026-06-01", sunset = "2027-01-01", link = "https://example.com/v2",),
+            GET /v0/users => get_users deprecated,
+            GET /v2/users => get_users,
+            _ => not_found,
+        );
+        for path in &["/v1/users", "/v1/people"] {
+            let response = router((), Method::GET, path);
+            assert_eq!(response.body(), "users");
+            assert_eq!(response.headers()["deprecation"], "@1780272000");
+            assert_eq!(
+                response.headers()["sunset"],
+                "Fri, 01 Jan 2027 00:00:00 GMT"
+            );
+            assert_eq!(
+                response.headers()["link"],
+                "<https://example.com/v2>; rel=\"deprecation\""
+            );
+        }
+        assert!(router((), Method::GET, "/v0/users").headers().is_empty());
+        assert!(router((), Method::GET, "/v2/users").headers().is_empty());
+
+        let routes = routes!(
+            GET /v1/users => get_users deprecated(sunset = "2027-01-01"),
+            GET /v2/users => get_users,
+            _ => not_found,
+        );
+        assert_eq!(
+            routes[0].deprecation,
+            Some(Deprecation::new().sunset("2027-01-01"))
+        );
+        assert_eq!(routes[1].deprecation, None);
+
+        fn get_users_async(_: ()) -> impl Future<Output = Response<String>> {
+            ready(Response::new("users".to_string()))
+        }
+        fn not_found_async(_: ()) -> impl Future<Output = Response<String>> {
+            ready(Response::new("404".to_string()))
+        }
+
+        let router = router!(async
+            with [deprecation_headers],
+            GET /v1/users => get_users_async deprecated(sunset = "2027-01-01"),
+            _ => not_found_async,
+        );
+        let response = block_on(router((), Method::GET, "/v1/users"));
+        assert_eq!(response.headers().get("deprecation"), None);
+        assert_eq!(
+            response.headers()["sunset"],
+            "Fri, 01 Jan 2027 00:00:00 GMT"
+        );
+    }
+
+    #[test]
+    fn test_header_routing() {
+        struct Context(Vec<(&'static str, &'static str)>);
+
+        impl RequestHeaders for Context {
+            fn header(&self, name: &str) -> Option<&str> {
+                self.0
+                    .iter()
+                    .find(|header| header.0.eq_ignore_ascii_case(name))
+                    .map(|header| header.1)
+            }
+        }
+
+        fn is_admin(context: &Context) -> bool {
+            context.header("x-user") == Some("admin")
+        }
+        fn get_users(_: &Context, version: Option<ApiVersion>) -> String {
+            format!("users {:?}", version)
+        }
+        fn get_users_v2(_: &Context, version: ApiVersion) -> String {
+            format!("users v{}", version.0)
+        }
+        fn get_acme_users(_: &Context) -> String {
+            "acme users".to_string()
+        }
+        fn get_admin_users(_: &Context) -> String {
+            "admin users".to_string()
+        }
+        fn not_found(_: &Context) -> String {
+            "404".to_string()
+        }
+
+        let router = router!(
+            GET /users => get_users_v2 where version = 2,
+            GET /users => get_admin_users where "x-tenant" = "acme" if is_admin,
+            GET /users => get_acme_users where "x-tenant" = "acme",
+            GET /users => get_users,
+            _ => not_found,
+        );
+        let handle = |headers| router(Context(headers), Method::GET, "/users");
+        assert_eq!(handle(vec![("X-API-Version", "2")]), "users v2");
+        assert_eq!(
+            handle(vec![("accept", "application/json; version=2")]),
+            "users v2"
+        );
+        assert_eq!(
+            handle(vec![("x-api-version", "3")]),
+            "users Some(ApiVersion(3))"
+        );
+        assert_eq!(handle(vec![("x-tenant", "acme")]), "acme users");
+        assert_eq!(
+            handle(vec![("x-tenant", "acme"), ("x-user", "admin")]),
+            "admin users"
+        );
+        assert_eq!(handle(vec![]), "users None");
+    }
+
+    #[test]
+    fn test_middleware() {
+        use futures::executor::block_on;
+        use std::future::{ready, Future};
+
+        fn logging(
+            user: &&'static str,
+            route: &RouteInfo,
+            next: Next<&'static str, String>,
+        ) -> String {
+            let params = next.params();
+            format!(
+                "{} {} {:?} [{}]",
+                route.method,
+                route.path,
+                params,
+                next.run(user)
+            )
+        }
+        fn auth(
+            user: &&'static str,
+            route: &RouteInfo,
+            next: Next<&'static str, String>,
+        ) -> String {
+            match *user {
+                "admin" => next.run(user),
+                _ => format!(
+                    "{} of {} {} is forbidden",
+                    route.handler,
+                    route.params[0].name,
+                    next.params()[0]
+                ),
+            }
+        }
+        fn impersonate(
+            _: &&'static str,
+            _: &RouteInfo,
+            next: Next<&'static str, String>,
+        ) -> String {
+            next.run(&"alice")
+        }
+        fn get_user(user: &&'static str, id: u32) -> String {
+            format!("user {} for {}", id, user)
+        }
+        fn delete_user(user: &&'static str, id: u32) -> String {
+            format!("user {} deleted by {}", id, user)
+        }
+        fn health(_: &&'static str, method: Method) -> String {
+            format!("health {}", method)
+        }
+        fn not_found(_: &&'static str) -> String {
+            "404".to_string()
+        }
+
+        let router = router!(
+            GET | HEAD /health => health,
+            with [logging],
+            GET /users/{user_id: u32} => get_user with [impersonate],
+            scope /admin with [auth] {
+                DELETE /users/{user_id: u32} => delete_user,
+            },
+            _ => not_found,
+        );
+        assert_eq!(router("bob", Method::HEAD, "/health"), "health HEAD");
+        assert_eq!(
+            router("bob", Method::GET, "/users/1"),
+            "GET /users/{user_id} [\"1\"] [user 1 for alice]"
+        );
+        assert_eq!(
+            router("bob", Method::DELETE, "/admin/users/2"),
+            "DELETE /admin/users/{user_id} [\"2\"] [delete_user of user_id 2 is forbidden]"
+        );
+        assert_eq!(
+            router("admin", Method::DELETE, "/admin/users/1"),
+            "DELETE /admin/users/{user_id} [\"1\"] [user 1 deleted by admin]"
+        );
+        assert_eq!(router("bob", Method::GET, "/users/x"), "404");
+
+        fn timing(
+            _: &&'static str,
+            route: &RouteInfo,
+            next: Next<&'static str, BoxFuture<String>>,
+        ) -> BoxFuture<String> {
+            let path = route.path.clone();
+            Box::pin(futures::FutureExt::map(
+                next.run(&"alice"),
+                move |response| format!("{} [{}]", path, response),
+            ))
+        }
+        fn get_user_async(user: &'static str, id: u32) -> impl Future<Output = String> {
+            ready(format!("user {} for {}", id, user))
+        }
+        fn not_found_async(_: &'static str) -> impl Future<Output = String> {
+            ready("404".to_string())
+        }
+
+        let router = router!(async
+            with [timing],
+            GET /users/{user_id: u32} => get_user_async,
+            _ => not_found_async,
+        );
+        assert_eq!(
+            block_on(router("bob", Method::GET, "/users/1")),
+            "/users/{user_id} [user 1 for alice]"
+        );
+    }
+
+    #[test]
+    fn test_metrics() {
+        use futures::executor::block_on;
+        use std::future::ready;
+        use std::sync::Arc;
+
+        fn is_admin(_: &()) -> Guard {
+            Guard::Reject(Rejection::MissingApiVersion)
+        }
+        fn get_user(_: &(), user_id: u32) -> String {
+            format!("user {}", user_id)
+        }
+        fn get_admin(_: &()) -> String {
+            "admin".to_string()
+        }
+        fn not_found(_: &()) -> String {
+            "404".to_string()
+        }
+
+        let metrics = Arc::new(MetricsRegistry::new());
+        let router = router!(
+            metrics(metrics.clone()),
+            GET / => get_admin,
+            GET /users/{user_id: u32} => get_user,
+            GET /admin => get_admin if is_admin,
+            _ => not_found,
+        );
+        router((), Method::GET, "/");
+        router((), Method::GET, "/users/1");
+        router((), Method::GET, "/users/2");
+        router((), Method::GET, "/users/x");
+        router((), Method::DELETE, "/users/1");
+        router((), Method::GET, "/admin");
+        router((), Method::GET, "/missing");
+        let count = |method, route, outcome| metrics.count(method, route, outcome);
+        let route = Some("/users/{user_id}");
+        assert_eq!(count(Method::GET, Some("/"), Outcome::Handled), 1);
+        assert_eq!(count(Method::GET, route, Outcome::Handled), 2);
+        assert_eq!(count(Method::GET, route, Outcome::BadParam), 1);
+        assert_eq!(count(Method::DELETE, None, Outcome::MethodNotAllowed), 1);
+        assert_eq!(count(Method::GET, Some("/admin"), Outcome::Rejected), 1);
+        assert_eq!(count(Method::GET, None, Outcome::Fallback), 1);
+
+        let routes = routes!(
+            metrics(metrics.clone()),
+            GET /users/{user_id: u32} => get_user,
+            _ => not_found,
+        );
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].method, Method::GET);
+        assert_eq!(routes[0].path, "/users/{user_id}");
+
+        let get_file = |_: (), path: String| ready(format!("file {}", path));
+        let not_found = |_: ()| ready("404".to_string());
+        let metrics = Arc::new(MetricsRegistry::new());
+        let router = router!(async
+            metrics(metrics.clone()),
+            GET /files/{*path: String} => get_file,
+            _ => not_found,
+        );
+        let future = router((), Method::GET, "/files/a/b");
+        let route = Some("/files/{*path}");
+        assert_eq!(metrics.count(Method::GET, route, Outcome::Handled), 0);
+        assert_eq!(block_on(future), "file a/b");
+        assert_eq!(metrics.count(Method::GET, route, Outcome::Handled), 1);
+        let routes = routes!(async
+            metrics(metrics.clone()),
+            GET /files/{*path: String} => get_file,
+            _ => not_found,
+        );
+        assert_eq!(routes[0].handler, "get_file");
+    }
+
+    #[cfg(feature = "with_hyper")]
+    #[test]
+    fn test_hyper_service_fn() {
+        extern crate http;
+
+        use self::http::{Request, Response, StatusCode};
+        use futures::executor::block_on;
+        use hyper_lib::service::{service_fn, Service};
+        use std::convert::Infallible;
+        use std::future::{ready, Future};
+
+        type Context = RequestContext<&'static str, String>;
+        type HttpResult = Result<Response<String>, Infallible>;
+
+        fn post_user(context: Context, user_id: u32) -> impl Future<Output = HttpResult> {
+            ready(Ok(Response::new(format!(
+                "{} {} saved in {}",
+                user_id,
+                context.body(),
+                context.state()
+            ))))
+        }
+        fn not_found(_: Context) -> impl Future<Output = HttpResult> {
+            let mut response = Response::new(String::new());
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            ready(Ok(response))
+        }
+
+        let router = router!(async
+            POST /users/{user_id: u32} => post_user,
+            _ => not_found,
+        );
+        let service = service_fn(move |request: Request<String>| {
+            dispatch(router, "db", request).unwrap_or_else(|_| {
+                let mut response = Response::new(String::new());
+                *response.status_mut() = StatusCode::NOT_IMPLEMENTED;
+                Box::pin(ready(Ok(response)))
+            })
+        });
+        let request = |method: &str, uri: &str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .body("alice".to_string())
+                .unwrap()
+        };
+        let response = block_on(service.call(request("POST", "/users/1"))).unwrap();
+        assert_eq!(response.body(), "1 alice saved in db");
+        let response = block_on(service.call(request("GET", "/users/1"))).unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = block_on(service.call(request(&"X".repeat(32), "/"))).unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+    }
+
+    #[test]
+    fn test_routes() {
+        let routes = routes!(
+            GET / => get_home,
+            POST /users => post_users,
+            PATCH /users/{user_id: u32}/transactions/{hash: String} => patch_transactions,
+            _ => not_found,
+        );
+        assert_eq!(
+            routes,
+            vec![
+                RouteInfo {
+                    method: Method::GET,
+                    host: None,
+                    path: "/".to_string(),
+                    params: vec![],
+                    handler: "get_home",
+                    redirect: None,
+                    deprecation: None,
+                    consumes: None,
+                    produces: None,
+                },
+                RouteInfo {
+                    method: Method::POST,
+                    host: None,
+                    path: "/users".to_string(),
+                    params: vec![],
+                    handler: "post_users",
+                    redirect: None,
+                    deprecation: None,
+                    consumes: None,
+                    produces: None,
+                },
+                RouteInfo {
+                    method: Method::PATCH,
+                    host: None,
+                    path: "/users/{user_id}/transactions/{hash}".to_string(),
+                    params: vec![
+                        ParamInfo {
+                            name: "user_id".to_string(),
+                            ty: "u32",
+                        },
+                        ParamInfo {
+                            name: "hash".to_string(),
+                            ty: "String",
+                        },
+                    ],
+                    handler: "patch_transactions",
+                    redirect: None,
+                    deprecation: None,
+                    consumes: None,
+                    produces: None,
+                },
+            ]
+        );
+        assert!(routes!(_ => not_found).is_empty());
+    }
 }
 
 // cargo +nightly rustc -- -Zunstable-options --pretty=expanded
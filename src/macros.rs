@@ -0,0 +1,1187 @@
+/// This macro returns a closure that takes 3 params. See crate doc for more details.
+///
+/// ### Methods
+/// Besides the standard methods, any token can be used as a method,
+/// e.g. `PROPFIND /files/{*path} => propfind`. `ANY` matches every method.
+///
+/// ### Scopes
+/// Routes sharing a prefix can be grouped into a scope. Scopes can be nested.
+///
+/// ```rust
+/// let router = router!(
+///     GET /users => get_users,
+///     scope /users/{user_id: usize} {
+///         GET /transactions => get_transactions,
+///         POST /transactions => post_transactions,
+///     },
+///     _ => not_found,
+/// );
+/// ```
+///
+/// ### Hosts
+/// A route can start with a host, e.g. `GET {tenant: String}.example.com/users => get_users`.
+/// Params of the host are passed to the handler before the params of the path, and the context
+/// has to implement `RequestHost`. Routes without a host match any host.
+///
+/// ### Async handlers
+/// With `async` before the routes, handlers take the context by value (it is
+/// cloned for the matched route) and return futures, e.g. `async fn` handlers.
+/// The closure returns a `BoxFuture` of the handlers' output.
+///
+/// ```rust
+/// let router = router!(async
+///     GET /users/{user_id: u64} => get_user, // async fn get_user(context: Context, user_id: u64) -> Response
+///     _ => not_found,
+/// );
+/// let response = router(context, method, path).await;
+/// ```
+///
+/// ### Rejections
+/// Handlers may take `FromRequest` extractors after path params. Requests
+/// rejected by an extractor go to the rejection handler (`!`), which comes right
+/// before the fallback route, or to the fallback if there is none. So do requests
+/// with a path param that can't be parsed with `FromParam`, if no other route matches.
+///
+/// ```rust
+/// let router = router!(
+///     PUT /users/{user_id: u64} => put_user, // fn put_user(context: &Context, user_id: u64, user: Json<User>)
+///     ! => rejected,                         // fn rejected(context: &Context, rejection: Rejection)
+///     _ => not_found,
+/// );
+/// ```
+///
+/// ### Guards
+/// `if` adds a guard to a route. It runs once the path matches and can let the route
+/// handle the request, skip the route or reject the request. See `Guard` for details.
+///
+/// ```rust
+/// let router = router!(
+///     GET /admin/users => list_users if is_admin, // fn is_admin(context: &Context) -> Guard
+///     DELETE /users/{user_id: u64} => delete_user if is_admin with [audit],
+///     _ => not_found,
+/// );
+/// ```
+///
+/// ### Headers and versions
+/// `where` adds header conditions to a route, the route is skipped unless they are met.
+/// The context has to implement `RequestHeaders`. See `ApiVersion` for how versions are negotiated.
+///
+/// ```rust
+/// let router = router!(
+///     GET /users => get_users_v2 where version = 2,
+///     GET /users => get_acme_users where "x-tenant" = "acme" if is_admin,
+///     GET /users => get_users,
+///     _ => not_found,
+/// );
+/// ```
+///
+/// ### Content negotiation
+/// `consumes` and `produces` add media types to a route, matched against `Content-Type` and `Accept`.
+/// Quality values of `Accept` choose between routes with the same path. If no route fits,
+/// the request is rejected with 415 or 406. Media types are checked before guards and header
+/// conditions. The context has to implement `RequestHeaders`.
+///
+/// ```rust
+/// let router = router!(
+///     GET /users => get_users_csv produces "text/csv",
+///     GET /users => get_users produces "application/json",
+///     POST /users => post_users consumes "application/json",
+///     _ => not_found,
+/// );
+/// ```
+///
+/// ### Redirects and aliases
+/// `redirect(status, target)` in place of a handler redirects to `target` with the params
+/// of the path substituted by name. The router output has to implement `From<Redirect>`.
+/// Alternative paths of a route are separated with `|`, each of them becomes a route of its own.
+///
+/// ```rust
+/// let router = router!(
+///     GET /users/{id: u32} | /people/{id: u32} => get_user,
+///     GET /old/users/{id: u32} => redirect(308, "/users/{id}"),
+///     _ => not_found,
+/// );
+/// ```
+///
+/// ### Deprecation
+/// `deprecated(since = "..", sunset = "..", link = "..")` marks a route as deprecated in its `RouteInfo`,
+/// the fields are optional. `deprecation_headers` middleware announces it to clients.
+///
+/// ```rust
+/// let router = router!(
+///     with [deprecation_headers],
+///     GET /v1/users => get_users deprecated(sunset = "2027-01-01"),
+///     _ => not_found,
+/// );
+/// ```
+///
+/// ### Middleware
+/// `with [..]` adds middleware to a route, to a scope (`scope /admin with [auth] {..}`)
+/// or, as a separate item, to all of the following routes of the enclosing scope or router.
+/// Outer middleware runs first. See `Middleware` for details.
+///
+/// ```rust
+/// let router = router!(
+///     with [logging],
+///     GET /users/{user_id: u64} => get_user with [cache], // fn cache(context: &Context, route: &RouteInfo, next: Next<Context, Response>) -> Response
+///     scope /admin with [auth] {
+///         DELETE /users/{user_id: u64} => delete_user,
+///     },
+///     _ => not_found,
+/// );
+/// ```
+///
+/// ### Metrics
+/// `metrics(..)` as the first item records every request with a `Metrics` hook, labelled by
+/// the template of the route that decided it, the same way as `RouterBuilder::metrics`.
+/// Async routers record a request once its future completes.
+///
+/// ```rust
+/// let metrics = Arc::new(MetricsRegistry::new());
+/// let router = router!(
+///     metrics(metrics.clone()),
+///     GET /users/{user_id: u64} => get_user,
+///     _ => not_found,
+/// );
+/// ```
+///
+/// ### Tracing
+/// With the `tracing` feature each request is handled in a `dispatch` span, the same way as
+/// in `Router::builder()`. `redact(..)` as the first item, after `metrics(..)` if any, hides
+/// the values of the named params, e.g. of tokens.
+///
+/// ```rust
+/// let router = router!(
+///     redact(token),
+///     GET /invites/{invite_id: u64}/{token: String} => get_invite,
+///     _ => not_found,
+/// );
+/// ```
+///
+/// ### Limitations:
+/// - Fallback route (`_`) is required and should come last
+/// - Each route or scope is one level of macro recursion, so very large routers
+///   may need a higher `#![recursion_limit]`
+///
+/// ### Performace
+/// Macro routers itself has almost no cost, so you can call it
+/// everywhere as many times as you like. The closure that it returns
+/// have some cost (approx 50 microseconds per one call).
+///
+/// ### Thread safety
+/// The closure returned by this macro is thread-safe.
+#[macro_export]
+macro_rules! router {
+    // convert params from string
+    // invalid params are returned as rejections, which don't stop matching of the other routes
+    (@parse_type $value:expr, $id:ident, $ty:ty) => {{
+        let value = $value;
+        match <$ty as $crate::FromParam<_>>::from_param(value) {
+            Ok(value) => value,
+            Err(e) => {
+                let error = e.with_param(stringify!($id), value);
+                return Some(Err($crate::Rejection::InvalidParam(error)));
+            }
+        }
+    }};
+
+    // Call handler when all path segments are processed. Parsed params are passed
+    // as a tuple to `$call`, which extracts the rest of the handler arguments
+    // and calls the handler through the middleware chain
+    (@call $call:path, $context:ident, $handler:tt, $params:ident, $chain:ident, [$($arg:tt)*]) => {
+        $call(&$handler, &$context, ($($arg)*), $chain)
+    };
+
+    // Host params are followed by path params
+    (@call $call:path, $context:ident, $handler:tt, $params:ident, $chain:ident, [$($arg:tt)*] @path $path_params:ident $($path_segment:tt)*) => {
+        router!(@call $call, $context, $handler, $path_params, $chain, [$($arg)*] $($path_segment)*)
+    };
+
+    // Wildcard param, String by default
+    (@call $call:path, $context:ident, $handler:tt, $params:ident, $chain:ident, [$($arg:tt)*] {* $id:ident} $($path_segment:tt)*) => {
+        router!(@call $call, $context, $handler, $params, $chain, [$($arg)* router!(@parse_type $params.next()?, $id, String),] $($path_segment)*)
+    };
+
+    // Typed wildcard param
+    (@call $call:path, $context:ident, $handler:tt, $params:ident, $chain:ident, [$($arg:tt)*] {* $id:ident : $ty:ty} $($path_segment:tt)*) => {
+        router!(@call $call, $context, $handler, $params, $chain, [$($arg)* router!(@parse_type $params.next()?, $id, $ty),] $($path_segment)*)
+    };
+
+    // Typed param
+    (@call $call:path, $context:ident, $handler:tt, $params:ident, $chain:ident, [$($arg:tt)*] {$id:ident : $ty:ty} $($path_segment:tt)*) => {
+        router!(@call $call, $context, $handler, $params, $chain, [$($arg)* router!(@parse_type $params.next()?, $id, $ty),] $($path_segment)*)
+    };
+
+    // Static segment
+    (@call $call:path, $context:ident, $handler:tt, $params:ident, $chain:ident, [$($arg:tt)*] $static_segment:tt $($path_segment:tt)*) => {
+        router!(@call $call, $context, $handler, $params, $chain, [$($arg)*] $($path_segment)*)
+    };
+
+    // Check if the request method is one of the route methods
+    (@method_matches $method:expr, ANY) => {
+        true
+    };
+
+    (@method_matches $method:expr, $method_token:ident) => {
+        $method == router!(@method $method_token)
+    };
+
+    // Transform method token to Method, e.g. GET to Method::GET
+    (@method GET) => { $crate::Method::GET };
+    (@method POST) => { $crate::Method::POST };
+    (@method PUT) => { $crate::Method::PUT };
+    (@method PATCH) => { $crate::Method::PATCH };
+    (@method DELETE) => { $crate::Method::DELETE };
+    (@method OPTIONS) => { $crate::Method::OPTIONS };
+    (@method HEAD) => { $crate::Method::HEAD };
+    (@method CONNECT) => { $crate::Method::CONNECT };
+    (@method TRACE) => { $crate::Method::TRACE };
+
+    // Other tokens are extension methods, e.g. PROPFIND
+    (@method $method_token:ident) => {
+        $crate::Method::extension(stringify!($method_token))
+    };
+
+    // Middleware chain of a route, without middleware the handler is called directly
+    (@chain $method:expr, $values:ident, [], [$($method_token:ident)+], [$($host_token:tt)*], $handler:tt, [$($guard:tt)*], $($path_segment:tt)*) => {
+        |context, next| next.run(context)
+    };
+
+    (@chain $method:expr, $values:ident, [$($middleware:ident)+], [$($method_token:ident)+], [$($host_token:tt)*], $handler:tt, [$($guard:tt)*], $($path_segment:tt)*) => {
+        |context, next| {
+            // described once per route
+            static ROUTES: ::std::sync::OnceLock<Vec<$crate::RouteInfo>> = ::std::sync::OnceLock::new();
+            let routes = ROUTES.get_or_init(|| {
+                let mut routes = Vec::new();
+                $crate::routes!(@methods routes, [$($method_token)+], [$($host_token)*], $handler, [$($guard)*], $($path_segment)*);
+                routes
+            });
+            let route = $crate::__http_router_route_info(routes, $method);
+            let route: &$crate::RouteInfo = &route;
+            router!(@chain_call context, route, $values, next, [$($middleware)+])
+        }
+    };
+
+    // Middleware runs in order, the last one runs the handler
+    (@chain_call $context:ident, $route:ident, $values:ident, $next:ident, [$middleware:ident]) => {
+        $middleware($context, $route, $next.with_params($values))
+    };
+
+    (@chain_call $context:ident, $route:ident, $values:ident, $next:ident, [$middleware:ident $($more_middleware:ident)+]) => {{
+        let mut next = Some($next);
+        $middleware($context, $route, $crate::Next::new(&mut |context| {
+            let next = next.take().expect("Next is run once");
+            router!(@chain_call context, $route, $values, next, [$($more_middleware)+])
+        }).with_params($values))
+    }};
+
+    // Raw param values for middleware, collected only for routes that have some
+    (@values $buffer:ident, [], $host_params:ident, $params:ident) => {
+        &[]
+    };
+
+    (@values $buffer:ident, [$($middleware:ident)+], $host_params:ident, $params:ident) => {
+        $crate::__http_router_values(&mut $buffer, $host_params.clone().chain($params.clone()))
+    };
+
+    // Raw host params, routes without a host have none
+    (@host $context:ident, []) => {
+        ::std::iter::empty::<&str>()
+    };
+
+    (@host $context:ident, [$($host_token:tt)+]) => {{
+        // compiled once per route
+        static HOST: ::std::sync::OnceLock<$crate::HostPattern> = ::std::sync::OnceLock::new();
+        let host = HOST.get_or_init(|| $crate::HostPattern::new(concat!($(stringify!($host_token)),+)));
+        host.captures($crate::__http_router_host(&$context)?)?
+    }};
+
+    // Once the path matches, media types are checked first, like in `Router::builder()`,
+    // then guards and header conditions run in order. A guard can skip the route or reject the request
+    (@guard $context:ident, $negotiation:ident, $template:ident, [$($guard:tt)*]) => {
+        $(router!(@consumes_one $context, $negotiation, $template, $guard);)*
+        $(router!(@produces_one $context, $negotiation, $template, $guard);)*
+        $(router!(@guard_one $context, $negotiation, $template, $guard);)*
+    };
+
+    // Media types are checked with the negotiation state of the request,
+    // which reports 415 or 406 if no route fits
+    (@consumes_one $context:ident, $negotiation:ident, $template:ident, (consumes $media_type:literal)) => {
+        if !$crate::__http_router_consumes(&$context, &$negotiation, $media_type) {
+            $crate::__http_router_trace_media($template);
+            return None
+        }
+    };
+
+    (@consumes_one $context:ident, $negotiation:ident, $template:ident, $guard:tt) => {
+        ()
+    };
+
+    (@produces_one $context:ident, $negotiation:ident, $template:ident, (produces $media_type:literal)) => {
+        if !$crate::__http_router_produces(&$context, &$negotiation, $media_type) {
+            $crate::__http_router_trace_media($template);
+            return None
+        }
+    };
+
+    (@produces_one $context:ident, $negotiation:ident, $template:ident, $guard:tt) => {
+        ()
+    };
+
+    (@guard_one $context:ident, $negotiation:ident, $template:ident, (consumes $media_type:literal)) => {
+        ()
+    };
+
+    (@guard_one $context:ident, $negotiation:ident, $template:ident, (produces $media_type:literal)) => {
+        ()
+    };
+
+    // Deprecation only describes the route
+    (@guard_one $context:ident, $negotiation:ident, $template:ident, (deprecated $($deprecation:tt)*)) => {
+        ()
+    };
+
+    // Header conditions skip the route unless they are met, like guards
+    (@guard_one $context:ident, $negotiation:ident, $template:ident, (version $version:literal)) => {
+        if !$crate::__http_router_version_matches(&$context, $version) {
+            $crate::__http_router_trace_guard($template);
+            return None
+        }
+    };
+
+    (@guard_one $context:ident, $negotiation:ident, $template:ident, (header $name:literal $value:literal)) => {
+        if !$crate::__http_router_header_matches(&$context, $name, $value) {
+            $crate::__http_router_trace_guard($template);
+            return None
+        }
+    };
+
+    (@guard_one $context:ident, $negotiation:ident, $template:ident, $guard:ident) => {
+        match $crate::Guard::from($guard(&$context)) {
+            $crate::Guard::Allow => (),
+            $crate::Guard::Skip => {
+                $crate::__http_router_trace_guard($template);
+                return None
+            }
+            $crate::Guard::Reject(rejection) => return Some(Err(rejection)),
+        }
+    };
+
+    // Typed params of a redirect route are parsed, so that invalid ones don't match
+    (@validate $params:ident,) => {
+        ()
+    };
+
+    (@validate $params:ident, {* $id:ident} $($path_segment:tt)*) => {
+        $params.next()?;
+        router!(@validate $params, $($path_segment)*)
+    };
+
+    (@validate $params:ident, {* $id:ident : $ty:ty} $($path_segment:tt)*) => {
+        let _ = router!(@parse_type $params.next()?, $id, $ty);
+        router!(@validate $params, $($path_segment)*)
+    };
+
+    (@validate $params:ident, {$id:ident : $ty:ty} $($path_segment:tt)*) => {
+        let _ = router!(@parse_type $params.next()?, $id, $ty);
+        router!(@validate $params, $($path_segment)*)
+    };
+
+    (@validate $params:ident, $static_segment:tt $($path_segment:tt)*) => {
+        router!(@validate $params, $($path_segment)*)
+    };
+
+    // Redirect route - params of the path are substituted into the target by name
+    (@one_route_with_args $call:path, $context:ident, $negotiation:ident, $redacted:ident, $method:expr, $path:expr, [$($method_token:ident)+], [$($host_token:tt)*], [$($arg:tt)*], [$($guard:tt)*], [$($middleware:ident)*], (redirect $status:literal $target:literal), $($path_segment:tt)*) => {{
+        if !($(router!(@method_matches $method, $method_token))||+) { return None };
+        #[allow(unused_variables)]
+        let host_params = router!(@host $context, [$($host_token)*]);
+        // compiled once per route
+        static PATTERN: ::std::sync::OnceLock<$crate::Pattern> = ::std::sync::OnceLock::new();
+        let pattern = PATTERN.get_or_init(|| $crate::Pattern::new(concat!($("/", stringify!($path_segment)),*)));
+        static TARGET: ::std::sync::OnceLock<$crate::RedirectTarget> = ::std::sync::OnceLock::new();
+        let target = TARGET.get_or_init(|| $crate::RedirectTarget::new($status, $target, pattern.params()));
+        if let Some(captures) = pattern.captures($path) {
+            let template = router!(@template [] $($path_segment)*);
+            router!(@guard $context, $negotiation, template, [$($guard)*]);
+            #[allow(unused_mut, unused_variables)]
+            let mut params = captures.clone();
+            router!(@validate params, $($path_segment)*);
+            $crate::__http_router_trace_route(template, "redirect", &router!(@param_names [] $($host_token)* $($path_segment)*), host_params.clone().chain(captures.clone()), $redacted);
+            #[allow(unused_mut, unused_variables)]
+            let mut buffer = [""; 8];
+            #[allow(unused_variables)]
+            let middleware_values: &[&str] = router!(@values buffer, [$($middleware)*], host_params, captures);
+            let values: Vec<&str> = captures.collect();
+            let chain: $crate::Chain<_, _> = &router!(@chain $method, middleware_values, [$($middleware)*], [$($method_token)+], [$($host_token)*], (redirect $status $target), [$($guard)*], $($path_segment)*);
+            Some(Ok($crate::__http_router_redirect(&$context, chain, target.redirect(&values))))
+        } else {
+            None
+        }
+    }};
+
+    // Test a particular route for match and forward to @call if there is match
+    (@one_route_with_args $call:path, $context:ident, $negotiation:ident, $redacted:ident, $method:expr, $path:expr, [$($method_token:ident)+], [$($host_token:tt)*], [$($arg:tt)*], [$($guard:tt)*], [$($middleware:ident)*], $handler:tt, $($path_segment:tt)*) => {{
+        if !($(router!(@method_matches $method, $method_token))||+) { return None };
+        #[allow(unused_mut, unused_variables)]
+        let mut host_params = router!(@host $context, [$($host_token)*]);
+        // compiled once per route
+        static PATTERN: ::std::sync::OnceLock<$crate::Pattern> = ::std::sync::OnceLock::new();
+        let pattern = PATTERN.get_or_init(|| $crate::Pattern::new(concat!($("/", stringify!($path_segment)),*)));
+        if let Some(captures) = pattern.captures($path) {
+            let template = router!(@template [] $($path_segment)*);
+            router!(@guard $context, $negotiation, template, [$($guard)*]);
+            #[allow(unused_mut, unused_variables)]
+            let mut params = captures;
+            $crate::__http_router_trace_route(template, $crate::routes!(@handler_name $handler), &router!(@param_names [] $($host_token)* $($path_segment)*), host_params.clone().chain(params.clone()), $redacted);
+            #[allow(unused_mut, unused_variables)]
+            let mut buffer = [""; 8];
+            #[allow(unused_variables)]
+            let values: &[&str] = router!(@values buffer, [$($middleware)*], host_params, params);
+            let chain: $crate::Chain<_, _> = &router!(@chain $method, values, [$($middleware)*], [$($method_token)+], [$($host_token)*], $handler, [$($guard)*], $($path_segment)*);
+            Some(router!(@call $call, $context, $handler, host_params, chain, [$($arg)*] $($host_token)* @path params $($path_segment)*))
+        } else {
+            None
+        }
+    }};
+
+    // Routes matching any method get the request method as the first param
+    (@one_route $call:path, $context:ident, $negotiation:ident, $redacted:ident, $method:expr, $path:expr, [ANY], [$($host_token:tt)*], [$($guard:tt)*], [$($middleware:ident)*], $handler:tt, $($path_segment:tt)*) => {
+        router!(@one_route_with_args $call, $context, $negotiation, $redacted, $method, $path, [ANY], [$($host_token)*], [$method,], [$($guard)*], [$($middleware)*], $handler, $($path_segment)*)
+    };
+
+    // Single method routes get only path params
+    (@one_route $call:path, $context:ident, $negotiation:ident, $redacted:ident, $method:expr, $path:expr, [$method_token:ident], [$($host_token:tt)*], [$($guard:tt)*], [$($middleware:ident)*], $handler:tt, $($path_segment:tt)*) => {
+        router!(@one_route_with_args $call, $context, $negotiation, $redacted, $method, $path, [$method_token], [$($host_token)*], [], [$($guard)*], [$($middleware)*], $handler, $($path_segment)*)
+    };
+
+    // Routes with several methods get the request method as the first param
+    (@one_route $call:path, $context:ident, $negotiation:ident, $redacted:ident, $method:expr, $path:expr, [$($method_token:ident)+], [$($host_token:tt)*], [$($guard:tt)*], [$($middleware:ident)*], $handler:tt, $($path_segment:tt)*) => {
+        router!(@one_route_with_args $call, $context, $negotiation, $redacted, $method, $path, [$($method_token)+], [$($host_token)*], [$method,], [$($guard)*], [$($middleware)*], $handler, $($path_segment)*)
+    };
+
+    // Flatten route definitions (expanding scopes) into a list of
+    // `{[METHODS] [host] [segments] handler [guards] [middleware]}` and pass it to `$callback!(@build ...)`.
+    // Middleware of the enclosing scopes comes first
+
+    // Skip separators
+    (@flatten $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] , $($rest:tt)*) => {
+        router!(@flatten $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] $($rest)*)
+    };
+
+    // Middleware for the rest of the scope (or of the router at the top level)
+    (@flatten $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] with [$($more_middleware:ident),* $(,)*] $($rest:tt)*) => {
+        router!(@flatten $callback $build [$($routes)*] [$($prefix)*] [$($middleware)* $($more_middleware)*] $($rest)*)
+    };
+
+    // Start of a scope with middleware
+    (@flatten $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] scope $(/$scope_segment:tt)+ with [$($more_middleware:ident),* $(,)*] { $($scope:tt)* } $($rest:tt)*) => {
+        router!(@flatten $callback $build [$($routes)*] [$($prefix)* $($scope_segment)*] [$($middleware)* $($more_middleware)*] $($scope)* @end_scope [$($prefix)*] [$($middleware)*] $($rest)*)
+    };
+
+    // Start of a scope - prefix and middleware are extended until the matching `@end_scope`
+    (@flatten $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] scope $(/$scope_segment:tt)+ { $($scope:tt)* } $($rest:tt)*) => {
+        router!(@flatten $callback $build [$($routes)*] [$($prefix)* $($scope_segment)*] [$($middleware)*] $($scope)* @end_scope [$($prefix)*] [$($middleware)*] $($rest)*)
+    };
+
+    // End of a scope - restore the prefix and middleware
+    (@flatten $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] @end_scope [$($outer_prefix:tt)*] [$($outer_middleware:tt)*] $($rest:tt)*) => {
+        router!(@flatten $callback $build [$($routes)*] [$($outer_prefix)*] [$($outer_middleware)*] $($rest)*)
+    };
+
+    // Home route (or root of a scope)
+    (@flatten $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] $method_token:ident $(| $more_method_token:ident)* / => $handler:ident $($rest:tt)*) => {
+        router!(@flatten_route $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] {[$method_token $($more_method_token)*] [] [$($prefix)*] $handler [] [$($middleware)*]} [] $($rest)*)
+    };
+
+    // Regular route
+    (@flatten $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] $method_token:ident $(| $more_method_token:ident)* $(/$path_segment:tt)+ => $handler:ident $($rest:tt)*) => {
+        router!(@flatten_route $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] {[$method_token $($more_method_token)*] [] [$($prefix)* $($path_segment)*] $handler [] [$($middleware)*]} [] $($rest)*)
+    };
+
+    // Home route with aliases, e.g. `GET / | /home => get_home`
+    (@flatten $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] $method_token:ident $(| $more_method_token:ident)* / $(| $(/$alias_segment:tt)+)+ => $handler:ident $($rest:tt)*) => {
+        router!(@flatten_route $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] {[$method_token $($more_method_token)*] [] [$($prefix)*] $handler [] [$($middleware)*]} [$([$($alias_segment)+])+] $($rest)*)
+    };
+
+    // Route with aliases, e.g. `GET /users | /people => get_users`
+    (@flatten $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] $method_token:ident $(| $more_method_token:ident)* $(/$path_segment:tt)+ $(| $(/$alias_segment:tt)+)+ => $handler:ident $($rest:tt)*) => {
+        router!(@flatten_route $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] {[$method_token $($more_method_token)*] [] [$($prefix)* $($path_segment)*] $handler [] [$($middleware)*]} [$([$($alias_segment)+])+] $($rest)*)
+    };
+
+    // Route with a host - host tokens are collected up to the path. A host starts with a label
+    // or a param, e.g. `{tenant: String}.example.com`
+    (@flatten $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] $method_token:ident $(| $more_method_token:ident)* $host_label:ident $($rest:tt)*) => {
+        router!(@flatten_host $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] [$method_token $($more_method_token)*] [$host_label] $($rest)*)
+    };
+
+    (@flatten $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] $method_token:ident $(| $more_method_token:ident)* {$($host_param:tt)*} $($rest:tt)*) => {
+        router!(@flatten_host $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] [$method_token $($more_method_token)*] [{$($host_param)*}] $($rest)*)
+    };
+
+    // Home route (or root of a scope) with a host
+    (@flatten_host $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] [$($method_token:ident)+] [$($host_token:tt)+] / => $handler:ident $($rest:tt)*) => {
+        router!(@flatten_route $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] {[$($method_token)+] [$($host_token)+] [$($prefix)*] $handler [] [$($middleware)*]} [] $($rest)*)
+    };
+
+    // Regular route with a host
+    (@flatten_host $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] [$($method_token:ident)+] [$($host_token:tt)+] $(/$path_segment:tt)+ => $handler:ident $($rest:tt)*) => {
+        router!(@flatten_route $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] {[$($method_token)+] [$($host_token)+] [$($prefix)* $($path_segment)*] $handler [] [$($middleware)*]} [] $($rest)*)
+    };
+
+    // Labels and params of a host are separated by `.` or `-`
+    (@flatten_host $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] [$($method_token:ident)+] [$($host_token:tt)+] . $host_label:ident $($rest:tt)*) => {
+        router!(@flatten_host $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] [$($method_token)+] [$($host_token)+ . $host_label] $($rest)*)
+    };
+
+    (@flatten_host $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] [$($method_token:ident)+] [$($host_token:tt)+] . {$($host_param:tt)*} $($rest:tt)*) => {
+        router!(@flatten_host $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] [$($method_token)+] [$($host_token)+ . {$($host_param)*}] $($rest)*)
+    };
+
+    (@flatten_host $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] [$($method_token:ident)+] [$($host_token:tt)+] - $host_label:ident $($rest:tt)*) => {
+        router!(@flatten_host $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] [$($method_token)+] [$($host_token)+ - $host_label] $($rest)*)
+    };
+
+    (@flatten_host $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] [$($method_token:ident)+] [$($host_token:tt)+] - {$($host_param:tt)*} $($rest:tt)*) => {
+        router!(@flatten_host $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] [$($method_token)+] [$($host_token)+ - {$($host_param)*}] $($rest)*)
+    };
+
+    // Anything else is a typo, e.g. a misspelled condition after the previous route
+    (@flatten_host $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] [$($method_token:ident)+] [$($host_token:tt)+] $($rest:tt)*) => {
+        compile_error!(concat!(
+            "invalid route `", stringify!($($method_token)|+ $($host_token)+), "`: ",
+            "expected a host made of labels and params separated by `.` or `-`, followed by a path and `=> handler`"
+        ))
+    };
+
+    // Redirect route, e.g. `GET /old-users/{id: u32} => redirect(308, "/users/{id}")`
+    (@flatten_route $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] {[$($method_token:ident)+] [$($host_token:tt)*] [$($path_segment:tt)*] redirect [] [$($route_middleware:ident)*]} [$($aliases:tt)*] ($status:literal, $target:literal) $($rest:tt)*) => {
+        router!(@flatten_route $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] {[$($method_token)+] [$($host_token)*] [$($path_segment)*] (redirect $status $target) [] [$($route_middleware)*]} [$($aliases)*] $($rest)*)
+    };
+
+    // Guards of a route
+    (@flatten_route $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] {[$($method_token:ident)+] [$($host_token:tt)*] [$($path_segment:tt)*] $handler:tt [$($guard:tt)*] [$($route_middleware:ident)*]} [$($aliases:tt)*] if $more_guard:ident $($rest:tt)*) => {
+        router!(@flatten_route $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] {[$($method_token)+] [$($host_token)*] [$($path_segment)*] $handler [$($guard)* $more_guard] [$($route_middleware)*]} [$($aliases)*] $($rest)*)
+    };
+
+    // Version condition of a route
+    (@flatten_route $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] {[$($method_token:ident)+] [$($host_token:tt)*] [$($path_segment:tt)*] $handler:tt [$($guard:tt)*] [$($route_middleware:ident)*]} [$($aliases:tt)*] where version = $version:literal $($rest:tt)*) => {
+        router!(@flatten_route $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] {[$($method_token)+] [$($host_token)*] [$($path_segment)*] $handler [$($guard)* (version $version)] [$($route_middleware)*]} [$($aliases)*] $($rest)*)
+    };
+
+    // Header condition of a route
+    (@flatten_route $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] {[$($method_token:ident)+] [$($host_token:tt)*] [$($path_segment:tt)*] $handler:tt [$($guard:tt)*] [$($route_middleware:ident)*]} [$($aliases:tt)*] where $name:literal = $value:literal $($rest:tt)*) => {
+        router!(@flatten_route $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] {[$($method_token)+] [$($host_token)*] [$($path_segment)*] $handler [$($guard)* (header $name $value)] [$($route_middleware)*]} [$($aliases)*] $($rest)*)
+    };
+
+    // Media type consumed by a route, matched against `Content-Type`
+    (@flatten_route $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] {[$($method_token:ident)+] [$($host_token:tt)*] [$($path_segment:tt)*] $handler:tt [$($guard:tt)*] [$($route_middleware:ident)*]} [$($aliases:tt)*] consumes $media_type:literal $($rest:tt)*) => {
+        router!(@flatten_route $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] {[$($method_token)+] [$($host_token)*] [$($path_segment)*] $handler [$($guard)* (consumes $media_type)] [$($route_middleware)*]} [$($aliases)*] $($rest)*)
+    };
+
+    // Media type produced by a route, negotiated with `Accept`
+    (@flatten_route $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] {[$($method_token:ident)+] [$($host_token:tt)*] [$($path_segment:tt)*] $handler:tt [$($guard:tt)*] [$($route_middleware:ident)*]} [$($aliases:tt)*] produces $media_type:literal $($rest:tt)*) => {
+        router!(@flatten_route $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] {[$($method_token)+] [$($host_token)*] [$($path_segment)*] $handler [$($guard)* (produces $media_type)] [$($route_middleware)*]} [$($aliases)*] $($rest)*)
+    };
+
+    // Deprecation of a route, e.g. `deprecated(since = "2026-06-01", sunset = "2027-01-01", link = "...")`
+    (@flatten_route $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] {[$($method_token:ident)+] [$($host_token:tt)*] [$($path_segment:tt)*] $handler:tt [$($guard:tt)*] [$($route_middleware:ident)*]} [$($aliases:tt)*] deprecated($($key:ident = $value:literal),* $(,)*) $($rest:tt)*) => {
+        router!(@flatten_route $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] {[$($method_token)+] [$($host_token)*] [$($path_segment)*] $handler [$($guard)* (deprecated $($key = $value)*)] [$($route_middleware)*]} [$($aliases)*] $($rest)*)
+    };
+
+    (@flatten_route $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] {[$($method_token:ident)+] [$($host_token:tt)*] [$($path_segment:tt)*] $handler:tt [$($guard:tt)*] [$($route_middleware:ident)*]} [$($aliases:tt)*] deprecated $($rest:tt)*) => {
+        router!(@flatten_route $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] {[$($method_token)+] [$($host_token)*] [$($path_segment)*] $handler [$($guard)* (deprecated)] [$($route_middleware)*]} [$($aliases)*] $($rest)*)
+    };
+
+    // Middleware of a route, after the middleware of the enclosing scopes
+    (@flatten_route $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] {[$($method_token:ident)+] [$($host_token:tt)*] [$($path_segment:tt)*] $handler:tt [$($guard:tt)*] [$($route_middleware:ident)*]} [$($aliases:tt)*] with [$($more_middleware:ident),* $(,)*] $($rest:tt)*) => {
+        router!(@flatten_route $callback $build [$($routes)*] [$($prefix)*] [$($middleware)*] {[$($method_token)+] [$($host_token)*] [$($path_segment)*] $handler [$($guard)*] [$($route_middleware)* $($more_middleware)*]} [$($aliases)*] $($rest)*)
+    };
+
+    // End of a route with aliases - each alias is a copy of the route with its own path
+    (@flatten_route $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] {[$($method_token:ident)+] [$($host_token:tt)*] [$($path_segment:tt)*] $handler:tt [$($guard:tt)*] [$($route_middleware:ident)*]} [[$($alias_segment:tt)*] $($more_aliases:tt)*] $($rest:tt)*) => {
+        router!(@flatten_route $callback $build [$($routes)* {[$($method_token)+] [$($host_token)*] [$($path_segment)*] $handler [$($guard)*] [$($route_middleware)*]}] [$($prefix)*] [$($middleware)*] {[$($method_token)+] [$($host_token)*] [$($prefix)* $($alias_segment)*] $handler [$($guard)*] [$($route_middleware)*]} [$($more_aliases)*] $($rest)*)
+    };
+
+    // End of a route
+    (@flatten_route $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] {$($route:tt)*} [] $($rest:tt)*) => {
+        router!(@flatten $callback $build [$($routes)* {$($route)*}] [$($prefix)*] [$($middleware)*] $($rest)*)
+    };
+
+    // Rejection handler and fallback route - end of input
+    (@flatten $callback:ident $build:ident [$($routes:tt)*] [] [$($middleware:tt)*] ! => $rejection:ident, _ => $default:ident $(,)*) => {
+        $callback!(@$build [$($routes)*] [$rejection] $default)
+    };
+
+    // Fallback route - end of input
+    (@flatten $callback:ident $build:ident [$($routes:tt)*] [] [$($middleware:tt)*] _ => $default:ident $(,)*) => {
+        $callback!(@$build [$($routes)*] [] $default)
+    };
+
+    (@flatten $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*]) => {
+        compile_error!("expected the fallback route `_ => handler` at the end of the router")
+    };
+
+    // Anything else is a typo
+    (@flatten $callback:ident $build:ident [$($routes:tt)*] [$($prefix:tt)*] [$($middleware:tt)*] $($rest:tt)+) => {
+        compile_error!(concat!("invalid route syntax at `", stringify!($($rest)+), "`"))
+    };
+
+    // Rejected requests go to the rejection handler if there is one, otherwise to the fallback.
+    // `$context` is a reference for sync routers and a value for async ones
+    (@reject $context:expr, $rejection:ident, [$rejection_handler:ident], $default:ident) => {
+        $rejection_handler($context, $rejection)
+    };
+
+    (@reject $context:expr, $rejection:ident, [], $default:ident) => {{
+        let _ = $rejection;
+        $default($context)
+    }};
+
+    // Route template of the path segments, e.g. `/users/{user_id}`, as labelled by metrics
+    (@template []) => {
+        "/"
+    };
+
+    (@template [$($part:expr),+]) => {
+        concat!($($part),+)
+    };
+
+    (@template [$($part:expr),*] {* $id:ident} $($path_segment:tt)*) => {
+        router!(@template [$($part,)* "/{*", stringify!($id), "}"] $($path_segment)*)
+    };
+
+    (@template [$($part:expr),*] {* $id:ident : $ty:ty} $($path_segment:tt)*) => {
+        router!(@template [$($part,)* "/{*", stringify!($id), "}"] $($path_segment)*)
+    };
+
+    (@template [$($part:expr),*] {$id:ident : $ty:ty} $($path_segment:tt)*) => {
+        router!(@template [$($part,)* "/{", stringify!($id), "}"] $($path_segment)*)
+    };
+
+    (@template [$($part:expr),*] $static_segment:tt $($path_segment:tt)*) => {
+        router!(@template [$($part,)* "/", stringify!($static_segment)] $($path_segment)*)
+    };
+
+    // Names of the params of the host and of the path, in the order they are passed to the handler
+    (@param_names [$($name:expr),*]) => {
+        [$($name),*]
+    };
+
+    (@param_names [$($name:expr),*] {* $id:ident} $($token:tt)*) => {
+        router!(@param_names [$($name,)* stringify!($id)] $($token)*)
+    };
+
+    (@param_names [$($name:expr),*] {* $id:ident : $ty:ty} $($token:tt)*) => {
+        router!(@param_names [$($name,)* stringify!($id)] $($token)*)
+    };
+
+    (@param_names [$($name:expr),*] {$id:ident : $ty:ty} $($token:tt)*) => {
+        router!(@param_names [$($name,)* stringify!($id)] $($token)*)
+    };
+
+    (@param_names [$($name:expr),*] $static_token:tt $($token:tt)*) => {
+        router!(@param_names [$($name),*] $($token)*)
+    };
+
+    // Whether the path matches a route, for telling 405 from a fallback
+    (@path_matches $path:expr, $($path_segment:tt)*) => {{
+        static PATTERN: ::std::sync::OnceLock<$crate::Pattern> = ::std::sync::OnceLock::new();
+        let pattern = PATTERN.get_or_init(|| $crate::Pattern::new(concat!($("/", stringify!($path_segment)),*)));
+        pattern.captures($path).is_some()
+    }};
+
+    // Requests not handled by a route are answered by the fallback, unless the path
+    // matches a route with another method (only told apart if `$check_method`)
+    (@fallback_outcome $check_method:ident, $method:expr, $path:expr, [$({[$($method_token:ident)+] [$($path_segment:tt)*]})*]) => {{
+        let mut matches_path = false;
+        let mut matches_method = false;
+        if $check_method {
+            $(
+                if router!(@path_matches $path, $($path_segment)*) {
+                    matches_path = true;
+                    matches_method |= $(router!(@method_matches $method, $method_token))||+;
+                }
+            )*
+        }
+        match matches_path && !matches_method {
+            true => $crate::Outcome::MethodNotAllowed,
+            false => $crate::Outcome::Fallback,
+        }
+    }};
+
+    // Dispatch of a router - default only. Dispatch returns the output with the template of
+    // the route that decided it and the outcome, see `Metrics`
+    (@dispatch [] [$($rejection_handler:ident)*] $default:ident) => {
+        |context, _method: $crate::Method, _path: &str, _check_method: bool, _redacted: &[&'static str]| {
+            $crate::__http_router_trace_fallback();
+            ($default(&context), None::<&'static str>, $crate::Outcome::Fallback)
+        }
+    };
+
+    // Dispatch of a router
+    (@dispatch [$({[$($method_token:ident)+] [$($host_token:tt)*] [$($path_segment:tt)*] $handler:tt [$($guard:tt)*] [$($middleware:ident)*]})*] [$($rejection_handler:ident)*] $default:ident) => {{
+        move |context, method: $crate::Method, path: &str, check_method: bool, redacted: &[&'static str]| {
+            let negotiation = $crate::Negotiation::new();
+            let mut result = None;
+            let mut route: Option<&'static str> = None;
+            // routes skipped for a less preferred media type are tried again if nothing matches
+            loop {
+                $(
+                    if !$crate::__http_router_is_final(&result) && negotiation.next_route() {
+                        // we use closure here so that we could make early return from macros inside of it
+                        let closure = || {
+                            router!(@one_route $crate::__http_router_call, context, negotiation, redacted, method, path, [$($method_token)+], [$($host_token)*], [$($guard)*], [$($middleware)*], $handler, $($path_segment)*)
+                        };
+                        #[allow(clippy::redundant_closure_call)]
+                        let route_result = closure();
+                        // keep the first invalid param unless another route matches
+                        if result.is_none() || $crate::__http_router_is_final(&route_result) {
+                            if route_result.is_some() {
+                                let template = router!(@template [] $($path_segment)*);
+                                $crate::__http_router_trace_result(template, &route_result);
+                                route = Some(template);
+                            }
+                            result = route_result;
+                        }
+                    }
+                )*
+                if $crate::__http_router_is_final(&result) || !negotiation.retry() {
+                    break;
+                }
+            }
+            let result = result.or_else(|| negotiation.take_rejection().map(Err));
+            let outcome = match $crate::__http_router_outcome(&result) {
+                Some(outcome) => outcome,
+                None => router!(@fallback_outcome check_method, method, path, [$({[$($method_token)+] [$($path_segment)*]})*]),
+            };
+            let output = match result {
+                Some(Ok(response)) => response,
+                Some(Err(rejection)) => router!(@reject &context, rejection, [$($rejection_handler)*], $default),
+                None => {
+                    $crate::__http_router_trace_fallback();
+                    $default(&context)
+                }
+            };
+            (output, route, outcome)
+        }
+    }};
+
+    // Dispatch of an async router - default only
+    (@dispatch_async [] [$($rejection_handler:ident)*] $default:ident) => {
+        |context, _method: $crate::Method, _path: &str, _check_method: bool, _redacted: &[&'static str]| {
+            $crate::__http_router_trace_fallback();
+            ($crate::__http_router_box_future($default(context)), None::<&'static str>, $crate::Outcome::Fallback)
+        }
+    };
+
+    // Dispatch of an async router - handlers take the context by value and return futures,
+    // which are boxed into one `BoxFuture` type
+    (@dispatch_async [$({[$($method_token:ident)+] [$($host_token:tt)*] [$($path_segment:tt)*] $handler:tt [$($guard:tt)*] [$($middleware:ident)*]})*] [$($rejection_handler:ident)*] $default:ident) => {{
+        move |context, method: $crate::Method, path: &str, check_method: bool, redacted: &[&'static str]| {
+            let negotiation = $crate::Negotiation::new();
+            let mut result = None;
+            let mut route: Option<&'static str> = None;
+            loop {
+                $(
+                    if !$crate::__http_router_is_final(&result) && negotiation.next_route() {
+                        let closure = || {
+                            router!(@one_route $crate::__http_router_call_async, context, negotiation, redacted, method, path, [$($method_token)+], [$($host_token)*], [$($guard)*], [$($middleware)*], $handler, $($path_segment)*)
+                        };
+                        #[allow(clippy::redundant_closure_call)]
+                        let route_result = closure();
+                        if result.is_none() || $crate::__http_router_is_final(&route_result) {
+                            if route_result.is_some() {
+                                let template = router!(@template [] $($path_segment)*);
+                                $crate::__http_router_trace_result(template, &route_result);
+                                route = Some(template);
+                            }
+                            result = route_result;
+                        }
+                    }
+                )*
+                if $crate::__http_router_is_final(&result) || !negotiation.retry() {
+                    break;
+                }
+            }
+            let result = result.or_else(|| negotiation.take_rejection().map(Err));
+            let outcome = match $crate::__http_router_outcome(&result) {
+                Some(outcome) => outcome,
+                None => router!(@fallback_outcome check_method, method, path, [$({[$($method_token)+] [$($path_segment)*]})*]),
+            };
+            let output = match result {
+                Some(Ok(future)) => future,
+                Some(Err(rejection)) => $crate::__http_router_box_future(
+                    router!(@reject context, rejection, [$($rejection_handler)*], $default)
+                ),
+                None => {
+                    $crate::__http_router_trace_fallback();
+                    $crate::__http_router_box_future($default(context))
+                }
+            };
+            (output, route, outcome)
+        }
+    }};
+
+    // A router returns the output of its dispatch, which runs in the dispatch span.
+    // `$finish` carries the span into the futures of async routers
+    (@build $finish:ident, [$($redacted:ident),*], $dispatch:expr) => {{
+        let dispatch = $dispatch;
+        move |context, method: $crate::Method, path: &str| {
+            let span = $crate::__HttpRouterSpan::new(method);
+            let redacted = &[$(stringify!($redacted)),*];
+            let (output, _, _) = span.in_scope(|| dispatch(context, method, path, false, redacted));
+            span.$finish(output)
+        }
+    }};
+
+    // A router with metrics records each request once its output is ready
+    (@build $finish:ident, [$($redacted:ident),*], $dispatch:expr, $record:path, $metrics:expr) => {{
+        let dispatch = $dispatch;
+        let metrics = ::std::sync::Arc::new($metrics);
+        move |context, method: $crate::Method, path: &str| {
+            let span = $crate::__HttpRouterSpan::new(method);
+            let started = ::std::time::Instant::now();
+            let redacted = &[$(stringify!($redacted)),*];
+            let (output, route, outcome) = span.in_scope(|| dispatch(context, method, path, true, redacted));
+            span.$finish($record(&metrics, method, route, outcome, started, output))
+        }
+    }};
+
+    // Async entry pattern with metrics and redacted params
+    (async metrics($metrics:expr), redact($($redacted:ident),+ $(,)*), $($route:tt)*) => {
+        router!(@build finish_async, [$($redacted),+], router!(@flatten router dispatch_async [] [] [] $($route)*), $crate::__http_router_record_async, $metrics)
+    };
+
+    // Entry pattern with metrics and redacted params
+    (metrics($metrics:expr), redact($($redacted:ident),+ $(,)*), $($route:tt)*) => {
+        router!(@build finish, [$($redacted),+], router!(@flatten router dispatch [] [] [] $($route)*), $crate::__http_router_record, $metrics)
+    };
+
+    // Async entry pattern with metrics
+    (async metrics($metrics:expr), $($route:tt)*) => {
+        router!(@build finish_async, [], router!(@flatten router dispatch_async [] [] [] $($route)*), $crate::__http_router_record_async, $metrics)
+    };
+
+    // Entry pattern with metrics
+    (metrics($metrics:expr), $($route:tt)*) => {
+        router!(@build finish, [], router!(@flatten router dispatch [] [] [] $($route)*), $crate::__http_router_record, $metrics)
+    };
+
+    // Async entry pattern with redacted params
+    (async redact($($redacted:ident),+ $(,)*), $($route:tt)*) => {
+        router!(@build finish_async, [$($redacted),+], router!(@flatten router dispatch_async [] [] [] $($route)*))
+    };
+
+    // Entry pattern with redacted params
+    (redact($($redacted:ident),+ $(,)*), $($route:tt)*) => {
+        router!(@build finish, [$($redacted),+], router!(@flatten router dispatch [] [] [] $($route)*))
+    };
+
+    // Async entry pattern
+    (async $($route:tt)*) => {
+        router!(@build finish_async, [], router!(@flatten router dispatch_async [] [] [] $($route)*))
+    };
+
+    // Entry pattern
+    ($($route:tt)*) => {
+        router!(@build finish, [], router!(@flatten router dispatch [] [] [] $($route)*))
+    };
+}
+
+/// This macro returns a `Vec<RouteInfo>` describing the routes. It takes exactly
+/// the same input as `router!`, so the same route definitions can be used for
+/// both routing and introspection (e.g. OpenAPI generation).
+///
+/// The fallback route (`_`) is not included in the result.
+#[macro_export]
+macro_rules! routes {
+    // Typed param segment
+    (@segment $path:ident, $params:ident, {$id:ident : $ty:ty}) => {{
+        $path.push_str(concat!("/{", stringify!($id), "}"));
+        $params.push($crate::ParamInfo { name: stringify!($id).to_string(), ty: stringify!($ty) });
+    }};
+
+    // Wildcard param, String by default
+    (@segment $path:ident, $params:ident, {* $id:ident}) => {{
+        $path.push_str(concat!("/{*", stringify!($id), "}"));
+        $params.push($crate::ParamInfo { name: stringify!($id).to_string(), ty: "String" });
+    }};
+
+    // Typed wildcard param
+    (@segment $path:ident, $params:ident, {* $id:ident : $ty:ty}) => {{
+        $path.push_str(concat!("/{*", stringify!($id), "}"));
+        $params.push($crate::ParamInfo { name: stringify!($id).to_string(), ty: stringify!($ty) });
+    }};
+
+    // Static segment
+    (@segment $path:ident, $params:ident, $path_segment:tt) => {{
+        $path.push('/');
+        $path.push_str(stringify!($path_segment));
+    }};
+
+    // Typed host param
+    (@host_segment $params:ident, {$id:ident : $ty:ty}) => {
+        $params.push($crate::ParamInfo { name: stringify!($id).to_string(), ty: stringify!($ty) })
+    };
+
+    // Static part of the host
+    (@host_segment $params:ident, $host_token:tt) => {
+        ()
+    };
+
+    (@host []) => {
+        None
+    };
+
+    (@host [$($host_token:tt)+]) => {
+        Some($crate::HostPattern::new(concat!($(stringify!($host_token)),+)).template().to_string())
+    };
+
+    (@handler_name (redirect $status:literal $target:literal)) => {
+        "redirect"
+    };
+
+    (@handler_name $handler:ident) => {
+        stringify!($handler)
+    };
+
+    (@redirect (redirect $status:literal $target:literal)) => {
+        Some($crate::Redirect::new($status, $target))
+    };
+
+    (@redirect $handler:ident) => {
+        None
+    };
+
+    // Deprecation is declared among the guards of a route
+    (@deprecation []) => {
+        None
+    };
+
+    (@deprecation [(deprecated $($key:ident = $value:literal)*) $($guard:tt)*]) => {
+        Some($crate::Deprecation::new()$(.$key($value))*)
+    };
+
+    (@deprecation [$other:tt $($guard:tt)*]) => {
+        $crate::routes!(@deprecation [$($guard)*])
+    };
+
+    (@consumes []) => {
+        None
+    };
+
+    (@consumes [(consumes $media_type:literal) $($guard:tt)*]) => {
+        Some($media_type)
+    };
+
+    (@consumes [$other:tt $($guard:tt)*]) => {
+        $crate::routes!(@consumes [$($guard)*])
+    };
+
+    (@produces []) => {
+        None
+    };
+
+    (@produces [(produces $media_type:literal) $($guard:tt)*]) => {
+        Some($media_type)
+    };
+
+    (@produces [$other:tt $($guard:tt)*]) => {
+        $crate::routes!(@produces [$($guard)*])
+    };
+
+    // Describe one route
+    (@one_route $method_token:ident, [$($host_token:tt)*], $handler:tt, [$($guard:tt)*], $($path_segment:tt)*) => {{
+        let mut path = String::new();
+        #[allow(unused_mut)]
+        let mut params = Vec::new();
+        $(
+            $crate::routes!(@host_segment params, $host_token);
+        )*
+        $(
+            $crate::routes!(@segment path, params, $path_segment);
+        )*
+        // handle home case
+        if path.is_empty() { path.push('/') }
+        $crate::RouteInfo {
+            method: $crate::router!(@method $method_token),
+            host: $crate::routes!(@host [$($host_token)*]),
+            path,
+            params,
+            handler: $crate::routes!(@handler_name $handler),
+            redirect: $crate::routes!(@redirect $handler),
+            deprecation: $crate::routes!(@deprecation [$($guard)*]),
+            consumes: $crate::routes!(@consumes [$($guard)*]),
+            produces: $crate::routes!(@produces [$($guard)*]),
+        }
+    }};
+
+    // ANY is described as all of the standard methods
+    (@methods $routes:ident, [ANY $($method_token:ident)*], [$($host_token:tt)*], $handler:tt, [$($guard:tt)*], $($path_segment:tt)*) => {
+        $crate::routes!(@methods $routes, [GET POST PUT PATCH DELETE OPTIONS HEAD CONNECT TRACE], [$($host_token)*], $handler, [$($guard)*], $($path_segment)*)
+    };
+
+    // One route description per method
+    (@methods $routes:ident, [$method_token:ident $($more_method_token:ident)*], [$($host_token:tt)*], $handler:tt, [$($guard:tt)*], $($path_segment:tt)*) => {{
+        $routes.push($crate::routes!(@one_route $method_token, [$($host_token)*], $handler, [$($guard)*], $($path_segment)*));
+        $crate::routes!(@methods $routes, [$($more_method_token)*], [$($host_token)*], $handler, [$($guard)*], $($path_segment)*);
+    }};
+
+    (@methods $routes:ident, [], [$($host_token:tt)*], $handler:tt, [$($guard:tt)*], $($path_segment:tt)*) => {
+        ()
+    };
+
+    // Build the list of routes
+    (@build [$({[$($method_token:ident)+] [$($host_token:tt)*] [$($path_segment:tt)*] $handler:tt [$($guard:tt)*] [$($middleware:ident)*]})*] [$($rejection_handler:ident)*] $default:ident) => {{
+        #[allow(unused_mut)]
+        let mut routes: Vec<$crate::RouteInfo> = Vec::new();
+        $(
+            $crate::routes!(@methods routes, [$($method_token)+], [$($host_token)*], $handler, [$($guard)*], $($path_segment)*);
+        )*
+        routes
+    }};
+
+    // Metrics and redacted params don't change the routes
+    (async metrics($metrics:expr), $($route:tt)*) => {
+        routes!($($route)*)
+    };
+
+    (metrics($metrics:expr), $($route:tt)*) => {
+        routes!($($route)*)
+    };
+
+    (redact($($redacted:ident),+ $(,)*), $($route:tt)*) => {
+        routes!($($route)*)
+    };
+
+    // Async routers are described in the same way
+    (async $($route:tt)*) => {
+        routes!($($route)*)
+    };
+
+    // Entry pattern
+    ($($route:tt)*) => {
+        router!(@flatten routes build [] [] [] $($route)*)
+    };
+}
+
+/// This macro returns an `Explainer` of the routes, which tells why they match
+/// a request or not, like `Router::explain` does for builder routers. It takes exactly
+/// the same input as `router!`, e.g. to debug a request that hits the fallback:
+///
+/// ```rust
+/// let explainer = explainer!(
+///     GET /users/{user_id: u32} => get_user,
+///     _ => not_found
+/// );
+/// let explanation = explainer.explain(Method::GET, "/users/x");
+/// assert!(explanation.matched().is_none());
+/// ```
+///
+/// The fallback route (`_`) is not included.
+#[macro_export]
+macro_rules! explainer {
+    (@methods [ANY]) => {
+        None
+    };
+
+    (@methods [$($method_token:ident)+]) => {
+        Some(vec![$($crate::router!(@method $method_token)),+])
+    };
+
+    // Params of the host and of the path are converted with the types of the handler params
+    (@check $checks:ident, {$id:ident : $ty:ty}) => {
+        $checks.push(|value| <$ty as $crate::FromParam<_>>::from_param(value).map(|_| ()))
+    };
+
+    // Wildcard param, String by default
+    (@check $checks:ident, {* $id:ident}) => {
+        $checks.push(|_| Ok(()))
+    };
+
+    (@check $checks:ident, {* $id:ident : $ty:ty}) => {
+        $checks.push(|value| <$ty as $crate::FromParam<_>>::from_param(value).map(|_| ()))
+    };
+
+    // Static segment or part of the host
+    (@check $checks:ident, $segment:tt) => {
+        ()
+    };
+
+    // Hosts, guards, header conditions and media types depend on the rest of the request
+    (@conditional [$($host_token:tt)+] [$($guard:tt)*]) => {
+        true
+    };
+
+    (@conditional [] [$($guard:tt)*]) => {
+        false $(|| $crate::explainer!(@conditional_guard $guard))*
+    };
+
+    // Deprecation only describes the route
+    (@conditional_guard (deprecated $($deprecation:tt)*)) => {
+        false
+    };
+
+    (@conditional_guard $guard:tt) => {
+        true
+    };
+
+    // Build the explainer of the routes
+    (@build [$({[$($method_token:ident)+] [$($host_token:tt)*] [$($path_segment:tt)*] $handler:tt [$($guard:tt)*] [$($middleware:ident)*]})*] [$($rejection_handler:ident)*] $default:ident) => {{
+        #[allow(unused_mut)]
+        let mut explainer = $crate::Explainer::default();
+        $({
+            let mut path = String::new();
+            // `routes!` describes the params too, only the path is needed
+            #[allow(unused_mut, unused_variables)]
+            let mut params: Vec<$crate::ParamInfo> = Vec::new();
+            $(
+                $crate::routes!(@segment path, params, $path_segment);
+            )*
+            // handle home case
+            if path.is_empty() { path.push('/') }
+            #[allow(unused_mut)]
+            let mut checks: Vec<$crate::ParamCheck> = Vec::new();
+            $(
+                $crate::explainer!(@check checks, $host_token);
+            )*
+            $(
+                $crate::explainer!(@check checks, $path_segment);
+            )*
+            $crate::__http_router_explain_route(
+                &mut explainer,
+                $crate::explainer!(@methods [$($method_token)+]),
+                $crate::routes!(@host [$($host_token)*]),
+                &path,
+                $crate::routes!(@handler_name $handler),
+                checks,
+                $crate::explainer!(@conditional [$($host_token)*] [$($guard)*]),
+            );
+        })*
+        explainer
+    }};
+
+    // Metrics and redacted params don't change the routes
+    (async metrics($metrics:expr), $($route:tt)*) => {
+        explainer!($($route)*)
+    };
+
+    (metrics($metrics:expr), $($route:tt)*) => {
+        explainer!($($route)*)
+    };
+
+    (redact($($redacted:ident),+ $(,)*), $($route:tt)*) => {
+        explainer!($($route)*)
+    };
+
+    // Async routers are explained in the same way
+    (async $($route:tt)*) => {
+        explainer!($($route)*)
+    };
+
+    // Entry pattern
+    ($($route:tt)*) => {
+        router!(@flatten explainer build [] [] [] $($route)*)
+    };
+}
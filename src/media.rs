@@ -0,0 +1,253 @@
+use extract::Rejection;
+use std::cell::{Cell, RefCell};
+
+/// Content negotiation state of one request, shared by the routes tried for it.
+///
+/// Routes declare the media type they produce (`produces "text/csv"`), which has to be
+/// acceptable by the `Accept` header, and the media type they consume (`consumes "application/json"`),
+/// which has to match `Content-Type`. Routes are first tried with the quality the client prefers most.
+/// Routes whose media type is acceptable with a lower quality are skipped, and if no route matches,
+/// the skipped routes are tried again with the best of the lower qualities. Other routes are not
+/// tried again, so their guards and extractors run once. So with
+/// `Accept: text/csv;q=0.5, application/json` a JSON route wins over a CSV route listed before it.
+///
+/// If no route matches at all, the first route that didn't fit is reported
+/// as `Rejection::UnsupportedMediaType` (415) or `Rejection::NotAcceptable` (406).
+#[doc(hidden)]
+#[derive(Default)]
+pub struct Negotiation {
+    // lowest quality of a produced media type, the preferred quality of `Accept` until a retry
+    threshold: Cell<Option<f32>>,
+    // best quality of the routes skipped for having a lower one
+    deferred: Cell<Option<f32>>,
+    // index of the next route in the order routes are tried
+    next_route: Cell<usize>,
+    // indices of the routes skipped for their quality
+    deferred_routes: RefCell<Vec<usize>>,
+    // routes tried again on a retry, `None` until the first retry
+    retried_routes: RefCell<Option<Vec<usize>>>,
+    // rejection of the first route that didn't fit
+    rejection: Cell<Option<Rejection>>,
+}
+
+impl Negotiation {
+    /// Creates the state for a new request
+    pub fn new() -> Self {
+        Negotiation::default()
+    }
+
+    /// Moves on to the next route, routes are counted in the order they are tried.
+    /// Returns `false` if the route should be skipped, because this is a retry
+    /// and the route wasn't skipped for its quality before
+    pub fn next_route(&self) -> bool {
+        let route = self.next_route.get();
+        self.next_route.set(route + 1);
+        self.retried_routes
+            .borrow()
+            .as_ref()
+            .is_none_or(|routes| routes.contains(&route))
+    }
+
+    /// Checks the `Content-Type` of the request against the media type a route consumes,
+    /// which may be a range, e.g. `text/*`
+    pub fn consumes(&self, content_type: Option<&str>, media_type: &str) -> bool {
+        let content_type = content_type.unwrap_or("");
+        if specificity(media_type, content_type).is_some() {
+            return true;
+        }
+        self.reject(Rejection::UnsupportedMediaType(content_type.to_string()));
+        false
+    }
+
+    /// Checks the `Accept` header of the request against the media type a route produces.
+    /// A missing header accepts any media type.
+    pub fn produces(&self, accept: Option<&str>, media_type: &str) -> bool {
+        let accept = match accept.map(str::trim) {
+            Some(accept) if !accept.is_empty() => accept,
+            _ => "*/*",
+        };
+        let quality = quality(accept, media_type);
+        if quality <= 0.0 {
+            self.reject(Rejection::NotAcceptable(accept.to_string()));
+            return false;
+        }
+        let threshold = match self.threshold.get() {
+            Some(threshold) => threshold,
+            None => {
+                let threshold = preferred_quality(accept);
+                self.threshold.set(Some(threshold));
+                threshold
+            }
+        };
+        if quality < threshold {
+            let deferred = self.deferred.get().map_or(quality, |q| q.max(quality));
+            self.deferred.set(Some(deferred));
+            let route = self.next_route.get().saturating_sub(1);
+            self.deferred_routes.borrow_mut().push(route);
+            return false;
+        }
+        true
+    }
+
+    /// Lowers the quality to the best one of the skipped routes, which are tried again.
+    /// Returns `false` if no route was skipped for its quality, so there is nothing to retry.
+    pub fn retry(&self) -> bool {
+        match self.deferred.take() {
+            Some(quality) => {
+                self.threshold.set(Some(quality));
+                let routes = self.deferred_routes.take();
+                *self.retried_routes.borrow_mut() = Some(routes);
+                self.next_route.set(0);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Rejection of the first route that didn't fit
+    pub fn take_rejection(&self) -> Option<Rejection> {
+        self.rejection.take()
+    }
+
+    fn reject(&self, rejection: Rejection) {
+        let first = self.rejection.take().unwrap_or(rejection);
+        self.rejection.set(Some(first));
+    }
+}
+
+// Quality of `media_type` given by the most specific matching range of `accept`, 0 if none matches
+fn quality(accept: &str, media_type: &str) -> f32 {
+    let mut best: Option<(u8, f32)> = None;
+    for range in accept.split(',') {
+        let (range, quality) = match parse_range(range) {
+            Some(range) => range,
+            None => continue,
+        };
+        if let Some(specificity) = specificity(range, media_type) {
+            if best.is_none_or(|(best, _)| specificity > best) {
+                best = Some((specificity, quality));
+            }
+        }
+    }
+    best.map_or(0.0, |(_, quality)| quality)
+}
+
+// Highest quality of the ranges of `accept`
+fn preferred_quality(accept: &str) -> f32 {
+    accept
+        .split(',')
+        .filter_map(parse_range)
+        .map(|(_, quality)| quality)
+        .fold(0.0, f32::max)
+}
+
+// `text/html;level=1;q=0.5` -> (`text/html`, 0.5), ranges with an invalid quality are ignored
+fn parse_range(range: &str) -> Option<(&str, f32)> {
+    let mut parts = range.split(';');
+    let media_range = parts.next()?.trim();
+    if media_range.is_empty() {
+        return None;
+    }
+    let mut quality = 1.0;
+    for param in parts {
+        let mut param = param.splitn(2, '=');
+        if param.next()?.trim().eq_ignore_ascii_case("q") {
+            quality = param.next()?.trim().parse().ok()?;
+            if !(0.0..=1.0).contains(&quality) {
+                return None;
+            }
+        }
+    }
+    Some((media_range, quality))
+}
+
+// How specific `range` is if it matches `media_type`: 3 for `type/subtype`, 2 for `type/*`
+// and 1 for `*/*`. Params of the media type are ignored, names are case-insensitive
+fn specificity(range: &str, media_type: &str) -> Option<u8> {
+    let (range_main, range_sub) = essence(range)?;
+    let (main, sub) = essence(media_type)?;
+    match (range_main, range_sub) {
+        ("*", "*") => Some(1),
+        (range_main, "*") if range_main.eq_ignore_ascii_case(main) => Some(2),
+        (range_main, range_sub)
+            if range_main.eq_ignore_ascii_case(main) && range_sub.eq_ignore_ascii_case(sub) =>
+        {
+            Some(3)
+        }
+        _ => None,
+    }
+}
+
+// `text/html; charset=utf-8` -> (`text`, `html`)
+fn essence(media_type: &str) -> Option<(&str, &str)> {
+    let media_type = media_type.split(';').next()?.trim();
+    let mut parts = media_type.splitn(2, '/');
+    let main = parts.next()?.trim();
+    let sub = parts.next()?.trim();
+    Some((main, sub))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quality() {
+        let accept = "text/*;q=0.5, text/csv, application/json;q=0.8, */*;q=0";
+        assert_eq!(quality(accept, "text/csv"), 1.0);
+        assert_eq!(quality(accept, "text/html; charset=utf-8"), 0.5);
+        assert_eq!(quality(accept, "Application/JSON"), 0.8);
+        assert_eq!(quality(accept, "application/msgpack"), 0.0);
+        assert_eq!(
+            quality("application/json;q=2, text/csv;q=x", "text/csv"),
+            0.0
+        );
+        assert_eq!(
+            preferred_quality("text/csv;q=0.3, application/json;q=0.6"),
+            0.6
+        );
+    }
+
+    #[test]
+    fn test_negotiation() {
+        let negotiation = Negotiation::new();
+        let header = "text/csv;q=0.5, application/msgpack;q=0.7, application/xml";
+        let accept = Some(header);
+        assert!(!negotiation.produces(accept, "text/csv"));
+        assert!(!negotiation.produces(accept, "application/msgpack"));
+        assert!(!negotiation.produces(accept, "application/json"));
+        assert!(negotiation.retry());
+        assert!(!negotiation.produces(accept, "text/csv"));
+        assert!(negotiation.produces(accept, "application/msgpack"));
+        assert!(negotiation.retry());
+        assert!(negotiation.produces(accept, "text/csv"));
+        assert!(!negotiation.retry());
+        assert_eq!(
+            negotiation.take_rejection(),
+            Some(Rejection::NotAcceptable(header.to_string()))
+        );
+
+        let negotiation = Negotiation::new();
+        let accept = Some("text/csv;q=0.5, application/json");
+        assert!(negotiation.next_route());
+        assert!(negotiation.next_route());
+        assert!(!negotiation.produces(accept, "text/csv"));
+        assert!(negotiation.next_route());
+        assert!(negotiation.retry());
+        assert!(!negotiation.next_route());
+        assert!(negotiation.next_route());
+        assert!(negotiation.produces(accept, "text/csv"));
+        assert!(!negotiation.next_route());
+
+        let negotiation = Negotiation::new();
+        assert!(negotiation.produces(None, "text/csv"));
+        assert!(negotiation.consumes(Some("application/json; charset=utf-8"), "application/json"));
+        assert!(negotiation.consumes(Some("text/plain"), "text/*"));
+        assert!(!negotiation.consumes(None, "application/json"));
+        assert!(!negotiation.consumes(Some("text/csv"), "application/json"));
+        assert_eq!(
+            negotiation.take_rejection(),
+            Some(Rejection::UnsupportedMediaType(String::new()))
+        );
+    }
+}
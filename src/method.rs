@@ -1,8 +1,14 @@
-#[cfg(feature = "with_hyper")]
-use hyper::Method as HyperMethod;
+#[cfg(feature = "with_http")]
+use http::Method as HttpMethod;
+use std::cmp::Ordering;
+#[cfg(feature = "with_http")]
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
 
 /// Http verbs
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Method {
     GET,
     POST,
@@ -13,22 +19,276 @@ pub enum Method {
     HEAD,
     CONNECT,
     TRACE,
+    /// Any other method, e.g. WebDAV `PROPFIND` or `QUERY`
+    Extension(ExtensionMethod),
 }
 
-#[cfg(feature = "with_hyper")]
-impl From<HyperMethod> for Method {
-    fn from(hm: HyperMethod) -> Method {
-        match hm {
-            HyperMethod::OPTIONS => Method::OPTIONS,
-            HyperMethod::GET => Method::GET,
-            HyperMethod::POST => Method::POST,
-            HyperMethod::PUT => Method::PUT,
-            HyperMethod::DELETE => Method::DELETE,
-            HyperMethod::HEAD => Method::HEAD,
-            HyperMethod::TRACE => Method::TRACE,
-            HyperMethod::CONNECT => Method::CONNECT,
-            HyperMethod::PATCH => Method::PATCH,
-            _ => panic!("Not implemented hyper method in http_router lib"),
+impl Method {
+    /// Creates a method from its name. Standard methods become their own
+    /// variants, others become `Method::Extension`. Names are case-sensitive.
+    ///
+    /// ### Panics
+    /// If `name` is not a valid method name. Use `str::parse` for a fallible version.
+    pub fn extension(name: &str) -> Method {
+        name.parse()
+            .unwrap_or_else(|e: InvalidMethod| panic!("{}", e))
+    }
+
+    /// Name of the method, e.g. `GET`
+    pub fn as_str(&self) -> &str {
+        match *self {
+            Method::GET => "GET",
+            Method::POST => "POST",
+            Method::PUT => "PUT",
+            Method::PATCH => "PATCH",
+            Method::DELETE => "DELETE",
+            Method::OPTIONS => "OPTIONS",
+            Method::HEAD => "HEAD",
+            Method::CONNECT => "CONNECT",
+            Method::TRACE => "TRACE",
+            Method::Extension(ref method) => method.as_str(),
         }
     }
 }
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Method names are case-sensitive (RFC 7231), so `get` is an extension method, not `GET`
+impl FromStr for Method {
+    type Err = InvalidMethod;
+
+    fn from_str(name: &str) -> Result<Method, InvalidMethod> {
+        match name {
+            "GET" => Ok(Method::GET),
+            "POST" => Ok(Method::POST),
+            "PUT" => Ok(Method::PUT),
+            "PATCH" => Ok(Method::PATCH),
+            "DELETE" => Ok(Method::DELETE),
+            "OPTIONS" => Ok(Method::OPTIONS),
+            "HEAD" => Ok(Method::HEAD),
+            "CONNECT" => Ok(Method::CONNECT),
+            "TRACE" => Ok(Method::TRACE),
+            _ => ExtensionMethod::new(name)
+                .map(Method::Extension)
+                .ok_or_else(|| InvalidMethod {
+                    name: name.to_string(),
+                }),
+        }
+    }
+}
+
+/// Error of converting a string or a foreign method type into `Method`.
+///
+/// Usually means that the request should be answered with `501 Not Implemented`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidMethod {
+    name: String,
+}
+
+impl InvalidMethod {
+    /// Name of the method that failed to convert
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl fmt::Display for InvalidMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid http method `{}`", self.name)
+    }
+}
+
+impl Error for InvalidMethod {}
+
+/// Name of a non-standard method, stored inline so that `Method` stays `Copy`
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExtensionMethod {
+    len: u8,
+    bytes: [u8; ExtensionMethod::MAX_LEN],
+}
+
+impl ExtensionMethod {
+    /// Max length of a method name
+    pub const MAX_LEN: usize = 31;
+
+    /// Returns `None` if `name` is empty, longer than `MAX_LEN` or is not
+    /// a valid token (RFC 7230), i.e. contains spaces, slashes, etc.
+    pub fn new(name: &str) -> Option<Self> {
+        if name.is_empty() || name.len() > ExtensionMethod::MAX_LEN || !name.bytes().all(is_tchar) {
+            return None;
+        }
+        let mut bytes = [0; ExtensionMethod::MAX_LEN];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Some(ExtensionMethod {
+            len: name.len() as u8,
+            bytes,
+        })
+    }
+
+    /// Name of the method
+    pub fn as_str(&self) -> &str {
+        // only ascii bytes are accepted in `new`
+        ::std::str::from_utf8(&self.bytes[..self.len as usize]).expect("Invalid method name")
+    }
+}
+
+impl PartialOrd for ExtensionMethod {
+    fn partial_cmp(&self, other: &ExtensionMethod) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ExtensionMethod {
+    fn cmp(&self, other: &ExtensionMethod) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl fmt::Debug for ExtensionMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+fn is_tchar(b: u8) -> bool {
+    match b {
+        b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'*' | b'+' | b'-' | b'.' | b'^' | b'_'
+        | b'`' | b'|' | b'~' => true,
+        _ => b.is_ascii_alphanumeric(),
+    }
+}
+
+/// Methods matched by `ANY` routes in route descriptions
+pub(crate) const STANDARD_METHODS: [Method; 9] = [
+    Method::GET,
+    Method::POST,
+    Method::PUT,
+    Method::PATCH,
+    Method::DELETE,
+    Method::OPTIONS,
+    Method::HEAD,
+    Method::CONNECT,
+    Method::TRACE,
+];
+
+// `hyper::Method` is a re-export of `http::Method`, so the conversions
+// below work for hyper as well
+
+/// Panics if the method can't be converted. Prefer `Method::try_from(&method)`
+/// for methods coming from the network.
+#[cfg(feature = "with_http")]
+impl From<HttpMethod> for Method {
+    fn from(method: HttpMethod) -> Method {
+        Method::try_from(&method).expect("Not implemented http method in http_router lib")
+    }
+}
+
+#[cfg(feature = "with_http")]
+impl<'a> TryFrom<&'a HttpMethod> for Method {
+    type Error = InvalidMethod;
+
+    fn try_from(method: &'a HttpMethod) -> Result<Method, InvalidMethod> {
+        match *method {
+            HttpMethod::OPTIONS => Ok(Method::OPTIONS),
+            HttpMethod::GET => Ok(Method::GET),
+            HttpMethod::POST => Ok(Method::POST),
+            HttpMethod::PUT => Ok(Method::PUT),
+            HttpMethod::DELETE => Ok(Method::DELETE),
+            HttpMethod::HEAD => Ok(Method::HEAD),
+            HttpMethod::TRACE => Ok(Method::TRACE),
+            HttpMethod::CONNECT => Ok(Method::CONNECT),
+            HttpMethod::PATCH => Ok(Method::PATCH),
+            _ => method.as_str().parse(),
+        }
+    }
+}
+
+#[cfg(feature = "with_http")]
+impl From<Method> for HttpMethod {
+    fn from(method: Method) -> HttpMethod {
+        match method {
+            Method::OPTIONS => HttpMethod::OPTIONS,
+            Method::GET => HttpMethod::GET,
+            Method::POST => HttpMethod::POST,
+            Method::PUT => HttpMethod::PUT,
+            Method::DELETE => HttpMethod::DELETE,
+            Method::HEAD => HttpMethod::HEAD,
+            Method::TRACE => HttpMethod::TRACE,
+            Method::CONNECT => HttpMethod::CONNECT,
+            Method::PATCH => HttpMethod::PATCH,
+            // extension methods are always valid tokens
+            Method::Extension(ref method) => HttpMethod::from_bytes(method.as_str().as_bytes())
+                .expect("Invalid extension method"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension() {
+        assert_eq!(Method::extension("GET"), Method::GET);
+        match Method::extension("PROPFIND") {
+            Method::Extension(method) => assert_eq!(method.as_str(), "PROPFIND"),
+            method => panic!("Unexpected method {:?}", method),
+        }
+        assert_eq!(
+            format!("{:?}", Method::extension("MKCOL")),
+            "Extension(MKCOL)"
+        );
+        assert_ne!(Method::extension("get"), Method::GET);
+        assert!(ExtensionMethod::new("").is_none());
+        assert!(ExtensionMethod::new("PROP FIND").is_none());
+        assert!(ExtensionMethod::new(&"X".repeat(32)).is_none());
+        assert!(ExtensionMethod::new(&"X".repeat(31)).is_some());
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("GET".parse(), Ok(Method::GET));
+        assert_eq!("QUERY".parse(), Ok(Method::extension("QUERY")));
+        assert_eq!("get".parse::<Method>().unwrap().as_str(), "get");
+        let err = "BAD METHOD".parse::<Method>().unwrap_err();
+        assert_eq!(err.name(), "BAD METHOD");
+        assert_eq!(err.to_string(), "Invalid http method `BAD METHOD`");
+    }
+
+    #[test]
+    fn test_display_and_order() {
+        assert_eq!(Method::PATCH.to_string(), "PATCH");
+        assert_eq!(Method::extension("MKCOL").to_string(), "MKCOL");
+        assert!(Method::GET < Method::POST);
+        assert!(Method::TRACE < Method::extension("COPY"));
+        assert!(Method::extension("COPY") < Method::extension("MOVE"));
+        let methods: ::std::collections::HashSet<Method> = vec![
+            Method::GET,
+            Method::extension("LOCK"),
+            Method::extension("LOCK"),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(methods.len(), 2);
+    }
+
+    #[cfg(feature = "with_http")]
+    #[test]
+    fn test_http() {
+        let method = HttpMethod::from_bytes(b"LOCK").unwrap();
+        assert_eq!(Method::try_from(&method), Ok(Method::extension("LOCK")));
+        assert_eq!(Method::from(method), Method::extension("LOCK"));
+        assert_eq!(Method::from(HttpMethod::GET), Method::GET);
+        let long = HttpMethod::from_bytes(&[b'X'; 32]).unwrap();
+        assert!(Method::try_from(&long).is_err());
+        assert_eq!(HttpMethod::from(Method::PUT), HttpMethod::PUT);
+        assert_eq!(
+            HttpMethod::from(Method::extension("PROPFIND")).as_str(),
+            "PROPFIND"
+        );
+    }
+}
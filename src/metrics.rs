@@ -0,0 +1,298 @@
+use handler::BoxFuture;
+use method::Method;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// How the router handled a request, see `Metrics`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Outcome {
+    /// The handler of the matched route was called
+    Handled,
+    /// A param of a route failed to parse and no other route matched
+    BadParam,
+    /// A guard or an extractor rejected the request, or no route fits its media types
+    Rejected,
+    /// The path matches a route, but not with the request method, i.e. 405 Method Not Allowed.
+    /// The fallback answers such requests
+    MethodNotAllowed,
+    /// No route matches
+    Fallback,
+}
+
+impl Outcome {
+    /// Label value of the outcome, e.g. `bad_param`
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Outcome::Handled => "handled",
+            Outcome::BadParam => "bad_param",
+            Outcome::Rejected => "rejected",
+            Outcome::MethodNotAllowed => "method_not_allowed",
+            Outcome::Fallback => "fallback",
+        }
+    }
+}
+
+/// Hook recording the requests of a `Router`, see `RouterBuilder::metrics`,
+/// or of a `router!` with `metrics(..)`.
+///
+/// Requests are labelled by the template of the matched route, e.g. `/users/{user_id}`,
+/// rather than by the path, so the number of label values is bounded by the number of routes.
+pub trait Metrics: Send + Sync {
+    /// Records a request. `route` is the template of the route that decided the outcome,
+    /// `None` for the fallback, 405 and media type rejections
+    fn record(&self, method: Method, route: Option<&str>, outcome: Outcome, elapsed: Duration);
+}
+
+impl<M: Metrics + ?Sized> Metrics for Arc<M> {
+    fn record(&self, method: Method, route: Option<&str>, outcome: Outcome, elapsed: Duration) {
+        (**self).record(method, route, outcome, elapsed)
+    }
+}
+
+/// Upper bounds of the latency buckets of `MetricsRegistry::new`, in seconds
+pub const DEFAULT_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// In-memory `Metrics` rendered in the Prometheus text exposition format:
+/// the `http_requests_total` counter and the `http_request_duration_seconds` histogram,
+/// both labelled by `method`, `route` (empty if no route decided) and `outcome`.
+///
+/// ```rust
+/// let metrics = Arc::new(MetricsRegistry::new());
+/// let router = Router::builder()
+///     .route(Method::GET, "/users/{user_id}", get_user)
+///     .metrics(metrics.clone())
+///     .fallback(not_found)
+///     .build();
+/// // e.g. in the handler of `GET /metrics`
+/// let text = metrics.render();
+/// ```
+#[derive(Debug)]
+pub struct MetricsRegistry {
+    buckets: Vec<f64>,
+    series: Mutex<BTreeMap<Labels, Series>>,
+}
+
+// method, route and outcome
+type Labels = (String, String, Outcome);
+
+#[derive(Debug)]
+struct Series {
+    count: u64,
+    sum: f64,
+    // cumulative counts, one per bucket
+    buckets: Vec<u64>,
+}
+
+impl MetricsRegistry {
+    /// Creates a registry with `DEFAULT_BUCKETS`
+    pub fn new() -> Self {
+        MetricsRegistry::with_buckets(&DEFAULT_BUCKETS)
+    }
+
+    /// Creates a registry with the upper bounds of latency buckets in seconds
+    pub fn with_buckets(buckets: &[f64]) -> Self {
+        let mut buckets = buckets.to_vec();
+        buckets.sort_by(|a, b| a.total_cmp(b));
+        buckets.dedup();
+        MetricsRegistry {
+            buckets,
+            series: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Number of recorded requests with the labels
+    pub fn count(&self, method: Method, route: Option<&str>, outcome: Outcome) -> u64 {
+        let labels = (method.to_string(), route.unwrap_or("").to_string(), outcome);
+        let series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        series.get(&labels).map_or(0, |series| series.count)
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        let mut text = String::new();
+        text.push_str(
+            "# HELP http_requests_total Requests by method, route template and outcome.\n",
+        );
+        text.push_str("# TYPE http_requests_total counter\n");
+        for (labels, series) in series.iter() {
+            let _ = writeln!(
+                text,
+                "http_requests_total{{{}}} {}",
+                format_labels(labels),
+                series.count
+            );
+        }
+        text.push_str(
+            "# HELP http_request_duration_seconds Latency by method, route template and outcome.\n",
+        );
+        text.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for (labels, series) in series.iter() {
+            let labels = format_labels(labels);
+            for (le, count) in self.buckets.iter().zip(series.buckets.iter()) {
+                let _ = writeln!(
+                    text,
+                    "http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, le, count
+                );
+            }
+            let _ = writeln!(
+                text,
+                "http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, series.count
+            );
+            let _ = writeln!(
+                text,
+                "http_request_duration_seconds_sum{{{}}} {}",
+                labels, series.sum
+            );
+            let _ = writeln!(
+                text,
+                "http_request_duration_seconds_count{{{}}} {}",
+                labels, series.count
+            );
+        }
+        text
+    }
+}
+
+impl Default for MetricsRegistry {
+    fn default() -> Self {
+        MetricsRegistry::new()
+    }
+}
+
+impl Metrics for MetricsRegistry {
+    fn record(&self, method: Method, route: Option<&str>, outcome: Outcome, elapsed: Duration) {
+        let labels = (method.to_string(), route.unwrap_or("").to_string(), outcome);
+        let seconds = elapsed.as_secs_f64();
+        let mut series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        let series = series.entry(labels).or_insert_with(|| Series {
+            count: 0,
+            sum: 0.0,
+            buckets: vec![0; self.buckets.len()],
+        });
+        series.count += 1;
+        series.sum += seconds;
+        for (le, count) in self.buckets.iter().zip(series.buckets.iter_mut()) {
+            if seconds <= *le {
+                *count += 1;
+            }
+        }
+    }
+}
+
+fn format_labels(labels: &Labels) -> String {
+    format!(
+        "method=\"{}\",route=\"{}\",outcome=\"{}\"",
+        escape(&labels.0),
+        escape(&labels.1),
+        labels.2.as_str()
+    )
+}
+
+// Label values escape backslashes, quotes and line feeds
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// Calls `done` once the future of an async router completes, so that its latency is recorded
+pub(crate) fn on_ready<T>(future: BoxFuture<T>, done: Box<dyn FnOnce() + Send>) -> BoxFuture<T>
+where
+    T: 'static,
+{
+    Box::pin(OnReady {
+        future,
+        done: Some(done),
+    })
+}
+
+struct OnReady<T> {
+    future: BoxFuture<T>,
+    done: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl<T> Future for OnReady<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+        let this = self.get_mut();
+        let output = this.future.as_mut().poll(cx);
+        if output.is_ready() {
+            if let Some(done) = this.done.take() {
+                done();
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let registry = MetricsRegistry::with_buckets(&[0.1, 0.01]);
+        let route = Some("/users/{user_id}");
+        registry.record(
+            Method::GET,
+            route,
+            Outcome::Handled,
+            Duration::from_millis(5),
+        );
+        registry.record(
+            Method::GET,
+            route,
+            Outcome::Handled,
+            Duration::from_millis(50),
+        );
+        registry.record(
+            Method::POST,
+            None,
+            Outcome::Fallback,
+            Duration::from_secs(1),
+        );
+        assert_eq!(registry.count(Method::GET, route, Outcome::Handled), 2);
+        assert_eq!(registry.count(Method::GET, route, Outcome::BadParam), 0);
+
+        let text = registry.render();
+        let labels = "method=\"GET\",route=\"/users/{user_id}\",outcome=\"handled\"";
+        for line in &[
+            format!("http_requests_total{{{}}} 2", labels),
+            "http_requests_total{method=\"POST\",route=\"\",outcome=\"fallback\"} 1".to_string(),
+            format!(
+                "http_request_duration_seconds_bucket{{{},le=\"0.01\"}} 1",
+                labels
+            ),
+            format!(
+                "http_request_duration_seconds_bucket{{{},le=\"0.1\"}} 2",
+                labels
+            ),
+            format!(
+                "http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} 2",
+                labels
+            ),
+            format!("http_request_duration_seconds_sum{{{}}} 0.055", labels),
+            format!("http_request_duration_seconds_count{{{}}} 2", labels),
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "{} is missing in\n{}",
+                line,
+                text
+            );
+        }
+        assert_eq!(escape("a\"b\\c\n"), "a\\\"b\\\\c\\n");
+    }
+}
@@ -0,0 +1,87 @@
+use route::RouteInfo;
+
+/// Code that runs around the handlers of routes, e.g. auth, logging or timing.
+///
+/// Implemented for functions and closures taking the context, the description
+/// of the matched route and the rest of the chain, which holds the raw param values:
+///
+/// ```rust
+/// fn logging(context: &Context, route: &RouteInfo, next: Next<Context, Response>) -> Response {
+///     let started = Instant::now();
+///     let params: Vec<String> = route.params.iter().zip(next.params())
+///         .map(|(param, value)| format!("{}={}", param.name, value))
+///         .collect();
+///     let response = next.run(context);
+///     println!("{:?} {} {:?} took {:?}", route.method, route.path, params, started.elapsed());
+///     response
+/// }
+/// ```
+///
+/// Middleware runs only for the matched route, once its params are parsed and its
+/// extractors succeed. It can return early without calling `next`, e.g. with `401 Unauthorized`.
+/// In async routers `R` is a `BoxFuture`, so middleware can wrap the future of the handler.
+pub trait Middleware<C, R>: Send + Sync {
+    /// Runs the middleware, `next.run(context)` calls the rest of the chain
+    fn call(&self, context: &C, route: &RouteInfo, next: Next<C, R>) -> R;
+}
+
+impl<C, R, F> Middleware<C, R> for F
+where
+    F: Fn(&C, &RouteInfo, Next<C, R>) -> R + Send + Sync,
+{
+    fn call(&self, context: &C, route: &RouteInfo, next: Next<C, R>) -> R {
+        self(context, route, next)
+    }
+}
+
+/// The rest of the middleware chain, ending with the handler of the matched route
+pub struct Next<'a, C: 'a, R: 'a> {
+    run: &'a mut dyn FnMut(&C) -> R,
+    params: &'a [&'a str],
+}
+
+impl<'a, C, R> Next<'a, C, R> {
+    /// This is an implementation detail and *should not* be called directly!
+    #[doc(hidden)]
+    pub fn new(run: &'a mut dyn FnMut(&C) -> R) -> Self {
+        Next { run, params: &[] }
+    }
+
+    /// This is an implementation detail and *should not* be called directly!
+    #[doc(hidden)]
+    pub fn with_params<'b>(self, params: &'b [&'b str]) -> Next<'b, C, R>
+    where
+        'a: 'b,
+    {
+        Next {
+            run: self.run,
+            params,
+        }
+    }
+
+    /// Raw values of the params of the matched route, before they are parsed,
+    /// in the order of `RouteInfo::params`: params of the host come first
+    pub fn params(&self) -> &'a [&'a str] {
+        self.params
+    }
+
+    /// Runs the rest of the chain with `context`, which is usually the context
+    /// the middleware was called with
+    pub fn run(self, context: &C) -> R {
+        (self.run)(context)
+    }
+}
+
+/// Middleware chain of the matched route, bound to its `RouteInfo`.
+/// The route handler passes the call of the handler itself as `Next`.
+pub type Chain<'a, C, R> = &'a dyn Fn(&C, Next<C, R>) -> R;
+
+// Returns a response that needs no handler, e.g. a redirect, as the last step
+// of the middleware chain of its route
+pub(crate) fn through_chain<C, R, T: Into<R>>(context: &C, chain: Chain<C, R>, response: T) -> R {
+    let mut response = Some(response);
+    chain(
+        context,
+        Next::new(&mut |_| response.take().expect("Next is run once").into()),
+    )
+}
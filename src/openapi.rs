@@ -0,0 +1,506 @@
+//! OpenAPI 3 document generation from route definitions (requires the `openapi` feature).
+//!
+//! ```rust
+//! let routes = routes!(
+//!     GET /users => get_users,
+//!     PUT /users/{user_id: usize} => put_users,
+//!     _ => not_found,
+//! );
+//! let doc = openapi::Document::new("Users API", "1.0.0").routes(&routes);
+//! println!("{}", doc.to_yaml());
+//! ```
+//!
+//! Only the `paths` skeleton is generated: path params with schemas derived from
+//! their Rust types and `operationId`s derived from handler names. Routes with the same
+//! path and method (e.g. with other hosts, versions or media types) are described as one
+//! operation, whose request body and response list the `consumes` and `produces` media
+//! types of all of them. Schemas of bodies are left to be filled in by hand.
+
+use method::Method;
+use route::{ParamInfo, RouteInfo};
+use serde_json::{self, Map, Value};
+use std::collections::HashMap;
+
+/// Version of the OpenAPI specification the generated documents conform to
+pub const OPENAPI_VERSION: &str = "3.0.3";
+
+/// OpenAPI document builder
+#[derive(Debug, Clone)]
+pub struct Document {
+    title: String,
+    version: String,
+    routes: Vec<RouteInfo>,
+}
+
+impl Document {
+    /// Creates an empty document with `info.title` and `info.version` set
+    pub fn new(title: &str, version: &str) -> Self {
+        Document {
+            title: title.to_string(),
+            version: version.to_string(),
+            routes: Vec::new(),
+        }
+    }
+
+    /// Adds routes (usually produced by the `routes!` macro) to the document
+    pub fn routes(mut self, routes: &[RouteInfo]) -> Self {
+        self.routes.extend_from_slice(routes);
+        self
+    }
+
+    /// Returns the document as a json value
+    pub fn to_value(&self) -> Value {
+        let mut info = Map::new();
+        info.insert("title".to_string(), Value::from(self.title.clone()));
+        info.insert("version".to_string(), Value::from(self.version.clone()));
+
+        let mut doc = Map::new();
+        doc.insert("openapi".to_string(), Value::from(OPENAPI_VERSION));
+        doc.insert("info".to_string(), Value::Object(info));
+        doc.insert("paths".to_string(), Value::Object(self.paths()));
+        Value::Object(doc)
+    }
+
+    /// Serializes the document to pretty printed json
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.to_value()).expect("Failed to serialize json value")
+    }
+
+    /// Serializes the document to yaml
+    pub fn to_yaml(&self) -> String {
+        let mut out = String::new();
+        write_yaml(&self.to_value(), 0, &mut out);
+        out
+    }
+
+    fn paths(&self) -> Map<String, Value> {
+        // routes with the same path and method that differ by host, version, headers or
+        // media types are variants of one operation
+        let mut operations: Vec<(String, &'static str, Vec<&RouteInfo>)> = Vec::new();
+        for route in self.routes.iter() {
+            let method = match operation_method(route.method) {
+                Some(method) => method,
+                None => continue,
+            };
+            // OpenAPI has no wildcard params, `{*rest}` is described as `{rest}`
+            let path = route.path.replace("{*", "{");
+            let same = |operation: &&mut (String, &str, Vec<&RouteInfo>)| {
+                operation.0 == path && operation.1 == method
+            };
+            match operations.iter_mut().find(same) {
+                Some(operation) => operation.2.push(route),
+                None => operations.push((path, method, vec![route])),
+            }
+        }
+
+        let mut paths = Map::new();
+        let mut operation_ids: HashMap<&str, usize> = HashMap::new();
+        for (path, method, variants) in operations {
+            let handler = variants[0].handler;
+            let count = operation_ids.entry(handler).or_insert(0);
+            *count += 1;
+            let operation_id = if *count == 1 {
+                handler.to_string()
+            } else {
+                format!("{}_{}", handler, count)
+            };
+            let path = paths
+                .entry(path)
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Value::Object(ref mut path) = *path {
+                path.insert(method.to_string(), operation(operation_id, &variants));
+            }
+        }
+        paths
+    }
+}
+
+// Operation of the route variants, the first one describes the params and the redirect.
+// Media types of all variants are listed as the content of the request body and the response
+fn operation(operation_id: String, variants: &[&RouteInfo]) -> Value {
+    let route = variants[0];
+    let mut operation = Map::new();
+    operation.insert("operationId".to_string(), Value::from(operation_id));
+    // host params come first, they are not path params and are left out
+    let host_params = route
+        .host
+        .as_ref()
+        .map_or(0, |host| host.matches('{').count());
+    let params: Vec<Value> = route
+        .params
+        .iter()
+        .skip(host_params)
+        .map(parameter)
+        .collect();
+    if !params.is_empty() {
+        operation.insert("parameters".to_string(), Value::Array(params));
+    }
+    let consumes = media_types(variants.iter().filter_map(|route| route.consumes));
+    if !consumes.is_empty() {
+        let body = json_object(vec![("content", Value::Object(consumes))]);
+        operation.insert("requestBody".to_string(), body);
+    }
+    let produces = media_types(variants.iter().filter_map(|route| route.produces));
+    let response = match route.redirect {
+        Some(ref redirect) => {
+            let description = format!("Redirect to `{}`", redirect.location);
+            let response = json_object(vec![("description", Value::from(description))]);
+            (redirect.status.to_string(), response)
+        }
+        None if !produces.is_empty() => {
+            let response = json_object(vec![
+                ("description", Value::from("Default response")),
+                ("content", Value::Object(produces)),
+            ]);
+            ("default".to_string(), response)
+        }
+        None => {
+            let response = json_object(vec![("description", Value::from("Default response"))]);
+            ("default".to_string(), response)
+        }
+    };
+    let mut responses = Map::new();
+    responses.insert(response.0, response.1);
+    operation.insert("responses".to_string(), Value::Object(responses));
+    // the operation is deprecated once all of its variants are
+    let deprecations: Option<Vec<_>> = variants
+        .iter()
+        .map(|route| route.deprecation.as_ref())
+        .collect();
+    if let Some(deprecation) = deprecations.and_then(|deprecations| deprecations.first().cloned()) {
+        operation.insert("deprecated".to_string(), Value::from(true));
+        // OpenAPI has no fields for the dates, so they are extensions
+        if let Some(ref since) = deprecation.since {
+            operation.insert("x-deprecated-since".to_string(), Value::from(since.clone()));
+        }
+        if let Some(ref sunset) = deprecation.sunset {
+            operation.insert("x-sunset".to_string(), Value::from(sunset.clone()));
+        }
+        if let Some(ref link) = deprecation.link {
+            let docs = json_object(vec![("url", Value::from(link.clone()))]);
+            operation.insert("externalDocs".to_string(), docs);
+        }
+    }
+    Value::Object(operation)
+}
+
+// `content` of a request body or a response, without schemas
+fn media_types<I: Iterator<Item = &'static str>>(media_types: I) -> Map<String, Value> {
+    media_types
+        .map(|media_type| (media_type.to_string(), Value::Object(Map::new())))
+        .collect()
+}
+
+// OpenAPI has no operations for CONNECT and extension methods, so such routes are skipped
+fn operation_method(method: Method) -> Option<&'static str> {
+    match method {
+        Method::GET => Some("get"),
+        Method::POST => Some("post"),
+        Method::PUT => Some("put"),
+        Method::PATCH => Some("patch"),
+        Method::DELETE => Some("delete"),
+        Method::OPTIONS => Some("options"),
+        Method::HEAD => Some("head"),
+        Method::TRACE => Some("trace"),
+        Method::CONNECT | Method::Extension(_) => None,
+    }
+}
+
+fn parameter(param: &ParamInfo) -> Value {
+    json_object(vec![
+        ("name", Value::from(param.name.clone())),
+        ("in", Value::from("path")),
+        ("required", Value::from(true)),
+        ("schema", schema(param.ty)),
+    ])
+}
+
+/// Maps a Rust type (as written in a route) to a json schema.
+/// Unknown types are treated as strings.
+pub fn schema(ty: &str) -> Value {
+    // `std::string::String` is stringified as `std :: string :: String`
+    let ty = ty.rsplit("::").next().unwrap_or(ty).trim();
+    match ty {
+        "i8" | "i16" | "i32" => json_object(vec![
+            ("type", Value::from("integer")),
+            ("format", Value::from("int32")),
+        ]),
+        "i64" | "i128" | "isize" => json_object(vec![
+            ("type", Value::from("integer")),
+            ("format", Value::from("int64")),
+        ]),
+        "u8" | "u16" => json_object(vec![
+            ("type", Value::from("integer")),
+            ("format", Value::from("int32")),
+            ("minimum", Value::from(0)),
+        ]),
+        "u32" | "u64" | "u128" | "usize" => json_object(vec![
+            ("type", Value::from("integer")),
+            ("format", Value::from("int64")),
+            ("minimum", Value::from(0)),
+        ]),
+        "f32" => json_object(vec![
+            ("type", Value::from("number")),
+            ("format", Value::from("float")),
+        ]),
+        "f64" => json_object(vec![
+            ("type", Value::from("number")),
+            ("format", Value::from("double")),
+        ]),
+        "bool" => json_object(vec![("type", Value::from("boolean"))]),
+        "char" => json_object(vec![
+            ("type", Value::from("string")),
+            ("minLength", Value::from(1)),
+            ("maxLength", Value::from(1)),
+        ]),
+        _ => json_object(vec![("type", Value::from("string"))]),
+    }
+}
+
+fn json_object(fields: Vec<(&str, Value)>) -> Value {
+    Value::Object(
+        fields
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect(),
+    )
+}
+
+fn write_yaml(value: &Value, indent: usize, out: &mut String) {
+    let pad = " ".repeat(indent);
+    match *value {
+        Value::Object(ref map) => {
+            for (key, value) in map {
+                out.push_str(&pad);
+                out.push_str(&yaml_key(key));
+                out.push(':');
+                write_yaml_child(value, indent, out);
+            }
+        }
+        Value::Array(ref values) => {
+            for value in values {
+                out.push_str(&pad);
+                out.push('-');
+                match *value {
+                    // nested collections start on the same line as the dash
+                    Value::Object(_) | Value::Array(_) if !is_empty(value) => {
+                        let mut nested = String::new();
+                        write_yaml(value, indent + 2, &mut nested);
+                        out.push(' ');
+                        out.push_str(nested.trim_start());
+                    }
+                    _ => {
+                        out.push(' ');
+                        out.push_str(&yaml_scalar(value));
+                        out.push('\n');
+                    }
+                }
+            }
+        }
+        _ => {
+            out.push_str(&pad);
+            out.push_str(&yaml_scalar(value));
+            out.push('\n');
+        }
+    }
+}
+
+fn write_yaml_child(value: &Value, indent: usize, out: &mut String) {
+    match *value {
+        Value::Object(_) | Value::Array(_) if !is_empty(value) => {
+            out.push('\n');
+            write_yaml(value, indent + 2, out);
+        }
+        _ => {
+            out.push(' ');
+            out.push_str(&yaml_scalar(value));
+            out.push('\n');
+        }
+    }
+}
+
+fn is_empty(value: &Value) -> bool {
+    match *value {
+        Value::Object(ref map) => map.is_empty(),
+        Value::Array(ref values) => values.is_empty(),
+        _ => false,
+    }
+}
+
+// Strings are always double-quoted; json string escaping is valid yaml
+fn yaml_scalar(value: &Value) -> String {
+    match *value {
+        Value::Object(_) => "{}".to_string(),
+        Value::Array(_) => "[]".to_string(),
+        _ => value.to_string(),
+    }
+}
+
+fn yaml_key(key: &str) -> String {
+    let is_plain = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' || c == '/');
+    if is_plain {
+        key.to_string()
+    } else {
+        Value::from(key).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn routes() -> Vec<RouteInfo> {
+        routes!(
+            GET / => get_home,
+            GET /users => get_users,
+            PUT /users/{user_id: usize} => put_users,
+            DELETE /users/{user_id: usize}/transactions/{hash: String} => delete_transactions,
+            GET /search/{q: String}/{exact: bool} => get_users,
+            CONNECT /tunnel => connect,
+            GET /people | /persons => redirect(301, "/users"),
+            GET /v1/users => get_users deprecated(sunset = "2027-01-01", link = "https://example.com/v2"),
+            _ => not_found,
+        )
+    }
+
+    #[test]
+    fn test_paths() {
+        let doc = Document::new("Test", "0.1.0").routes(&routes()).to_value();
+        assert_eq!(doc["openapi"], OPENAPI_VERSION);
+        assert_eq!(doc["info"]["title"], "Test");
+        let paths = doc["paths"].as_object().unwrap();
+        let keys: Vec<&str> = paths.keys().map(|k| k.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "/",
+                "/users",
+                "/users/{user_id}",
+                "/users/{user_id}/transactions/{hash}",
+                "/search/{q}/{exact}",
+                "/people",
+                "/persons",
+                "/v1/users",
+            ]
+        );
+
+        let put = &paths["/users/{user_id}"]["put"];
+        assert_eq!(put["operationId"], "put_users");
+        assert_eq!(put["parameters"][0]["name"], "user_id");
+        assert_eq!(put["parameters"][0]["in"], "path");
+        assert_eq!(put["parameters"][0]["required"], true);
+        assert_eq!(put["parameters"][0]["schema"]["type"], "integer");
+        assert_eq!(put["parameters"][0]["schema"]["minimum"], 0);
+
+        let delete = &paths["/users/{user_id}/transactions/{hash}"]["delete"];
+        assert_eq!(delete["parameters"][1]["schema"]["type"], "string");
+        assert_eq!(
+            paths["/search/{q}/{exact}"]["get"]["operationId"],
+            "get_users_2"
+        );
+        assert_eq!(
+            paths["/search/{q}/{exact}"]["get"]["parameters"][1]["schema"]["type"],
+            "boolean"
+        );
+        let redirect = &paths["/persons"]["get"];
+        assert_eq!(redirect["operationId"], "redirect_2");
+        assert_eq!(
+            redirect["responses"]["301"]["description"],
+            "Redirect to `/users`"
+        );
+        let deprecated = &paths["/v1/users"]["get"];
+        assert_eq!(deprecated["deprecated"], true);
+        assert_eq!(deprecated["x-sunset"], "2027-01-01");
+        assert_eq!(deprecated["externalDocs"]["url"], "https://example.com/v2");
+        assert_eq!(put.get("deprecated"), None);
+    }
+
+    #[test]
+    fn test_variants() {
+        let routes = routes!(
+            GET /users => get_users_csv produces "text/csv",
+            GET /users => get_users produces "application/json",
+            POST /users => post_users_csv consumes "text/csv",
+            POST /users => post_users consumes "application/json",
+            GET /v1/users => get_users_v2 where version = 2 deprecated,
+            GET /v1/users => get_users,
+            GET /v2/users => get_users_v2 where version = 2 deprecated,
+            GET /v2/users => get_users deprecated,
+            _ => not_found,
+        );
+        assert!(routes[4].deprecation.is_some());
+        assert_eq!(routes[4].method, Method::GET);
+        let doc = Document::new("Test", "1").routes(&routes).to_value();
+        let users = &doc["paths"]["/users"];
+        assert_eq!(users["get"]["operationId"], "get_users_csv");
+        let content = users["get"]["responses"]["default"]["content"]
+            .as_object()
+            .unwrap();
+        let keys: Vec<&str> = content.keys().map(|k| k.as_str()).collect();
+        assert_eq!(keys, vec!["text/csv", "application/json"]);
+        let content = users["post"]["requestBody"]["content"].as_object().unwrap();
+        let keys: Vec<&str> = content.keys().map(|k| k.as_str()).collect();
+        assert_eq!(keys, vec!["text/csv", "application/json"]);
+        let paths: Vec<&str> = doc["paths"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        assert_eq!(paths, vec!["/users", "/v1/users", "/v2/users"]);
+        let methods: Vec<&str> = doc["paths"]["/v1/users"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        assert_eq!(methods, vec!["get"]);
+        // an operation is deprecated only if all of its variants are
+        let v1 = &doc["paths"]["/v1/users"]["get"];
+        assert_eq!(v1["operationId"], "get_users_v2");
+        assert_eq!(v1.get("deprecated"), None);
+        assert_eq!(doc["paths"]["/v2/users"]["get"]["deprecated"], true);
+    }
+
+    #[test]
+    fn test_schema() {
+        assert_eq!(schema("i32")["format"], "int32");
+        assert_eq!(schema("u64")["format"], "int64");
+        assert_eq!(schema("f64")["type"], "number");
+        assert_eq!(schema("std :: string :: String")["type"], "string");
+        assert_eq!(schema("Uuid")["type"], "string");
+    }
+
+    #[test]
+    fn test_yaml() {
+        let routes = routes!(
+            PUT /users/{user_id: u32} => put_users,
+            _ => not_found,
+        );
+        let yaml = Document::new("Test", "1").routes(&routes).to_yaml();
+        let expected = r#"openapi: "3.0.3"
+info:
+  title: "Test"
+  version: "1"
+paths:
+  "/users/{user_id}":
+    put:
+      operationId: "put_users"
+      parameters:
+        - name: "user_id"
+          in: "path"
+          required: true
+          schema:
+            type: "integer"
+            format: "int64"
+            minimum: 0
+      responses:
+        default:
+          description: "Default response"
+"#;
+        assert_eq!(yaml, expected);
+    }
+}
@@ -0,0 +1,170 @@
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Conversion of a raw path param into a typed handler argument.
+///
+/// Implemented for every `FromStr` type whose error is `Display` (so `usize`, `String`,
+/// etc. work out of the box) and for `&str` and `Cow<str>`, which borrow the param
+/// from the path.
+/// Custom types (ids, hashes, slugs) that are not `FromStr` can implement it directly:
+///
+/// ```rust
+/// impl<'a> FromParam<'a> for Hash {
+///     fn from_param(param: &'a str) -> Result<Self, ParamError> {
+///         if !param.starts_with("0x") {
+///             return Err(ParamError::new("hash should start with `0x`"));
+///         }
+///         ...
+///     }
+/// }
+/// ```
+///
+/// `Kind` only serves to tell the `FromStr` impl apart from the others and should be left out.
+pub trait FromParam<'a, Kind = ()>: Sized {
+    /// Returns an error if the param can't be converted. The route then counts as
+    /// non-matching, and if no other route matches, the error is passed to the
+    /// rejection handler as `Rejection::InvalidParam`.
+    fn from_param(param: &'a str) -> Result<Self, ParamError>;
+}
+
+/// `Kind` of the `FromParam` impl for `FromStr` types
+#[doc(hidden)]
+pub enum FromStrParam {}
+
+impl<'a, T> FromParam<'a, FromStrParam> for T
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    fn from_param(param: &'a str) -> Result<Self, ParamError> {
+        param.parse().map_err(ParamError::new)
+    }
+}
+
+impl<'a> FromParam<'a> for &'a str {
+    fn from_param(param: &'a str) -> Result<Self, ParamError> {
+        Ok(param)
+    }
+}
+
+impl<'a> FromParam<'a> for Cow<'a, str> {
+    fn from_param(param: &'a str) -> Result<Self, ParamError> {
+        Ok(Cow::Borrowed(param))
+    }
+}
+
+/// A handler param type with the lifetime of the path it is converted from, so that
+/// builder handlers taking `&str` can be called with params of any request.
+/// This is an implementation detail and *should not* be used directly!
+#[doc(hidden)]
+pub trait ParamOf<'p, Kind> {
+    type Param: FromParam<'p, Kind>;
+}
+
+// Owned params are the same for every path
+impl<'p, Kind, T> ParamOf<'p, Kind> for T
+where
+    T: for<'a> FromParam<'a, Kind>,
+{
+    type Param = T;
+}
+
+impl<'p> ParamOf<'p, ()> for &str {
+    type Param = &'p str;
+}
+
+impl<'p> ParamOf<'p, ()> for Cow<'_, str> {
+    type Param = Cow<'p, str>;
+}
+
+/// Error of converting a path param, e.g. `user_id = "x"` for `u32`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParamError {
+    message: String,
+    name: String,
+    value: String,
+    // position of the param in the handler, used to find its name
+    pub(crate) index: usize,
+}
+
+impl ParamError {
+    /// Creates an error. Name and value of the param are filled in by the router.
+    pub fn new<M: fmt::Display>(message: M) -> Self {
+        ParamError {
+            message: message.to_string(),
+            name: String::new(),
+            value: String::new(),
+            index: 0,
+        }
+    }
+
+    /// Name of the param, e.g. `user_id`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Raw value of the param
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Description of the error returned by `FromParam`
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub(crate) fn at(mut self, index: usize, value: &str) -> Self {
+        self.index = index;
+        self.value = value.to_string();
+        self
+    }
+
+    /// This is an implementation detail and *should not* be called directly!
+    #[doc(hidden)]
+    pub fn with_param(mut self, name: &str, value: &str) -> Self {
+        self.name = name.to_string();
+        self.value = value.to_string();
+        self
+    }
+
+    pub(crate) fn set_name(&mut self, names: &[String]) {
+        if let Some(name) = names.get(self.index) {
+            self.name = name.clone();
+        }
+    }
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid param `{}` = `{}`: {}",
+            self.name, self.value, self.message
+        )
+    }
+}
+
+impl Error for ParamError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_param() {
+        assert_eq!(u32::from_param("12"), Ok(12));
+        assert_eq!(<&str as FromParam>::from_param("alice"), Ok("alice"));
+        assert_eq!(
+            <Cow<str> as FromParam>::from_param("alice"),
+            Ok(Cow::Borrowed("alice"))
+        );
+        let error = u32::from_param("x").unwrap_err().with_param("user_id", "x");
+        assert_eq!(error.name(), "user_id");
+        assert_eq!(
+            error.to_string(),
+            "Invalid param `user_id` = `x`: invalid digit found in string"
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use std::slice;
+
+/// Compiled route path, e.g. `/users/{user_id}/transactions`.
+///
+/// This is the matcher shared by the `router!` macro and the `Router` builder.
+/// Params may be written either as `{user_id}` or as `{user_id: usize}` - the type
+/// is ignored here, parsing is done by the handler.
+///
+/// The last segment can be a wildcard param, e.g. `/files/{*path}`, that matches
+/// the rest of the path including slashes.
+///
+/// Matching doesn't allocate: param values are borrowed from the path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    template: String,
+    params: Vec<String>,
+    segments: Vec<Segment>,
+    is_prefix: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Static(String),
+    Param,
+    Wildcard,
+}
+
+impl Pattern {
+    /// Compiles a path template into a pattern
+    pub fn new(template: &str) -> Self {
+        Pattern::compile(template, false)
+    }
+
+    /// Compiles a path template into a pattern that also matches any path
+    /// under it, e.g. `/api/v1` matches `/api/v1` and `/api/v1/users`
+    pub fn prefix(template: &str) -> Self {
+        Pattern::compile(template, true)
+    }
+
+    fn compile(template: &str, is_prefix: bool) -> Self {
+        let mut normalized = String::new();
+        let mut params = Vec::new();
+        let mut segments = Vec::new();
+        let mut has_wildcard = false;
+        for segment in template.split('/').filter(|s| !s.is_empty()) {
+            assert!(
+                !has_wildcard,
+                "Wildcard param must be the last segment of `{}`",
+                template
+            );
+            normalized.push('/');
+            let segment = segment.trim();
+            if segment.starts_with('{') && segment.ends_with('}') {
+                let inner = segment[1..segment.len() - 1].trim();
+                has_wildcard = inner.starts_with('*');
+                let inner = inner.trim_start_matches('*');
+                let name = inner.split(':').next().unwrap_or("").trim();
+                normalized.push('{');
+                if has_wildcard {
+                    normalized.push('*');
+                    segments.push(Segment::Wildcard);
+                } else {
+                    segments.push(Segment::Param);
+                }
+                normalized.push_str(name);
+                normalized.push('}');
+                params.push(name.to_string());
+            } else {
+                normalized.push_str(segment);
+                segments.push(Segment::Static(segment.to_string()));
+            }
+        }
+        // handle home case
+        if normalized.is_empty() {
+            normalized.push('/');
+        }
+        Pattern {
+            template: normalized,
+            params,
+            segments,
+            is_prefix,
+        }
+    }
+
+    /// Normalized path template, e.g. `/users/{user_id}`
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// Names of params in the order they appear in the path
+    pub fn params(&self) -> &[String] {
+        &self.params
+    }
+
+    /// Returns raw param values if the path matches the pattern
+    pub fn matches<'a>(&self, path: &'a str) -> Option<Vec<&'a str>> {
+        self.captures(path).map(|captures| captures.collect())
+    }
+
+    /// Same as `matches`, but returns an iterator over raw param values,
+    /// so that nothing is allocated
+    pub fn captures<'p, 'a>(&'p self, path: &'a str) -> Option<Captures<'p, 'a>> {
+        let captures = Captures {
+            segments: self.segments.iter(),
+            rest: path,
+        };
+        // home route matches only `/`, the prefix of home matches everything
+        if self.segments.is_empty() {
+            return match self.is_prefix || path == "/" {
+                true => Some(captures),
+                false => None,
+            };
+        }
+        let mut check = captures.clone();
+        loop {
+            match check.step()? {
+                Step::End => break,
+                _ => continue,
+            }
+        }
+        match check.rest {
+            "" => Some(captures),
+            rest if self.is_prefix && rest.starts_with('/') => Some(captures),
+            _ => None,
+        }
+    }
+}
+
+/// Iterator over raw param values of a matched path, see `Pattern::captures`
+#[derive(Debug, Clone)]
+pub struct Captures<'p, 'a> {
+    segments: slice::Iter<'p, Segment>,
+    rest: &'a str,
+}
+
+// Result of consuming one segment of the path
+enum Step<'a> {
+    Static,
+    Param(&'a str),
+    End,
+}
+
+impl<'p, 'a> Captures<'p, 'a> {
+    // Returns `None` if the path doesn't match the segment
+    fn step(&mut self) -> Option<Step<'a>> {
+        let segment = match self.segments.next() {
+            Some(segment) => segment,
+            None => return Some(Step::End),
+        };
+        if !self.rest.starts_with('/') {
+            return None;
+        }
+        let rest = &self.rest[1..];
+        match *segment {
+            Segment::Static(ref value) => {
+                if !rest.starts_with(value.as_str()) {
+                    return None;
+                }
+                self.rest = &rest[value.len()..];
+                Some(Step::Static)
+            }
+            Segment::Param => {
+                let end = rest.find('/').unwrap_or(rest.len());
+                let value = &rest[..end];
+                if value.is_empty() || value.contains(['?', '#']) {
+                    return None;
+                }
+                self.rest = &rest[end..];
+                Some(Step::Param(value))
+            }
+            Segment::Wildcard => {
+                self.rest = "";
+                Some(Step::Param(rest))
+            }
+        }
+    }
+}
+
+impl<'p, 'a> Iterator for Captures<'p, 'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        loop {
+            match self.step()? {
+                Step::Static => continue,
+                Step::Param(value) => return Some(value),
+                Step::End => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template() {
+        let pattern = Pattern::new("/users/{ user_id : u32 }/transactions/{hash}");
+        assert_eq!(pattern.template(), "/users/{user_id}/transactions/{hash}");
+        assert_eq!(pattern.params(), &["user_id", "hash"]);
+        assert_eq!(Pattern::new("").template(), "/");
+        assert_eq!(Pattern::new("/").template(), "/");
+    }
+
+    #[test]
+    fn test_matches() {
+        let pattern = Pattern::new("/users/{user_id}/transactions/{hash}");
+        assert_eq!(
+            pattern.matches("/users/12/transactions/0x1"),
+            Some(vec!["12", "0x1"])
+        );
+        assert_eq!(pattern.matches("/users/12/transactions"), None);
+        assert_eq!(pattern.matches("/users/12/transactions/0x1/"), None);
+        assert_eq!(Pattern::new("/").matches("/"), Some(vec![]));
+        assert_eq!(
+            Pattern::new("/users.json").matches("/users.json"),
+            Some(vec![])
+        );
+        assert_eq!(Pattern::new("/users.json").matches("/usersxjson"), None);
+    }
+
+    #[test]
+    fn test_wildcard() {
+        let pattern = Pattern::new("/proxy/{ * rest : String }");
+        assert_eq!(pattern.template(), "/proxy/{*rest}");
+        assert_eq!(pattern.params(), &["rest"]);
+        assert_eq!(pattern.matches("/proxy/a/b.json"), Some(vec!["a/b.json"]));
+        assert_eq!(pattern.matches("/proxy/"), Some(vec![""]));
+        assert_eq!(pattern.matches("/proxy"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Wildcard param must be the last segment")]
+    fn test_wildcard_not_last() {
+        Pattern::new("/proxy/{*rest}/info");
+    }
+
+    #[test]
+    fn test_prefix() {
+        let pattern = Pattern::prefix("/tenants/{tenant_id}");
+        assert_eq!(pattern.matches("/tenants/1"), Some(vec!["1"]));
+        assert_eq!(pattern.matches("/tenants/1/users/2"), Some(vec!["1"]));
+        assert_eq!(pattern.matches("/tenants"), None);
+        assert_eq!(pattern.matches("/tenants1"), None);
+        assert_eq!(Pattern::prefix("/").matches("/users"), Some(vec![]));
+    }
+
+    #[test]
+    fn test_captures() {
+        let pattern = Pattern::new("/files/{user}/{*path}");
+        let mut captures = pattern.captures("/files/alice/docs/a.txt").unwrap();
+        assert_eq!(captures.next(), Some("alice"));
+        assert_eq!(captures.next(), Some("docs/a.txt"));
+        assert_eq!(captures.next(), None);
+        assert!(pattern.captures("/files//docs").is_none());
+        assert!(pattern.captures("/files/a?b/docs").is_none());
+        assert!(Pattern::new("/").captures("").is_none());
+    }
+}
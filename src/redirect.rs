@@ -0,0 +1,287 @@
+use handler::BoxFuture;
+use std::fmt::Write;
+use std::future::ready;
+
+#[cfg(feature = "with_http")]
+use http::{header::LOCATION, Response, StatusCode};
+
+/// Response of a redirect route, e.g. `GET /old-users/{id: u32} => redirect(308, "/users/{id}")`.
+///
+/// Routers return redirects through `From<Redirect>`, which is implemented for `http::Response<B>`,
+/// for `Result<T, E>` and for `BoxFuture<T>` (given `T: From<Redirect>`), so routers returning
+/// responses get redirects for free. Other response types need their own impl.
+///
+/// In `RouteInfo` the location is the target template, e.g. `/users/{id}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    /// Http status, e.g. 301, 302, 307 or 308
+    pub status: u16,
+    /// Value of the `Location` header
+    pub location: String,
+}
+
+impl Redirect {
+    /// Creates a redirect to `location`
+    ///
+    /// ### Panics
+    /// If the status is not a redirection (3xx) status.
+    pub fn new<L: Into<String>>(status: u16, location: L) -> Self {
+        assert_redirect_status(status);
+        Redirect {
+            status,
+            location: location.into(),
+        }
+    }
+}
+
+fn assert_redirect_status(status: u16) {
+    assert!(
+        (300..400).contains(&status),
+        "Redirect status should be 3xx, got {}",
+        status
+    );
+}
+
+#[cfg(feature = "with_http")]
+impl<B: Default> From<Redirect> for Response<B> {
+    fn from(redirect: Redirect) -> Self {
+        let mut response = Response::new(B::default());
+        *response.status_mut() =
+            StatusCode::from_u16(redirect.status).unwrap_or(StatusCode::PERMANENT_REDIRECT);
+        if let Ok(location) = redirect.location.parse() {
+            response.headers_mut().insert(LOCATION, location);
+        }
+        response
+    }
+}
+
+impl<T: From<Redirect>, E> From<Redirect> for Result<T, E> {
+    fn from(redirect: Redirect) -> Self {
+        Ok(T::from(redirect))
+    }
+}
+
+impl<T: From<Redirect> + Send + 'static> From<Redirect> for BoxFuture<T> {
+    fn from(redirect: Redirect) -> Self {
+        Box::pin(ready(T::from(redirect)))
+    }
+}
+
+/// Compiled target of a redirect route, e.g. `/users/{id}`, whose params are replaced
+/// with the raw values of the params of the same name in the route path.
+/// This is an implementation detail and *should not* be used directly!
+#[doc(hidden)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedirectTarget {
+    status: u16,
+    template: String,
+    // static parts and indices of the route params
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Static(String),
+    Param(usize),
+}
+
+impl RedirectTarget {
+    /// Compiles the target of a route with params `names`
+    ///
+    /// ### Panics
+    /// If the status is not 3xx, the target doesn't start with `/` or a scheme (e.g. `https:`),
+    /// or the target has a param that is not in `names`.
+    pub fn new(status: u16, template: &str, names: &[String]) -> Self {
+        assert_redirect_status(status);
+        // otherwise a param at the start could make the location point to another host
+        assert!(
+            template.starts_with('/') || has_scheme(template),
+            "Redirect target `{}` should start with `/` or a scheme, e.g. `https:`",
+            template
+        );
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => break,
+            };
+            if start > 0 {
+                parts.push(Part::Static(rest[..start].to_string()));
+            }
+            let name = rest[start + 1..end].trim().trim_start_matches('*').trim();
+            let index = names.iter().position(|param| param == name);
+            let index = index.unwrap_or_else(|| {
+                panic!(
+                    "Redirect target `{}` has param `{}`, which is not in the route",
+                    template, name
+                )
+            });
+            parts.push(Part::Param(index));
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Static(rest.to_string()));
+        }
+        RedirectTarget {
+            status,
+            template: template.to_string(),
+            parts,
+        }
+    }
+
+    /// Target of the route mounted under `prefix` with params `names`, which come before
+    /// the params of the route. Paths are relative to the prefix, e.g. `/users/{id}` mounted
+    /// under `/api` becomes `/api/users/{id}`, while targets with a scheme stay as they are.
+    pub fn mount(&self, prefix: &str, names: &[String]) -> Self {
+        let parts = self.parts.iter().map(|part| match *part {
+            Part::Param(index) => Part::Param(names.len() + index),
+            ref part => part.clone(),
+        });
+        if !self.template.starts_with('/') {
+            return RedirectTarget {
+                status: self.status,
+                template: self.template.clone(),
+                parts: parts.collect(),
+            };
+        }
+        let mut target = RedirectTarget::new(self.status, prefix, names);
+        target.template.push_str(&self.template);
+        target.parts.extend(parts);
+        target
+    }
+
+    /// Description of the target for `RouteInfo`
+    pub fn info(&self) -> Redirect {
+        Redirect::new(self.status, self.template.clone())
+    }
+
+    /// Redirect for the raw param values of a matched route, in the order of `names`.
+    /// Values can't make the location protocol-relative, e.g. `//evil.com`
+    pub fn redirect(&self, values: &[&str]) -> Redirect {
+        let mut location = String::new();
+        for part in self.parts.iter() {
+            match *part {
+                Part::Static(ref value) => location.push_str(value),
+                Part::Param(index) => push_param(&mut location, values.get(index).unwrap_or(&"")),
+            }
+        }
+        // e.g. `/{*path}/index.html` with an empty path
+        while location.starts_with("//") {
+            location.remove(0);
+        }
+        Redirect::new(self.status, location)
+    }
+}
+
+// `https://example.com/{path}` starts with a scheme, `{host}/users` doesn't
+fn has_scheme(template: &str) -> bool {
+    let scheme = match template.find(':') {
+        Some(end) => &template[..end],
+        None => return false,
+    };
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+}
+
+// Appends a param value without empty segments, and percent-encodes backslashes, which
+// browsers take for slashes, as well as spaces and control characters
+fn push_param(location: &mut String, value: &str) {
+    for c in value.chars() {
+        match c {
+            '/' if location.is_empty() || location.ends_with('/') => (),
+            '\\' | ' ' => {
+                let _ = write!(location, "%{:02X}", c as u32);
+            }
+            c if c.is_control() => {
+                let mut buffer = [0; 4];
+                for byte in c.encode_utf8(&mut buffer).bytes() {
+                    let _ = write!(location, "%{:02X}", byte);
+                }
+            }
+            c => location.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // routers in tests return strings
+    impl From<Redirect> for String {
+        fn from(redirect: Redirect) -> Self {
+            format!("{} {}", redirect.status, redirect.location)
+        }
+    }
+
+    #[test]
+    fn test_redirect_target() {
+        let names = vec!["user_id".to_string(), "rest".to_string()];
+        let target = RedirectTarget::new(301, "/users/{user_id}/files/{*rest}?v=1", &names);
+        assert_eq!(
+            target.redirect(&["12", "docs/a.txt"]),
+            Redirect::new(301, "/users/12/files/docs/a.txt?v=1")
+        );
+        assert_eq!(
+            target.info(),
+            Redirect::new(301, "/users/{user_id}/files/{*rest}?v=1")
+        );
+        let target = RedirectTarget::new(308, "https://example.com/", &[]);
+        assert_eq!(target.redirect(&[]).location, "https://example.com/");
+    }
+
+    #[test]
+    fn test_open_redirect() {
+        let names = vec!["path".to_string()];
+        let target = RedirectTarget::new(301, "/{path}", &names);
+        assert_eq!(target.redirect(&["/evil.com"]).location, "/evil.com");
+        assert_eq!(target.redirect(&["//evil.com/a"]).location, "/evil.com/a");
+        assert_eq!(target.redirect(&["\\evil.com"]).location, "/%5Cevil.com");
+        assert_eq!(target.redirect(&["a//b/"]).location, "/a/b/");
+        assert_eq!(target.redirect(&["a\r\nb c"]).location, "/a%0D%0Ab%20c");
+        let target = RedirectTarget::new(301, "/{*path}/index.html", &names);
+        assert_eq!(target.redirect(&[""]).location, "/index.html");
+    }
+
+    #[test]
+    #[should_panic(expected = "should start with `/` or a scheme")]
+    fn test_relative_target() {
+        RedirectTarget::new(301, "{path}", &["path".to_string()]);
+    }
+
+    #[test]
+    fn test_mount() {
+        let names = vec!["id".to_string()];
+        let prefix = vec!["tenant".to_string()];
+        let target = RedirectTarget::new(308, "/users/{id}", &names).mount("/t/{tenant}", &prefix);
+        assert_eq!(target.info().location, "/t/{tenant}/users/{id}");
+        assert_eq!(target.redirect(&["acme", "1"]).location, "/t/acme/users/1");
+        let target = RedirectTarget::new(308, "https://example.com/{id}", &names);
+        let mounted = target.mount("/t/{tenant}", &prefix);
+        assert_eq!(mounted.info().location, "https://example.com/{id}");
+        assert_eq!(
+            mounted.redirect(&["acme", "1"]).location,
+            "https://example.com/1"
+        );
+        assert!(has_scheme("mailto:admin@example.com"));
+        assert!(!has_scheme("{host}:8080/users"));
+    }
+
+    #[test]
+    #[should_panic(expected = "not in the route")]
+    fn test_unknown_param() {
+        RedirectTarget::new(308, "/users/{id}", &["user_id".to_string()]);
+    }
+
+    #[cfg(feature = "with_http")]
+    #[test]
+    fn test_redirect_response() {
+        let response: Result<Response<String>, ()> = Redirect::new(307, "/users").into();
+        let response = response.unwrap();
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(response.headers()[LOCATION], "/users");
+    }
+}
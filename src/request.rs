@@ -0,0 +1,210 @@
+use http::request::Parts;
+use http::{HeaderMap, Request, Uri};
+use method::{InvalidMethod, Method};
+use router::Router;
+use std::convert::TryFrom;
+
+/// Context of a request routed directly from an `http::Request<B>`.
+///
+/// Keeps the user state together with the request parts and body, so that
+/// handlers can read headers, query, etc. The router passes it to handlers
+/// by reference, just like any other context.
+///
+/// ```rust
+/// fn get_users(context: &RequestContext<AppState, Body>) -> Response<Body> {
+///     let page = context.query();
+///     ...
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RequestContext<S, B> {
+    state: S,
+    parts: Parts,
+    body: B,
+}
+
+impl<S, B> RequestContext<S, B> {
+    /// Creates a context from the user state and a request
+    pub fn new(state: S, request: Request<B>) -> Self {
+        let (parts, body) = request.into_parts();
+        RequestContext { state, parts, body }
+    }
+
+    /// User defined state, e.g. database interfaces and http clients
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Request method, as it came from the `http` crate
+    pub fn method(&self) -> &http::Method {
+        &self.parts.method
+    }
+
+    /// Request uri
+    pub fn uri(&self) -> &Uri {
+        &self.parts.uri
+    }
+
+    /// Request path, e.g. `/users/12`
+    pub fn path(&self) -> &str {
+        self.parts.uri.path()
+    }
+
+    /// Raw query string, e.g. `page=2&limit=10`
+    pub fn query(&self) -> Option<&str> {
+        self.parts.uri.query()
+    }
+
+    /// Request headers
+    pub fn headers(&self) -> &HeaderMap {
+        &self.parts.headers
+    }
+
+    /// All request parts (method, uri, version, headers and extensions)
+    pub fn parts(&self) -> &Parts {
+        &self.parts
+    }
+
+    /// Request body
+    pub fn body(&self) -> &B {
+        &self.body
+    }
+
+    /// Splits the context back into the user state and the request
+    pub fn into_inner(self) -> (S, Request<B>) {
+        (self.state, Request::from_parts(self.parts, self.body))
+    }
+}
+
+/// Routes an `http::Request<B>` with a closure returned by `router!`.
+///
+/// Method and path are taken from the request, and handlers get a `RequestContext`.
+/// Returns an error if the request method can't be represented as `Method`,
+/// usually answered with `501 Not Implemented`.
+///
+/// ```rust
+/// let router = router!(
+///     GET /users => get_users,
+///     _ => not_found,
+/// );
+/// let response = http_router::dispatch(router, state, request)
+///     .unwrap_or_else(|_| not_implemented());
+/// ```
+pub fn dispatch<S, B, R, F>(router: F, state: S, request: Request<B>) -> Result<R, InvalidMethod>
+where
+    F: FnOnce(RequestContext<S, B>, Method, &str) -> R,
+{
+    let method = Method::try_from(request.method())?;
+    // the path is copied because the context takes ownership of the request
+    let path = request.uri().path().to_string();
+    Ok(router(RequestContext::new(state, request), method, &path))
+}
+
+impl<S, B, R> Router<RequestContext<S, B>, R> {
+    /// Routes an `http::Request<B>`. See `dispatch` for details.
+    pub fn handle_request(&self, state: S, request: Request<B>) -> Result<R, InvalidMethod> {
+        dispatch(
+            |context, method, path| self.handle(context, method, path),
+            state,
+            request,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Context = RequestContext<&'static str, String>;
+
+    fn get_users(context: &Context) -> String {
+        format!(
+            "{} users of {}, query {}",
+            context.method(),
+            context.state(),
+            context.query().unwrap_or("-")
+        )
+    }
+
+    fn post_users(context: &Context) -> String {
+        format!("created {}", context.body())
+    }
+
+    fn get_user(context: &Context, user_id: u32) -> String {
+        let version = context
+            .headers()
+            .get("x-api-version")
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("v1");
+        format!("{} user {}", version, user_id)
+    }
+
+    fn not_found(context: &Context) -> String {
+        format!("not found {}", context.path())
+    }
+
+    fn request(method: &str, uri: &str, body: &str) -> Request<String> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("x-api-version", "v2")
+            .body(body.to_string())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let router = router!(
+            GET /users => get_users,
+            POST /users => post_users,
+            GET /users/{user_id: u32} => get_user,
+            _ => not_found,
+        );
+        let route = |request| dispatch(router, "acme", request).unwrap();
+        assert_eq!(
+            route(request("GET", "/users?page=2", "")),
+            "GET users of acme, query page=2"
+        );
+        assert_eq!(route(request("POST", "/users", "alice")), "created alice");
+        assert_eq!(
+            route(request("GET", "http://localhost/users/12", "")),
+            "v2 user 12"
+        );
+        assert_eq!(
+            route(request("GET", "/users/alice", "")),
+            "not found /users/alice"
+        );
+        let long = request(&"X".repeat(32), "/users", "");
+        assert_eq!(
+            dispatch(router, "acme", long).unwrap_err().name(),
+            "X".repeat(32)
+        );
+    }
+
+    #[test]
+    fn test_handle_request() {
+        let router = Router::builder()
+            .route(Method::GET, "/users", get_users)
+            .route(Method::GET, "/users/{user_id}", get_user)
+            .fallback(not_found)
+            .build();
+        assert_eq!(
+            router.handle_request("acme", request("GET", "/users", "")),
+            Ok("GET users of acme, query -".to_string())
+        );
+        assert_eq!(
+            router.handle_request("acme", request("GET", "/users/3", "")),
+            Ok("v2 user 3".to_string())
+        );
+    }
+
+    #[test]
+    fn test_into_inner() {
+        let context = RequestContext::new("acme", request("PUT", "/users/1?x=1", "body"));
+        assert_eq!(context.uri(), "/users/1?x=1");
+        let (state, request) = context.into_inner();
+        assert_eq!(state, "acme");
+        assert_eq!(request.method(), http::Method::PUT);
+        assert_eq!(request.body(), "body");
+    }
+}
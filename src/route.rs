@@ -0,0 +1,50 @@
+use deprecation::Deprecation;
+use method::Method;
+use redirect::Redirect;
+use std::borrow::Cow;
+
+/// Description of a single route, as declared in `router!` / `routes!`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteInfo {
+    /// Http verb of the route
+    pub method: Method,
+    /// Host template of routes that match on the host, e.g. `{tenant}.example.com`
+    pub host: Option<String>,
+    /// Path template with params in braces, e.g. `/users/{user_id}`
+    pub path: String,
+    /// Typed params in the order they are passed to the handler, i.e. the params
+    /// of the host followed by the params of the path
+    pub params: Vec<ParamInfo>,
+    /// Name of the handler function, `redirect` for redirect routes, `files` for static files
+    /// and `explain` for the explain endpoint
+    pub handler: &'static str,
+    /// Status and target template of redirect routes, e.g. `/users/{id}`
+    pub redirect: Option<Redirect>,
+    /// Deprecation of deprecated routes, e.g. with the date of their removal
+    pub deprecation: Option<Deprecation>,
+    /// Media type of the request body the route consumes, e.g. `application/json`
+    pub consumes: Option<&'static str>,
+    /// Media type of the response the route produces, e.g. `text/csv`
+    pub produces: Option<&'static str>,
+}
+
+/// Description of a typed path param, e.g. `{user_id: usize}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParamInfo {
+    /// Name of the param, e.g. `user_id`
+    pub name: String,
+    /// Rust type of the param, e.g. `usize`
+    pub ty: &'static str,
+}
+
+// Description of a route for the request method. Extension methods of routes
+// matching any method are not described, so their description is made on the fly
+pub(crate) fn info_for(infos: &[RouteInfo], method: Method) -> Cow<'_, RouteInfo> {
+    match infos.iter().find(|info| info.method == method) {
+        Some(info) => Cow::Borrowed(info),
+        None => Cow::Owned(RouteInfo {
+            method,
+            ..infos[0].clone()
+        }),
+    }
+}
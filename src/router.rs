@@ -0,0 +1,2341 @@
+use deprecation::Deprecation;
+use explain::{self, Explanation, RouteMatcher, SharedParamCheck};
+use extract::Rejection;
+#[cfg(feature = "static_files")]
+use files::{FileResponse, StaticFiles};
+use guard::Guard;
+use handler::{AsyncHandler, BoxFuture, Handler, MethodHandler};
+use headers::{self, ApiVersion, RequestHeaders};
+use host::{HostPattern, RequestHost};
+use media::Negotiation;
+use method::{Method, STANDARD_METHODS};
+use metrics::{self, Metrics, Outcome};
+use middleware::{self, Chain, Middleware, Next};
+use param::FromParam;
+use pattern::Pattern;
+use redirect::{Redirect, RedirectTarget};
+use route::{self, ParamInfo, RouteInfo};
+use std::any::type_name;
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+#[cfg(feature = "tracing")]
+use trace;
+#[cfg(feature = "tracing")]
+use tracing::debug;
+
+// Handlers and guards are shared by the aliases of a route
+type SharedHandler<C, R> =
+    Arc<dyn Fn(&C, Method, &[&str], Chain<C, R>) -> Option<Result<R, Rejection>> + Send + Sync>;
+type BoxedFallback<C, R> = Box<dyn Fn(&C) -> R + Send + Sync>;
+type BoxedRejectionHandler<C, R> = Box<dyn Fn(&C, Rejection) -> R + Send + Sync>;
+type SharedMiddleware<C, R> = Arc<dyn Middleware<C, R>>;
+type SharedGuard<C> = Arc<dyn Fn(&C) -> Guard + Send + Sync>;
+type Finish<R> = Box<dyn Fn(R, Box<dyn FnOnce() + Send>) -> R + Send + Sync>;
+// Handler of a redirect route for its target and the number of its path params
+type RedirectHandler<C, R> = fn(RedirectTarget, usize) -> SharedHandler<C, R>;
+
+// Metrics of a router. `finish` records the request once the output is ready,
+// i.e. right away for sync routers and when the future completes for async ones
+struct RouterMetrics<R> {
+    metrics: Arc<dyn Metrics>,
+    finish: Finish<R>,
+}
+
+struct RouteHost<C> {
+    pattern: HostPattern,
+    host_of: fn(&C) -> Option<&str>,
+}
+
+// derived `Clone` would require `C: Clone`
+impl<C> Clone for RouteHost<C> {
+    fn clone(&self) -> Self {
+        RouteHost {
+            pattern: self.pattern.clone(),
+            host_of: self.host_of,
+        }
+    }
+}
+
+struct RouteMedia<C> {
+    consumes: Option<&'static str>,
+    produces: Option<&'static str>,
+    header_of: for<'a> fn(&'a C, &str) -> Option<&'a str>,
+}
+
+impl<C> Clone for RouteMedia<C> {
+    fn clone(&self) -> Self {
+        RouteMedia {
+            consumes: self.consumes,
+            produces: self.produces,
+            header_of: self.header_of,
+        }
+    }
+}
+
+impl<C> RouteMedia<C> {
+    fn matches(&self, context: &C, negotiation: &Negotiation) -> bool {
+        let header = |name| (self.header_of)(context, name);
+        self.consumes
+            .is_none_or(|media_type| negotiation.consumes(header("content-type"), media_type))
+            && self
+                .produces
+                .is_none_or(|media_type| negotiation.produces(header("accept"), media_type))
+    }
+}
+
+struct Route<C, R> {
+    // `None` matches any method
+    methods: Option<Vec<Method>>,
+    host: Option<RouteHost<C>>,
+    pattern: Pattern,
+    // other paths handled by the route, expanded into routes by `RouterBuilder::build`
+    aliases: Vec<Pattern>,
+    media: Option<RouteMedia<C>>,
+    param_types: Vec<&'static str>,
+    handler_name: &'static str,
+    redirect: Option<Redirect>,
+    // target of a redirect route, its handler is rebuilt when the route is mounted
+    redirect_target: Option<(RedirectTarget, RedirectHandler<C, R>)>,
+    deprecation: Option<Deprecation>,
+    handler: SharedHandler<C, R>,
+    // the handler takes all params of the host and the path as `&str`, e.g. of redirects
+    raw_params: bool,
+    param_check: SharedParamCheck,
+    guards: Vec<SharedGuard<C>>,
+    // outer middleware comes first
+    middleware: Vec<SharedMiddleware<C, R>>,
+    // descriptions passed to middleware, filled in by `RouterBuilder::build`
+    infos: Vec<RouteInfo>,
+}
+
+impl<C, R> Route<C, R> {
+    // Copy of the route with another path
+    fn alias(&self, pattern: Pattern) -> Self {
+        Route {
+            methods: self.methods.clone(),
+            host: self.host.clone(),
+            pattern,
+            aliases: Vec::new(),
+            media: self.media.clone(),
+            param_types: self.param_types.clone(),
+            handler_name: self.handler_name,
+            redirect: self.redirect.clone(),
+            redirect_target: self.redirect_target.clone(),
+            deprecation: self.deprecation.clone(),
+            handler: self.handler.clone(),
+            raw_params: self.raw_params,
+            param_check: self.param_check.clone(),
+            guards: self.guards.clone(),
+            middleware: self.middleware.clone(),
+            infos: Vec::new(),
+        }
+    }
+
+    // The route under `prefix`, whose params come after those of the host
+    fn mount(mut self, prefix: &Pattern) -> Self {
+        let path = format!("{}{}", prefix.template(), self.pattern.template());
+        self.pattern = Pattern::new(&path);
+        if self.raw_params {
+            self.param_types = vec!["&str"; self.param_count()];
+        }
+        if let Some((target, handler)) = self.redirect_target.take() {
+            let target = target.mount(prefix.template(), prefix.params());
+            self.handler = handler(target.clone(), self.pattern.params().len());
+            self.redirect = Some(target.info());
+            self.redirect_target = Some((target, handler));
+        }
+        self
+    }
+
+    fn matches_method(&self, method: Method) -> bool {
+        match self.methods {
+            Some(ref methods) => methods.contains(&method),
+            None => true,
+        }
+    }
+
+    // Names of the host params followed by the names of the path params
+    fn param_names(&self) -> Vec<String> {
+        let host_params = match self.host {
+            Some(ref host) => host.pattern.params(),
+            None => &[],
+        };
+        host_params
+            .iter()
+            .chain(self.pattern.params())
+            .cloned()
+            .collect()
+    }
+
+    fn param_count(&self) -> usize {
+        let host_params = self
+            .host
+            .as_ref()
+            .map_or(0, |host| host.pattern.params().len());
+        host_params + self.pattern.params().len()
+    }
+
+    // One description per method, routes matching any method are described
+    // with all of the standard methods
+    fn info(&self) -> Vec<RouteInfo> {
+        let names = self.param_names();
+        // leading handler params without a name in the path are expected
+        // to come from a mount prefix, so names are aligned to the end
+        let skip = self.param_types.len() - names.len();
+        let params: Vec<ParamInfo> = names
+            .iter()
+            .zip(self.param_types.iter().skip(skip))
+            .map(|(name, ty)| ParamInfo {
+                name: name.clone(),
+                ty,
+            })
+            .collect();
+        let methods = match self.methods {
+            Some(ref methods) => methods.clone(),
+            None => STANDARD_METHODS.to_vec(),
+        };
+        methods
+            .into_iter()
+            .map(|method| RouteInfo {
+                method,
+                host: self
+                    .host
+                    .as_ref()
+                    .map(|host| host.pattern.template().to_string()),
+                path: self.pattern.template().to_string(),
+                params: params.clone(),
+                handler: self.handler_name,
+                redirect: self.redirect.clone(),
+                deprecation: self.deprecation.clone(),
+                consumes: self.media.as_ref().and_then(|media| media.consumes),
+                produces: self.media.as_ref().and_then(|media| media.produces),
+            })
+            .collect()
+    }
+
+    fn matcher(&self) -> RouteMatcher {
+        RouteMatcher {
+            methods: self.methods.clone(),
+            host: self
+                .host
+                .as_ref()
+                .map(|host| host.pattern.template().to_string()),
+            pattern: self.pattern.clone(),
+            param_count: self.param_types.len(),
+            handler: self.handler_name,
+            param_check: self.param_check.clone(),
+            conditional: self.host.is_some() || self.media.is_some() || !self.guards.is_empty(),
+        }
+    }
+
+    // The first guard that doesn't allow the request decides
+    fn check_guards(&self, context: &C) -> Guard {
+        for guard in self.guards.iter() {
+            match guard(context) {
+                Guard::Allow => (),
+                outcome => return outcome,
+            }
+        }
+        Guard::Allow
+    }
+
+    fn run_middleware(&self, context: &C, method: Method, params: &[&str], next: Next<C, R>) -> R {
+        if self.middleware.is_empty() {
+            return next.run(context);
+        }
+        let info = route::info_for(&self.infos, method);
+        run_middleware(&self.middleware, context, &info, params, next)
+    }
+}
+
+fn run_middleware<C, R>(
+    middleware: &[SharedMiddleware<C, R>],
+    context: &C,
+    route: &RouteInfo,
+    params: &[&str],
+    next: Next<C, R>,
+) -> R {
+    match middleware.split_first() {
+        Some((first, rest)) => {
+            let mut next = Some(next);
+            let mut run = |context: &C| {
+                let next = next.take().expect("Next is run once");
+                run_middleware(rest, context, route, params, next)
+            };
+            first.call(context, route, Next::new(&mut run).with_params(params))
+        }
+        None => next.run(context),
+    }
+}
+
+// Handler of a redirect route, whose path has `count` params
+fn redirect_handler<C, R: From<Redirect>>(
+    target: RedirectTarget,
+    count: usize,
+) -> SharedHandler<C, R> {
+    Arc::new(move |context, _, params, chain| {
+        // params of the host come first
+        let params = &params[params.len() - count..];
+        let redirect = target.redirect(params);
+        Some(Ok(middleware::through_chain(context, chain, redirect)))
+    })
+}
+
+/// Router built at runtime, an alternative to the `router!` macro for routes
+/// that are not known at compile time (e.g. coming from plugins or config).
+///
+/// ```rust
+/// let router = Router::builder()
+///     .route(Method::GET, "/users", get_users)
+///     .route(Method::PUT, "/users/{user_id}", put_users)
+///     .fallback(not_found)
+///     .build();
+///
+/// router.handle(ctx, Method::PUT, "/users/12")
+/// ```
+///
+/// Matching rules are the same as in `router!`: routes are tried in the order they
+/// were added, and a route whose params can't be parsed counts as non-matching.
+/// If no route matches, the error of the first invalid param is passed to the
+/// rejection handler as `Rejection::InvalidParam`.
+///
+/// If an extractor of the matched route fails, the request is passed to the
+/// rejection handler (see `RouterBuilder::rejection`), or to the fallback if there is none.
+///
+/// Guards of a route (see `RouterBuilder::guard`) run once its path matches and
+/// can skip the route or reject the request.
+///
+/// Media types of a route (see `RouterBuilder::consumes` and `RouterBuilder::produces`)
+/// are checked against `Content-Type` and `Accept`, so several routes with the same path
+/// can serve different formats.
+///
+/// Middleware set with `RouterBuilder::middleware` and `RouterBuilder::with`
+/// runs around the handler of the matched route.
+///
+/// Routers can be nested with `RouterBuilder::mount`.
+pub struct Router<C, R> {
+    routes: Vec<Route<C, R>>,
+    // fallbacks of mounted routers, applied only to paths under their prefix
+    scoped_fallbacks: Vec<(Pattern, BoxedFallback<C, R>)>,
+    fallback: BoxedFallback<C, R>,
+    rejection: Option<BoxedRejectionHandler<C, R>>,
+    metrics: Option<RouterMetrics<R>>,
+    // names of the params whose values are not traced
+    #[cfg(feature = "tracing")]
+    redacted: Vec<String>,
+    #[cfg(feature = "tracing")]
+    instrument: trace::Instrument<R>,
+}
+
+impl<C, R> Router<C, R> {
+    /// Creates a builder for the router
+    pub fn builder() -> RouterBuilder<C, R> {
+        RouterBuilder {
+            routes: Vec::new(),
+            scoped_fallbacks: Vec::new(),
+            fallback: None,
+            rejection: None,
+            middleware: Vec::new(),
+            metrics: None,
+            #[cfg(feature = "tracing")]
+            redacted: Vec::new(),
+            #[cfg(feature = "tracing")]
+            instrument: trace::keep,
+            explained: Vec::new(),
+        }
+    }
+
+    /// Routes the request and returns the result of the matched handler
+    pub fn handle(&self, context: C, method: Method, path: &str) -> R {
+        match self.route(context, method, path, true) {
+            Ok(output) => output,
+            Err(context) => (self.fallback)(&context),
+        }
+    }
+
+    // Routes the request, handing the context back instead of calling the fallback
+    // of the router if no route matches and `fallback` is not set
+    pub(crate) fn route(
+        &self,
+        context: C,
+        method: Method,
+        path: &str,
+        fallback: bool,
+    ) -> Result<R, C> {
+        #[cfg(feature = "tracing")]
+        {
+            let span = trace::dispatch_span(method);
+            let output = span.in_scope(|| self.record(context, method, path, fallback));
+            output.map(|output| (self.instrument)(output, span))
+        }
+        #[cfg(not(feature = "tracing"))]
+        self.record(context, method, path, fallback)
+    }
+
+    // Dispatches the request and records it with the metrics of the router
+    fn record(&self, context: C, method: Method, path: &str, fallback: bool) -> Result<R, C> {
+        let metrics = match self.metrics {
+            Some(ref metrics) => metrics,
+            None => {
+                return match self.dispatch(&context, method, path, fallback) {
+                    Some((output, _, _)) => Ok(output),
+                    None => Err(context),
+                }
+            }
+        };
+        let started = Instant::now();
+        let (output, route, mut outcome) = match self.dispatch(&context, method, path, fallback) {
+            Some(dispatched) => dispatched,
+            None => return Err(context),
+        };
+        if outcome == Outcome::Fallback && self.is_method_not_allowed(method, path) {
+            outcome = Outcome::MethodNotAllowed;
+        }
+        let route = route.map(|route| self.routes[route].pattern.template().to_string());
+        let recorder = metrics.metrics.clone();
+        let done = move || recorder.record(method, route.as_deref(), outcome, started.elapsed());
+        Ok((metrics.finish)(output, Box::new(done)))
+    }
+
+    // Returns the output with the index of the route that decided it, if any, and the outcome.
+    // Returns `None` instead of calling the fallback of the router if `fallback` is not set
+    fn dispatch(
+        &self,
+        context: &C,
+        method: Method,
+        path: &str,
+        fallback: bool,
+    ) -> Option<(R, Option<usize>, Outcome)> {
+        let negotiation = Negotiation::new();
+        // the first invalid param is reported if no other route matches
+        let mut param_error = None;
+        // routes skipped for a less preferred media type are tried again if nothing matches
+        loop {
+            let outcome = self.try_routes(context, method, path, &negotiation, &mut param_error);
+            if outcome.is_some() {
+                return outcome;
+            }
+            if !negotiation.retry() {
+                break;
+            }
+        }
+        if let Some((route, rejection)) = param_error {
+            let output = self.reject(context, rejection, path);
+            return Some((output, Some(route), Outcome::BadParam));
+        }
+        if let Some(rejection) = negotiation.take_rejection() {
+            let output = self.reject(context, rejection, path);
+            return Some((output, None, Outcome::Rejected));
+        }
+        let output = match self.scoped_fallback(context, path) {
+            Some(output) => output,
+            None if fallback => (self.fallback)(context),
+            None => return None,
+        };
+        #[cfg(feature = "tracing")]
+        debug!("no route matched, calling the fallback");
+        Some((output, None, Outcome::Fallback))
+    }
+
+    fn try_routes(
+        &self,
+        context: &C,
+        method: Method,
+        path: &str,
+        negotiation: &Negotiation,
+        param_error: &mut Option<(usize, Rejection)>,
+    ) -> Option<(R, Option<usize>, Outcome)> {
+        for (index, route) in self.routes.iter().enumerate() {
+            // a retry only tries the routes skipped for a less preferred media type
+            if !negotiation.next_route() || !route.matches_method(method) {
+                continue;
+            }
+            // host params come before path params
+            let host_captures = match route.host {
+                Some(ref host) => {
+                    match (host.host_of)(context).and_then(|h| host.pattern.captures(h)) {
+                        Some(captures) => Some(captures),
+                        None => continue,
+                    }
+                }
+                None => None,
+            };
+            if let Some(captures) = route.pattern.captures(path) {
+                #[cfg(feature = "tracing")]
+                let template = route.pattern.template();
+                if let Some(ref media) = route.media {
+                    if !media.matches(context, negotiation) {
+                        #[cfg(feature = "tracing")]
+                        debug!(route = template, "route skipped, media types don't fit");
+                        continue;
+                    }
+                }
+                let rejected = |rejection: Rejection| {
+                    #[cfg(feature = "tracing")]
+                    debug!(route = template, rejection = %rejection, "request rejected");
+                    let output = self.reject(context, rejection, path);
+                    Some((output, Some(index), Outcome::Rejected))
+                };
+                match route.check_guards(context) {
+                    Guard::Allow => (),
+                    Guard::Skip => {
+                        #[cfg(feature = "tracing")]
+                        debug!(route = template, "route skipped by a guard");
+                        continue;
+                    }
+                    Guard::Reject(rejection) => return rejected(rejection),
+                }
+                let count = route.param_count();
+                let captures = host_captures.into_iter().flatten().chain(captures);
+                let result = with_params(captures, count, |params| {
+                    #[cfg(feature = "tracing")]
+                    trace::record_route(
+                        template,
+                        route.handler_name,
+                        || route.param_names(),
+                        params.iter().cloned(),
+                        &self.redacted,
+                    );
+                    (route.handler)(context, method, params, &|context, next| {
+                        route.run_middleware(context, method, params, next)
+                    })
+                });
+                match result {
+                    Some(Ok(result)) => {
+                        #[cfg(feature = "tracing")]
+                        debug!(route = template, "route matched");
+                        return Some((result, Some(index), Outcome::Handled));
+                    }
+                    Some(Err(Rejection::InvalidParam(mut error))) if param_error.is_none() => {
+                        error.set_name(&route.param_names());
+                        #[cfg(feature = "tracing")]
+                        debug!(route = template, error = %error, "route skipped, invalid param");
+                        *param_error = Some((index, Rejection::InvalidParam(error)));
+                    }
+                    Some(Err(Rejection::InvalidParam(_))) | None => (),
+                    Some(Err(rejection)) => return rejected(rejection),
+                }
+            }
+        }
+        None
+    }
+
+    // The path matches routes, but none of them matches the method
+    fn is_method_not_allowed(&self, method: Method, path: &str) -> bool {
+        let mut matches_path = false;
+        for route in self.routes.iter() {
+            if route.pattern.captures(path).is_some() {
+                if route.matches_method(method) {
+                    return false;
+                }
+                matches_path = true;
+            }
+        }
+        matches_path
+    }
+
+    fn reject(&self, context: &C, rejection: Rejection, path: &str) -> R {
+        match self.rejection {
+            Some(ref handler) => handler(context, rejection),
+            None => self.fallback(context, path),
+        }
+    }
+
+    fn fallback(&self, context: &C, path: &str) -> R {
+        match self.scoped_fallback(context, path) {
+            Some(output) => output,
+            None => (self.fallback)(context),
+        }
+    }
+
+    // Calls the fallback of the mounted router the path belongs to, if any
+    fn scoped_fallback(&self, context: &C, path: &str) -> Option<R> {
+        self.scoped_fallbacks
+            .iter()
+            .find(|(pattern, _)| pattern.matches(path).is_some())
+            .map(|(_, fallback)| fallback(context))
+    }
+
+    /// Descriptions of all routes in the order they are matched
+    pub fn routes(&self) -> Vec<RouteInfo> {
+        self.routes
+            .iter()
+            .flat_map(|route| route.infos.iter().cloned())
+            .collect()
+    }
+
+    /// Explains for every route whether it matches the method and the path of a request,
+    /// and which param its handler can't convert, e.g. to find out why a request hits
+    /// the fallback. Handlers are not called. Hosts, guards and media types depend on
+    /// the rest of the request, so routes that have them are only marked as `conditional`.
+    /// Routes of `router!` are explained by `explainer!`.
+    ///
+    /// ```rust
+    /// let explanation = router.explain(Method::GET, "/users/x");
+    /// assert!(explanation.matched().is_none());
+    /// println!("{}", explanation);
+    /// ```
+    ///
+    /// See `RouterBuilder::explain_endpoint` to explain requests over http.
+    pub fn explain(&self, method: Method, path: &str) -> Explanation {
+        let routes: Vec<RouteMatcher> = self.routes.iter().map(Route::matcher).collect();
+        explain::explain(&routes, method, path)
+    }
+}
+
+/// Builder for `Router`. See `Router` for details.
+pub struct RouterBuilder<C, R> {
+    routes: Vec<Route<C, R>>,
+    scoped_fallbacks: Vec<(Pattern, BoxedFallback<C, R>)>,
+    fallback: Option<BoxedFallback<C, R>>,
+    rejection: Option<BoxedRejectionHandler<C, R>>,
+    middleware: Vec<SharedMiddleware<C, R>>,
+    metrics: Option<RouterMetrics<R>>,
+    #[cfg(feature = "tracing")]
+    redacted: Vec<String>,
+    #[cfg(feature = "tracing")]
+    instrument: trace::Instrument<R>,
+    // routes of explain endpoints, filled in by `build`
+    explained: Vec<Arc<OnceLock<Vec<RouteMatcher>>>>,
+}
+
+impl<C: 'static, R: 'static> RouterBuilder<C, R> {
+    /// Adds a route. Params in `path` are written in braces, e.g. `/users/{user_id}`,
+    /// and are passed to the handler in the same order, parsed with `FromParam`.
+    ///
+    /// A handler may take more params than there are in `path` if the router is going
+    /// to be mounted under a prefix with params. See `mount` for details.
+    ///
+    /// ### Panics
+    /// If the handler takes fewer params than there are in `path`.
+    pub fn route<H, Args>(self, method: Method, path: &str, handler: H) -> Self
+    where
+        H: Handler<C, R, Args>,
+        Args: 'static,
+    {
+        let param_types = handler.param_types();
+        let handler = Arc::new(handler);
+        let checker = handler.clone();
+        self.add_route(
+            Some(vec![method]),
+            path,
+            param_types,
+            short_type_name::<H>(),
+            Arc::new(move |context, _, params, chain| handler.call(context, params, chain)),
+        )
+        .param_check(Arc::new(move |index, param| {
+            checker.check_param(index, param)
+        }))
+    }
+
+    /// Adds a route matching several methods, e.g. `GET` and `HEAD`.
+    /// The handler gets the request method right after the context.
+    /// See `route` for details.
+    pub fn route_methods<H, Args>(self, methods: &[Method], path: &str, handler: H) -> Self
+    where
+        H: MethodHandler<C, R, Args>,
+        Args: 'static,
+    {
+        let param_types = handler.param_types();
+        let handler = Arc::new(handler);
+        let checker = handler.clone();
+        self.add_route(
+            Some(methods.to_vec()),
+            path,
+            param_types,
+            short_type_name::<H>(),
+            Arc::new(move |context, method, params, chain| {
+                handler.call(context, method, params, chain)
+            }),
+        )
+        .param_check(Arc::new(move |index, param| {
+            checker.check_param(index, param)
+        }))
+    }
+
+    /// Adds a route matching any method.
+    /// The handler gets the request method right after the context.
+    /// See `route` for details.
+    pub fn any<H, Args>(self, path: &str, handler: H) -> Self
+    where
+        H: MethodHandler<C, R, Args>,
+        Args: 'static,
+    {
+        let param_types = handler.param_types();
+        let handler = Arc::new(handler);
+        let checker = handler.clone();
+        self.add_route(
+            None,
+            path,
+            param_types,
+            short_type_name::<H>(),
+            Arc::new(move |context, method, params, chain| {
+                handler.call(context, method, params, chain)
+            }),
+        )
+        .param_check(Arc::new(move |index, param| {
+            checker.check_param(index, param)
+        }))
+    }
+
+    /// Adds a redirect route, e.g. from `/old-users/{id}` to `/users/{id}` with status 308.
+    /// Params in `target` are replaced with the raw values of the params of the same name in `path`.
+    /// Empty segments in the values are dropped, so the location can't become protocol-relative
+    /// (e.g. `//evil.com` for `/old//evil.com` and `/old/{*path}` to `/{path}`).
+    /// The router returns redirects with `From<Redirect>`, see `Redirect`.
+    ///
+    /// Once mounted, a target starting with `/` is relative to the prefix, e.g. `/users/{id}`
+    /// mounted under `/tenants/{tenant}` redirects to `/tenants/{tenant}/users/{id}`.
+    /// Targets with a scheme, e.g. `https://example.com/users/{id}`, are not changed.
+    ///
+    /// ```rust
+    /// let router = Router::builder()
+    ///     .redirect(Method::GET, "/old-users/{id}", 308, "/users/{id}")
+    ///     .route(Method::GET, "/users/{id}", get_user)
+    ///     .fallback(not_found)
+    ///     .build();
+    /// ```
+    ///
+    /// ### Panics
+    /// If `status` is not 3xx, `target` doesn't start with `/` or a scheme,
+    /// or `target` has a param that is not in `path`.
+    pub fn redirect(self, method: Method, path: &str, status: u16, target: &str) -> Self
+    where
+        R: From<Redirect>,
+    {
+        let pattern = Pattern::new(path);
+        let target = RedirectTarget::new(status, target, pattern.params());
+        let count = pattern.params().len();
+        let mut builder = self.add_route(
+            Some(vec![method]),
+            path,
+            vec!["&str"; count],
+            "redirect",
+            redirect_handler(target.clone(), count),
+        );
+        let route = builder.routes.last_mut().expect("Route is added");
+        route.redirect = Some(target.info());
+        route.redirect_target = Some((target, redirect_handler::<C, R>));
+        route.raw_params = true;
+        builder
+    }
+
+    /// Serves the files of a directory under `prefix` for `GET` and `HEAD`,
+    /// i.e. adds the route `{prefix}/{*path}`. See `StaticFiles` for details.
+    ///
+    /// ```rust
+    /// let router = Router::builder()
+    ///     .files("/assets", StaticFiles::new("./public"))
+    ///     .fallback(not_found)
+    ///     .build();
+    /// ```
+    #[cfg(feature = "static_files")]
+    pub fn files(self, prefix: &str, files: StaticFiles) -> Self
+    where
+        C: RequestHeaders,
+        R: From<FileResponse>,
+    {
+        let path = format!("{}/{{*path}}", prefix.trim_end_matches('/'));
+        let count = Pattern::new(&path).params().len();
+        self.add_route(
+            Some(vec![Method::GET, Method::HEAD]),
+            &path,
+            vec!["&str"; count],
+            "files",
+            Arc::new(move |context, method, params, chain| {
+                // params of the host and of the prefix come first
+                let path = params.last().copied().unwrap_or("");
+                let response = files.serve(context, method, path);
+                Some(Ok(middleware::through_chain(context, chain, response)))
+            }),
+        )
+        .raw_params()
+    }
+
+    pub(crate) fn add_route(
+        mut self,
+        methods: Option<Vec<Method>>,
+        path: &str,
+        param_types: Vec<&'static str>,
+        handler_name: &'static str,
+        handler: SharedHandler<C, R>,
+    ) -> Self {
+        let pattern = Pattern::new(path);
+        assert!(
+            pattern.params().len() <= param_types.len(),
+            "Route `{}` has {} params, but its handler takes {}",
+            path,
+            pattern.params().len(),
+            param_types.len()
+        );
+        self.routes.push(Route {
+            methods,
+            host: None,
+            pattern,
+            aliases: Vec::new(),
+            media: None,
+            param_types,
+            handler_name,
+            redirect: None,
+            redirect_target: None,
+            deprecation: None,
+            handler,
+            raw_params: false,
+            // params of routes without a typed handler are raw strings
+            param_check: Arc::new(|_, _| Ok(())),
+            guards: Vec::new(),
+            middleware: Vec::new(),
+            infos: Vec::new(),
+        });
+        self
+    }
+
+    fn param_check(mut self, param_check: SharedParamCheck) -> Self {
+        self.routes.last_mut().expect("Route is added").param_check = param_check;
+        self
+    }
+
+    #[cfg(feature = "static_files")]
+    fn raw_params(mut self) -> Self {
+        self.routes.last_mut().expect("Route is added").raw_params = true;
+        self
+    }
+
+    /// Adds a `GET` route at `{prefix}/{method}/{*path}` answering with the `Explanation`
+    /// of the request `method path`, e.g. `GET /_explain/POST/users/x` explains `POST /users/x`.
+    /// It is meant for debugging, so it's up to the application to add it only in development
+    /// or to guard it. A mounted router explains its own routes.
+    ///
+    /// ```rust
+    /// let router = Router::builder()
+    ///     .route(Method::GET, "/users/{user_id}", get_user)
+    ///     .explain_endpoint("/_explain")
+    ///     .fallback(not_found)
+    ///     .build();
+    /// ```
+    ///
+    /// An invalid method is rejected as `Rejection::InvalidParam`.
+    ///
+    /// ### Panics
+    /// If the router is mounted under a prefix with params.
+    pub fn explain_endpoint(mut self, prefix: &str) -> Self
+    where
+        R: From<Explanation>,
+    {
+        let explained = Arc::new(OnceLock::new());
+        self.explained.push(explained.clone());
+        let path = format!("{}/{{method}}/{{*path}}", prefix.trim_end_matches('/'));
+        self.add_route(
+            Some(vec![Method::GET]),
+            &path,
+            vec!["Method", "&str"],
+            "explain",
+            Arc::new(move |context, _, params, chain| {
+                let method = match <Method as FromParam<_>>::from_param(params[0]) {
+                    Ok(method) => method,
+                    Err(error) => {
+                        let error = error.at(0, params[0]);
+                        return Some(Err(Rejection::InvalidParam(error)));
+                    }
+                };
+                let path = format!("/{}", params[1]);
+                let routes = explained.get().map_or(&[][..], Vec::as_slice);
+                let explanation = explain::explain(routes, method, &path);
+                Some(Ok(middleware::through_chain(context, chain, explanation)))
+            }),
+        )
+        .param_check(Arc::new(|index, param| match index {
+            0 => <Method as FromParam<_>>::from_param(param)
+                .map(drop)
+                .map_err(|error| error.at(index, param)),
+            _ => Ok(()),
+        }))
+    }
+
+    /// Restricts the route added last to requests with a matching host, e.g. `{tenant}.example.com`.
+    /// Params of the host are passed to the handler before the params of the path.
+    /// The host is taken from the context with `RequestHost`.
+    ///
+    /// ```rust
+    /// let router = Router::builder()
+    ///     .route(Method::GET, "/users", get_users) // fn get_users(context: &Context, tenant: String)
+    ///     .host("{tenant}.example.com")
+    ///     .fallback(not_found)
+    ///     .build();
+    /// ```
+    ///
+    /// ### Panics
+    /// If no routes were added or the handler takes fewer params than there are in the host and the path.
+    pub fn host(mut self, host: &str) -> Self
+    where
+        C: RequestHost,
+    {
+        let route = self
+            .routes
+            .last_mut()
+            .expect("Host should be set after a route");
+        let pattern = HostPattern::new(host);
+        if route.raw_params {
+            route.param_types = vec!["&str"; pattern.params().len() + route.pattern.params().len()];
+        }
+        assert!(
+            pattern.params().len() + route.pattern.params().len() <= route.param_types.len(),
+            "Route `{}{}` has {} params, but its handler takes {}",
+            pattern.template(),
+            route.pattern.template(),
+            pattern.params().len() + route.pattern.params().len(),
+            route.param_types.len()
+        );
+        route.host = Some(RouteHost {
+            pattern,
+            host_of: <C as RequestHost>::host,
+        });
+        self
+    }
+
+    /// Adds another path to the route added last, e.g. `/people` for `/users`. The alias shares
+    /// the handler, host, guards, media types and middleware of the route, and is described
+    /// as a separate route by `Router::routes`.
+    ///
+    /// ```rust
+    /// let router = Router::builder()
+    ///     .route(Method::GET, "/users/{user_id}", get_user)
+    ///     .alias("/people/{user_id}")
+    ///     .fallback(not_found)
+    ///     .build();
+    /// ```
+    ///
+    /// ### Panics
+    /// If no routes were added or `path` has a different number of params than the route.
+    pub fn alias(mut self, path: &str) -> Self {
+        let route = self
+            .routes
+            .last_mut()
+            .expect("Alias should be added after a route");
+        let pattern = Pattern::new(path);
+        assert_eq!(
+            pattern.params().len(),
+            route.pattern.params().len(),
+            "Alias `{}` should have the same number of params as `{}`",
+            path,
+            route.pattern.template()
+        );
+        route.aliases.push(pattern);
+        self
+    }
+
+    /// Marks the route added last as deprecated. The deprecation is a part of the `RouteInfo`
+    /// of the route, so `deprecation_headers` middleware can announce it to clients.
+    ///
+    /// ```rust
+    /// let router = Router::builder()
+    ///     .route(Method::GET, "/v1/users", get_users)
+    ///     .deprecated(Deprecation::new().sunset("2027-01-01"))
+    ///     .middleware(deprecation_headers)
+    ///     .fallback(not_found)
+    ///     .build();
+    /// ```
+    ///
+    /// ### Panics
+    /// If no routes were added.
+    pub fn deprecated(mut self, deprecation: Deprecation) -> Self {
+        let route = self
+            .routes
+            .last_mut()
+            .expect("Deprecation should be added after a route");
+        route.deprecation = Some(deprecation);
+        self
+    }
+
+    /// Adds a guard to the route added last. Guards run in the order they are added,
+    /// once the path matches and before the params are converted. A guard can skip the
+    /// route, so that later routes are tried, or reject the request (e.g. with 403).
+    ///
+    /// ```rust
+    /// let router = Router::builder()
+    ///     .route(Method::GET, "/admin/users", list_users)
+    ///     .guard(is_admin) // fn is_admin(context: &Context) -> Guard
+    ///     .fallback(not_found)
+    ///     .build();
+    /// ```
+    ///
+    /// See `Guard` for details.
+    ///
+    /// ### Panics
+    /// If no routes were added.
+    pub fn guard<G, O>(mut self, guard: G) -> Self
+    where
+        G: Fn(&C) -> O + Send + Sync + 'static,
+        O: Into<Guard>,
+    {
+        self.routes
+            .last_mut()
+            .expect("Guard should be added after a route")
+            .guards
+            .push(Arc::new(move |context| guard(context).into()));
+        self
+    }
+
+    /// Restricts the route added last to requests with a header of the given value,
+    /// e.g. `X-Tenant: acme`. Other requests skip the route, so that later routes with
+    /// the same method and path are tried.
+    ///
+    /// ### Panics
+    /// If no routes were added.
+    pub fn header(self, name: &'static str, value: &'static str) -> Self
+    where
+        C: RequestHeaders,
+    {
+        self.guard(move |context: &C| headers::header_matches(context, name, value))
+    }
+
+    /// Restricts the route added last to requests for an API version, see `ApiVersion`.
+    /// Other requests skip the route, so that later routes, e.g. an unversioned one, are tried.
+    ///
+    /// ```rust
+    /// let router = Router::builder()
+    ///     .route(Method::GET, "/users", get_users_v2)
+    ///     .version(2)
+    ///     .route(Method::GET, "/users", get_users)
+    ///     .fallback(not_found)
+    ///     .build();
+    /// ```
+    ///
+    /// ### Panics
+    /// If no routes were added.
+    pub fn version(self, version: u32) -> Self
+    where
+        C: RequestHeaders,
+    {
+        self.guard(move |context: &C| ApiVersion::negotiate(context) == Some(ApiVersion(version)))
+    }
+
+    /// Restricts the route added last to requests with a matching `Content-Type`, e.g. `application/json`
+    /// or a range like `text/*`. If no route matches, the request is rejected with
+    /// `Rejection::UnsupportedMediaType` (415) instead of going to the fallback.
+    ///
+    /// ### Panics
+    /// If no routes were added.
+    pub fn consumes(mut self, media_type: &'static str) -> Self
+    where
+        C: RequestHeaders,
+    {
+        self.last_media().consumes = Some(media_type);
+        self
+    }
+
+    /// Restricts the route added last to requests that accept `media_type`. Routes with the
+    /// same path and different media types are chosen by the quality values of `Accept`,
+    /// and routes without a media type match any `Accept`, so they should come last.
+    /// If no route matches, the request is rejected with `Rejection::NotAcceptable` (406).
+    ///
+    /// ```rust
+    /// let router = Router::builder()
+    ///     .route(Method::GET, "/users", get_users_csv)
+    ///     .produces("text/csv")
+    ///     .route(Method::GET, "/users", get_users)
+    ///     .produces("application/json")
+    ///     .fallback(not_found)
+    ///     .build();
+    /// ```
+    ///
+    /// ### Panics
+    /// If no routes were added.
+    pub fn produces(mut self, media_type: &'static str) -> Self
+    where
+        C: RequestHeaders,
+    {
+        self.last_media().produces = Some(media_type);
+        self
+    }
+
+    fn last_media(&mut self) -> &mut RouteMedia<C>
+    where
+        C: RequestHeaders,
+    {
+        let route = self
+            .routes
+            .last_mut()
+            .expect("Media types should be set after a route");
+        route.media.get_or_insert(RouteMedia {
+            consumes: None,
+            produces: None,
+            header_of: <C as RequestHeaders>::header,
+        })
+    }
+
+    /// Adds a middleware to all routes of the router, including the mounted ones.
+    /// Middleware runs in the order it is added, the middleware of an outer router
+    /// runs before the middleware of the nested one, which gives per scope middleware.
+    ///
+    /// ```rust
+    /// let router = Router::builder()
+    ///     .middleware(logging)
+    ///     .route(Method::GET, "/users", get_users)
+    ///     .mount("/admin", admin_router) // `admin_router` has `.middleware(auth)`
+    ///     .fallback(not_found)
+    ///     .build();
+    /// ```
+    ///
+    /// See `Middleware` for details.
+    pub fn middleware<M: Middleware<C, R> + 'static>(mut self, middleware: M) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Adds a middleware to the route added last, after the middleware of the router
+    ///
+    /// ```rust
+    /// let router = Router::builder()
+    ///     .route(Method::DELETE, "/users/{user_id}", delete_user)
+    ///     .with(auth)
+    ///     .fallback(not_found)
+    ///     .build();
+    /// ```
+    ///
+    /// ### Panics
+    /// If no routes were added.
+    pub fn with<M: Middleware<C, R> + 'static>(mut self, middleware: M) -> Self {
+        self.routes
+            .last_mut()
+            .expect("Middleware should be added after a route")
+            .middleware
+            .push(Arc::new(middleware));
+        self
+    }
+
+    /// Mounts all routes of `router` under `prefix`, e.g. `/api/v1` + `/users` = `/api/v1/users`.
+    ///
+    /// Params of the prefix are passed to the nested handlers before their own params,
+    /// so with `/tenants/{tenant_id}` prefix, the handler of `/users/{user_id}` route
+    /// is called as `handler(&context, tenant_id, user_id)`.
+    ///
+    /// Handlers taking the params of the prefix can't be built into a router of their own,
+    /// so such a router is mounted as a `RouterBuilder`, which `mount` builds. To be tested
+    /// on its own, it is built with the prefix declaring its params, see `build_under`:
+    ///
+    /// ```rust
+    /// let users = Router::builder()
+    ///     .route(Method::GET, "/users/{user_id}", get_user) // fn get_user(context: &Context, tenant_id: u64, user_id: u32)
+    ///     .fallback(not_found);
+    /// let router = Router::builder()
+    ///     .mount("/tenants/{tenant_id}", users)
+    ///     .fallback(not_found)
+    ///     .build();
+    /// ```
+    ///
+    /// The fallback of `router` is called for unmatched paths under `prefix`.
+    /// Its rejection handler is not used, rejections are handled by the outer router.
+    ///
+    /// ### Panics
+    /// If a nested handler takes fewer params than there are in the prefix and its path,
+    /// or `router` is a builder without a fallback. `build` panics if a nested handler
+    /// still takes more params.
+    pub fn mount<M: Mountable<C, R>>(mut self, prefix: &str, router: M) -> Self {
+        let router = router.into_router();
+        let prefix_pattern = Pattern::prefix(prefix);
+        for route in router.routes.into_iter() {
+            let route = route.mount(&prefix_pattern);
+            assert!(
+                route.param_count() <= route.param_types.len(),
+                "Route `{}` has {} params, but its handler takes {}",
+                route.pattern.template(),
+                route.param_count(),
+                route.param_types.len()
+            );
+            self.routes.push(route);
+        }
+        for (pattern, fallback) in router.scoped_fallbacks.into_iter() {
+            let path = format!("{}{}", prefix_pattern.template(), pattern.template());
+            self.scoped_fallbacks
+                .push((Pattern::prefix(&path), fallback));
+        }
+        self.scoped_fallbacks
+            .push((prefix_pattern, router.fallback));
+        #[cfg(feature = "tracing")]
+        self.redacted.extend(router.redacted);
+        self
+    }
+
+    /// Records every request with `metrics`, e.g. a `MetricsRegistry`, labelled by the template
+    /// of the matched route. Latency is the time `handle` takes, async routers should use
+    /// `metrics_async`. Metrics of mounted routers are not used, the router they are mounted
+    /// into records their requests.
+    ///
+    /// ```rust
+    /// let metrics = Arc::new(MetricsRegistry::new());
+    /// let router = Router::builder()
+    ///     .route(Method::GET, "/users/{user_id}", get_user)
+    ///     .metrics(metrics.clone())
+    ///     .fallback(not_found)
+    ///     .build();
+    /// ```
+    pub fn metrics<M: Metrics + 'static>(mut self, metrics: M) -> Self {
+        self.metrics = Some(RouterMetrics {
+            metrics: Arc::new(metrics),
+            finish: Box::new(|output, done| {
+                done();
+                output
+            }),
+        });
+        self
+    }
+
+    /// Hides the values of the params named `name` in traces, e.g. of tokens
+    /// (requires the `tracing` feature). Applies to all routes of the router.
+    ///
+    /// ```rust
+    /// let router = Router::builder()
+    ///     .route(Method::GET, "/invites/{token}", get_invite)
+    ///     .redact("token")
+    ///     .fallback(not_found)
+    ///     .build();
+    /// ```
+    #[cfg(feature = "tracing")]
+    pub fn redact(mut self, name: &str) -> Self {
+        self.redacted.push(name.to_string());
+        self
+    }
+
+    /// Sets the handler that is called when no route matches
+    pub fn fallback<F>(mut self, fallback: F) -> Self
+    where
+        F: Fn(&C) -> R + Send + Sync + 'static,
+    {
+        self.fallback = Some(Box::new(fallback));
+        self
+    }
+
+    /// Sets the handler that is called when an extractor of the matched route fails,
+    /// e.g. to answer with `rejection.status()`. Without it rejected requests go to the fallback.
+    pub fn rejection<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&C, Rejection) -> R + Send + Sync + 'static,
+    {
+        self.rejection = Some(Box::new(handler));
+        self
+    }
+
+    /// Builds the router
+    ///
+    /// ### Panics
+    /// If the fallback is not set, or a handler takes more params than there are in
+    /// the host and the path of its route. Such routes can only be mounted, see `mount`,
+    /// or built with the prefix declaring the missing params, see `build_under`.
+    pub fn build(self) -> Router<C, R> {
+        self.build_router(true)
+    }
+
+    /// Builds the router with all of its routes under `prefix`, as if it was mounted there,
+    /// e.g. to test on its own a router whose handlers take the params of the prefix.
+    /// Unlike in `mount`, the fallback, the rejection handler and the metrics of the router
+    /// are used for all paths.
+    ///
+    /// ```rust
+    /// // `users_router` is a `RouterBuilder`, its handlers take `tenant_id: u64` as their first param
+    /// let router = users_router.build_under("/tenants/{tenant_id}");
+    /// router.handle(ctx, Method::GET, "/tenants/1/users/2")
+    /// ```
+    ///
+    /// ### Panics
+    /// Same as `build`, with the params of `prefix` counted in every route.
+    pub fn build_under(mut self, prefix: &str) -> Router<C, R> {
+        let prefix = Pattern::prefix(prefix);
+        self.expand_aliases();
+        self.routes = ::std::mem::take(&mut self.routes)
+            .into_iter()
+            .map(|route| route.mount(&prefix))
+            .collect();
+        for &mut (ref mut pattern, _) in self.scoped_fallbacks.iter_mut() {
+            let path = format!("{}{}", prefix.template(), pattern.template());
+            *pattern = Pattern::prefix(&path);
+        }
+        self.build()
+    }
+
+    fn expand_aliases(&mut self) {
+        let mut routes = Vec::with_capacity(self.routes.len());
+        for mut route in ::std::mem::take(&mut self.routes).into_iter() {
+            let aliases = ::std::mem::take(&mut route.aliases);
+            let aliases: Vec<_> = aliases
+                .into_iter()
+                .map(|alias| route.alias(alias))
+                .collect();
+            routes.push(route);
+            routes.extend(aliases);
+        }
+        self.routes = routes;
+    }
+
+    // Routers that are built to be mounted may have handlers taking the params of the prefix
+    fn build_router(mut self, complete: bool) -> Router<C, R> {
+        self.expand_aliases();
+        for route in self.routes.iter_mut() {
+            // such a route would never match, since its handler can't be called
+            assert!(
+                !complete || route.param_count() == route.param_types.len(),
+                "Route `{}` has {} params, but its handler takes {}",
+                route.pattern.template(),
+                route.param_count(),
+                route.param_types.len()
+            );
+            let middleware = self.middleware.iter().cloned();
+            route.middleware.splice(0..0, middleware);
+            route.infos = route.info();
+        }
+        for explained in self.explained.iter() {
+            let routes = self.routes.iter().map(Route::matcher).collect();
+            let _ = explained.set(routes);
+        }
+        Router {
+            routes: self.routes,
+            scoped_fallbacks: self.scoped_fallbacks,
+            fallback: self.fallback.expect("Fallback route is required"),
+            rejection: self.rejection,
+            metrics: self.metrics,
+            #[cfg(feature = "tracing")]
+            redacted: self.redacted,
+            #[cfg(feature = "tracing")]
+            instrument: self.instrument,
+        }
+    }
+}
+
+/// A router that can be mounted into another one, see `RouterBuilder::mount`.
+///
+/// Implemented for `Router`, and for `RouterBuilder` whose handlers take the params of the prefix.
+pub trait Mountable<C, R> {
+    /// This is an implementation detail and *should not* be called directly!
+    #[doc(hidden)]
+    fn into_router(self) -> Router<C, R>;
+}
+
+impl<C, R> Mountable<C, R> for Router<C, R> {
+    fn into_router(self) -> Router<C, R> {
+        self
+    }
+}
+
+impl<C: 'static, R: 'static> Mountable<C, R> for RouterBuilder<C, R> {
+    fn into_router(self) -> Router<C, R> {
+        self.build_router(false)
+    }
+}
+
+impl<C: Clone + 'static, R: 'static> RouterBuilder<C, BoxFuture<R>> {
+    /// Adds a route with an async handler, e.g. `async fn get_user(context: Context, user_id: u64)`.
+    /// Async handlers take the context by value, so it is cloned for the matched route.
+    /// See `route` for details.
+    pub fn route_async<H, Args>(self, method: Method, path: &str, handler: H) -> Self
+    where
+        H: AsyncHandler<C, R, Args>,
+        Args: 'static,
+    {
+        let param_types = handler.param_types();
+        let handler = Arc::new(handler);
+        let checker = handler.clone();
+        self.add_route(
+            Some(vec![method]),
+            path,
+            param_types,
+            short_type_name::<H>(),
+            Arc::new(move |context, _, params, chain| handler.call(context, params, chain)),
+        )
+        .param_check(Arc::new(move |index, param| {
+            checker.check_param(index, param)
+        }))
+        .instrumented()
+    }
+
+    /// Same as `files`, but the files are read on worker threads, see `StaticFiles::serve_async`
+    #[cfg(feature = "static_files")]
+    pub fn files_async(self, prefix: &str, files: StaticFiles) -> Self
+    where
+        C: RequestHeaders,
+        R: From<FileResponse> + Send,
+    {
+        let path = format!("{}/{{*path}}", prefix.trim_end_matches('/'));
+        let count = Pattern::new(&path).params().len();
+        self.add_route(
+            Some(vec![Method::GET, Method::HEAD]),
+            &path,
+            vec!["&str"; count],
+            "files",
+            Arc::new(move |context, method, params, chain| {
+                let path = params.last().copied().unwrap_or("");
+                let response = files.serve_async(context, method, path);
+                Some(Ok(middleware::through_chain(context, chain, response)))
+            }),
+        )
+        .raw_params()
+        .instrumented()
+    }
+
+    /// Sets an async handler that is called when no route matches
+    pub fn fallback_async<F, Fut>(self, fallback: F) -> Self
+    where
+        F: Fn(C) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+    {
+        self.fallback(move |context: &C| Box::pin(fallback(context.clone())) as BoxFuture<R>)
+            .instrumented()
+    }
+
+    /// Sets an async handler that is called when an extractor of the matched route fails
+    pub fn rejection_async<F, Fut>(self, handler: F) -> Self
+    where
+        F: Fn(C, Rejection) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+    {
+        self.rejection(move |context: &C, rejection| {
+            Box::pin(handler(context.clone(), rejection)) as BoxFuture<R>
+        })
+        .instrumented()
+    }
+
+    /// Same as `metrics`, but latency lasts until the future of the request completes
+    pub fn metrics_async<M: Metrics + 'static>(mut self, metrics: M) -> Self {
+        self.metrics = Some(RouterMetrics {
+            metrics: Arc::new(metrics),
+            finish: Box::new(metrics::on_ready),
+        });
+        self.instrumented()
+    }
+
+    // Futures of routers with async handlers run in the dispatch span
+    #[cfg(feature = "tracing")]
+    fn instrumented(mut self) -> Self {
+        self.instrument = trace::instrument;
+        self
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    fn instrumented(self) -> Self {
+        self
+    }
+}
+
+// Handlers take at most 7 params, so they fit into a buffer on the stack
+const PARAMS_BUFFER_SIZE: usize = 8;
+
+// Calls `f` with the param values, allocating only for routes with many params
+fn with_params<'a, I, T, F>(captures: I, count: usize, f: F) -> T
+where
+    I: Iterator<Item = &'a str>,
+    F: FnOnce(&[&'a str]) -> T,
+{
+    if count > PARAMS_BUFFER_SIZE {
+        return f(&captures.collect::<Vec<_>>());
+    }
+    let mut buffer = [""; PARAMS_BUFFER_SIZE];
+    let mut len = 0;
+    for (slot, value) in buffer.iter_mut().zip(captures) {
+        *slot = value;
+        len += 1;
+    }
+    f(&buffer[..len])
+}
+
+// `my_crate::controller::get_users` -> `get_users`
+pub(crate) fn short_type_name<T>() -> &'static str {
+    let name = type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use extract::FromRequest;
+    use metrics::MetricsRegistry;
+    use param::{FromParam, ParamError};
+
+    fn get_users(_: &()) -> String {
+        "get_users".to_string()
+    }
+
+    fn patch_transactions(_: &(), id: u32, hash: String) -> String {
+        format!("patch_transactions({}, {})", id, hash)
+    }
+
+    fn fallback(_: &()) -> String {
+        "404".to_string()
+    }
+
+    #[test]
+    fn test_same_as_macro() {
+        let patch_users = |_: &(), id: u32| format!("patch_users({})", id);
+        let macro_router = router!(
+            GET / => get_users,
+            GET /users => get_users,
+            PATCH /users/{user_id: u32} => patch_users,
+            PATCH /users/{user_id: u32}/transactions/{hash: String} => patch_transactions,
+            _ => fallback,
+        );
+        let router = Router::builder()
+            .route(Method::GET, "/", get_users)
+            .route(Method::GET, "/users", get_users)
+            .route(Method::PATCH, "/users/{user_id}", patch_users)
+            .route(
+                Method::PATCH,
+                "/users/{user_id}/transactions/{hash}",
+                patch_transactions,
+            )
+            .fallback(fallback)
+            .build();
+        let test_cases = [
+            (Method::GET, "/"),
+            (Method::GET, "/users"),
+            (Method::POST, "/users"),
+            (Method::PATCH, "/users/12"),
+            (Method::PATCH, "/users/1x2"),
+            (Method::PATCH, "/users/534/transactions/0x234"),
+            (Method::PATCH, "/users/5d34/transactions/0x234"),
+            (Method::GET, "/u"),
+        ];
+        for &(method, path) in test_cases.iter() {
+            assert_eq!(
+                router.handle((), method, path),
+                macro_router((), method, path)
+            );
+        }
+        assert_eq!(
+            router.handle((), Method::PATCH, "/users/12/transactions/0x1"),
+            "patch_transactions(12, 0x1)"
+        );
+    }
+
+    #[test]
+    fn test_routes() {
+        let router = Router::builder()
+            .route(Method::GET, "/users", get_users)
+            .route(
+                Method::PATCH,
+                "/users/{user_id}/transactions/{hash}",
+                patch_transactions,
+            )
+            .fallback(fallback)
+            .build();
+        let routes = router.routes();
+        assert_eq!(routes[0].path, "/users");
+        assert_eq!(routes[0].handler, "get_users");
+        assert_eq!(routes[1].path, "/users/{user_id}/transactions/{hash}");
+        assert_eq!(routes[1].handler, "patch_transactions");
+        assert_eq!(routes[1].params[0].name, "user_id");
+        assert_eq!(routes[1].params[0].ty, "u32");
+        assert_eq!(routes[1].params[1].name, "hash");
+    }
+
+    #[test]
+    #[should_panic(expected = "has 1 params, but its handler takes 0")]
+    fn test_params_mismatch() {
+        let _ = Router::builder()
+            .route(Method::GET, "/users/{user_id}", get_users)
+            .fallback(fallback)
+            .build();
+    }
+
+    #[test]
+    fn test_methods() {
+        let health = |_: &(), method: Method| format!("health({:?})", method);
+        let proxy = |_: &(), method: Method, rest: String| format!("proxy({:?}, {})", method, rest);
+        let router = Router::builder()
+            .route_methods(&[Method::GET, Method::HEAD], "/health", health)
+            .any("/proxy/{*rest}", proxy)
+            .fallback(fallback)
+            .build();
+        assert_eq!(router.handle((), Method::GET, "/health"), "health(GET)");
+        assert_eq!(router.handle((), Method::HEAD, "/health"), "health(HEAD)");
+        assert_eq!(router.handle((), Method::POST, "/health"), "404");
+        assert_eq!(
+            router.handle((), Method::DELETE, "/proxy/a/b"),
+            "proxy(DELETE, a/b)"
+        );
+
+        let routes = router.routes();
+        assert_eq!(routes.len(), 11);
+        assert_eq!(routes[1].method, Method::HEAD);
+        assert_eq!(routes[2].path, "/proxy/{*rest}");
+        assert_eq!(routes[2].params[0].name, "rest");
+    }
+
+    #[test]
+    fn test_mount() {
+        let get_user =
+            |_: &(), tenant_id: u64, user_id: u32| format!("get_user({}, {})", tenant_id, user_id);
+        let get_tenant = |_: &(), tenant_id: u64| format!("get_tenant({})", tenant_id);
+        let users_fallback = |_: &()| "users 404".to_string();
+        let users = Router::builder()
+            .route(Method::GET, "/", get_tenant)
+            .route(Method::GET, "/users/{user_id}", get_user)
+            .fallback(users_fallback);
+        let api = Router::builder()
+            .mount("/tenants/{tenant_id}", users)
+            .fallback(fallback)
+            .build();
+        let router = Router::builder()
+            .route(Method::GET, "/", get_users)
+            .mount("/api/v1", api)
+            .fallback(fallback)
+            .build();
+
+        assert_eq!(router.handle((), Method::GET, "/"), "get_users");
+        assert_eq!(
+            router.handle((), Method::GET, "/api/v1/tenants/1"),
+            "get_tenant(1)"
+        );
+        assert_eq!(
+            router.handle((), Method::GET, "/api/v1/tenants/1/users/2"),
+            "get_user(1, 2)"
+        );
+        assert_eq!(
+            router.handle((), Method::GET, "/api/v1/tenants/1/users/x"),
+            "users 404"
+        );
+        assert_eq!(
+            router.handle((), Method::POST, "/api/v1/tenants/1/users/2"),
+            "users 404"
+        );
+        assert_eq!(router.handle((), Method::GET, "/api/v1/tenants"), "404");
+        assert_eq!(router.handle((), Method::GET, "/users"), "404");
+
+        let routes = router.routes();
+        assert_eq!(
+            routes[2].path,
+            "/api/v1/tenants/{tenant_id}/users/{user_id}"
+        );
+        assert_eq!(routes[2].params[0].name, "tenant_id");
+        assert_eq!(routes[2].params[0].ty, "u64");
+        assert_eq!(routes[2].params[1].name, "user_id");
+        assert_eq!(routes[2].params[1].ty, "u32");
+    }
+
+    #[test]
+    #[should_panic(expected = "has 2 params, but its handler takes 1")]
+    fn test_mount_params_mismatch() {
+        let get_user = |_: &(), user_id: u32| format!("get_user({})", user_id);
+        let users = Router::builder()
+            .route(Method::GET, "/users/{user_id}", get_user)
+            .fallback(fallback)
+            .build();
+        let _ = Router::builder()
+            .mount("/tenants/{tenant_id}", users)
+            .fallback(fallback)
+            .build();
+    }
+
+    #[test]
+    #[should_panic(expected = "has 1 params, but its handler takes 2")]
+    fn test_unmounted_params() {
+        let get_user =
+            |_: &(), tenant_id: u64, user_id: u32| format!("get_user({}, {})", tenant_id, user_id);
+        let _ = Router::builder()
+            .route(Method::GET, "/users/{user_id}", get_user)
+            .fallback(fallback)
+            .build();
+    }
+
+    #[test]
+    fn test_build_under() {
+        let get_user =
+            |_: &(), tenant_id: u64, user_id: u32| format!("get_user({}, {})", tenant_id, user_id);
+        let get_users = |_: &(), tenant_id: u64| format!("get_users({})", tenant_id);
+        let users = || {
+            Router::builder()
+                .route(Method::GET, "/users/{user_id}", get_user)
+                .alias("/people/{user_id}")
+                .redirect(Method::GET, "/old/{id}", 308, "/users/{id}")
+                .mount(
+                    "/all",
+                    Router::builder()
+                        .route(Method::GET, "/", get_users)
+                        .fallback(|_: &()| "all 404".to_string()),
+                )
+                .fallback(|_: &()| "users 404".to_string())
+        };
+
+        // tested on its own
+        let router = users().build_under("/tenants/{tenant_id}");
+        assert_eq!(
+            router.handle((), Method::GET, "/tenants/1/users/2"),
+            "get_user(1, 2)"
+        );
+        assert_eq!(
+            router.handle((), Method::GET, "/tenants/1/people/2"),
+            "get_user(1, 2)"
+        );
+        assert_eq!(
+            router.handle((), Method::GET, "/tenants/1/old/2"),
+            "308 /tenants/1/users/2"
+        );
+        assert_eq!(
+            router.handle((), Method::GET, "/tenants/1/all"),
+            "get_users(1)"
+        );
+        assert_eq!(
+            router.handle((), Method::GET, "/tenants/1/all/x"),
+            "all 404"
+        );
+        assert_eq!(
+            router.handle((), Method::GET, "/tenants/x/users/2"),
+            "users 404"
+        );
+        assert_eq!(router.handle((), Method::GET, "/users/2"), "users 404");
+        let routes = router.routes();
+        assert_eq!(routes[1].path, "/tenants/{tenant_id}/people/{user_id}");
+        assert_eq!(routes[1].params[0].ty, "u64");
+
+        // and mounted
+        let router = Router::builder()
+            .mount("/tenants/{tenant_id}", users())
+            .fallback(fallback)
+            .build();
+        assert_eq!(
+            router.handle((), Method::GET, "/tenants/1/users/2"),
+            "get_user(1, 2)"
+        );
+        assert_eq!(router.handle((), Method::GET, "/users/2"), "404");
+    }
+
+    #[test]
+    #[should_panic(expected = "has 2 params, but its handler takes 3")]
+    fn test_build_under_params_mismatch() {
+        let get_user = |_: &(), org: u64, tenant_id: u64, user_id: u32| {
+            format!("get_user({}, {}, {})", org, tenant_id, user_id)
+        };
+        let _ = Router::builder()
+            .route(Method::GET, "/users/{user_id}", get_user)
+            .fallback(fallback)
+            .build_under("/tenants/{tenant_id}");
+    }
+
+    #[test]
+    #[should_panic(expected = "Fallback route is required")]
+    fn test_no_fallback() {
+        let _: Router<(), String> = Router::builder()
+            .route(Method::GET, "/users", get_users)
+            .build();
+    }
+
+    #[test]
+    fn test_rejection() {
+        struct Token(String);
+
+        impl FromRequest<&'static str> for Token {
+            fn from_request(context: &&'static str) -> Result<Self, Rejection> {
+                match *context {
+                    "" => Err(Rejection::MissingHeader("authorization")),
+                    token => Ok(Token(token.to_string())),
+                }
+            }
+        }
+
+        let get_user =
+            |_: &&'static str, id: u32, token: Token| format!("user {} for {}", id, token.0);
+        let users = Router::builder()
+            .route(Method::GET, "/users/{user_id}", get_user)
+            .fallback(|_: &&'static str| "users 404".to_string())
+            .build();
+        let router = Router::builder()
+            .route(Method::GET, "/users/{user_id}", get_user)
+            .mount("/api", users)
+            .fallback(|_: &&'static str| "404".to_string())
+            .build();
+        assert_eq!(
+            router.handle("alice", Method::GET, "/users/1"),
+            "user 1 for alice"
+        );
+        assert_eq!(router.handle("", Method::GET, "/users/1"), "404");
+        assert_eq!(router.handle("", Method::GET, "/api/users/1"), "users 404");
+
+        let router = Router::builder()
+            .route(Method::GET, "/users/{user_id}", get_user)
+            .rejection(|_: &&'static str, rejection: Rejection| rejection.to_string())
+            .fallback(|_: &&'static str| "404".to_string())
+            .build();
+        assert_eq!(
+            router.handle("", Method::GET, "/users/1"),
+            "Missing header `authorization`"
+        );
+        assert_eq!(
+            router.handle("alice", Method::GET, "/users/x"),
+            "Invalid param `user_id` = `x`: invalid digit found in string"
+        );
+        assert_eq!(router.routes()[0].params[0].ty, "u32");
+    }
+
+    #[test]
+    fn test_custom_param() {
+        struct Slug(String);
+
+        impl<'a> FromParam<'a> for Slug {
+            fn from_param(param: &'a str) -> Result<Self, ParamError> {
+                match param.chars().all(|c| c.is_ascii_lowercase() || c == '-') {
+                    true => Ok(Slug(param.to_string())),
+                    false => Err(ParamError::new("should be lowercase")),
+                }
+            }
+        }
+
+        let get_post = |_: &(), id: u32, slug: Slug| format!("post {} {}", id, slug.0);
+        let router = Router::builder()
+            .route(Method::GET, "/posts/{id}/{slug}", get_post)
+            .rejection(|_: &(), rejection: Rejection| rejection.to_string())
+            .fallback(fallback)
+            .build();
+        assert_eq!(
+            router.handle((), Method::GET, "/posts/1/hello-world"),
+            "post 1 hello-world"
+        );
+        assert_eq!(
+            router.handle((), Method::GET, "/posts/1/Hello"),
+            "Invalid param `slug` = `Hello`: should be lowercase"
+        );
+    }
+
+    #[test]
+    fn test_borrowed_params() {
+        use std::borrow::Cow;
+
+        fn get_file(_: &(), user: Cow<str>, path: &str) -> String {
+            format!("{} of {}", path, user)
+        }
+        let router = Router::builder()
+            .route(Method::GET, "/users/{name}", |_: &(), name: &str| {
+                format!("user {}", name)
+            })
+            .route(Method::GET, "/files/{user}/{*path}", get_file)
+            .route_methods(
+                &[Method::PUT, Method::PATCH],
+                "/users/{name}",
+                |_: &(), method: Method, name: &str| format!("{} {}", method, name),
+            )
+            .fallback(fallback)
+            .build();
+        let path = String::from("/users/alice");
+        assert_eq!(router.handle((), Method::GET, &path), "user alice");
+        assert_eq!(router.handle((), Method::PATCH, &path), "PATCH alice");
+        assert_eq!(
+            router.handle((), Method::GET, "/files/bob/docs/a.txt"),
+            "docs/a.txt of bob"
+        );
+        assert_eq!(router.routes()[0].params[0].ty, "&str");
+    }
+
+    #[test]
+    fn test_hosts() {
+        use host::RequestHost;
+
+        struct Context(&'static str);
+
+        impl RequestHost for Context {
+            fn host(&self) -> Option<&str> {
+                Some(self.0)
+            }
+        }
+
+        let get_user = |_: &Context, tenant: String, id: u32| format!("user {} of {}", id, tenant);
+        let get_any_user = |_: &Context, id: u32| format!("user {}", id);
+        let router = Router::builder()
+            .route(Method::GET, "/users/{user_id}", get_user)
+            .host("{tenant}.example.com")
+            .route(Method::GET, "/users/{user_id}", get_any_user)
+            .rejection(|_: &Context, rejection: Rejection| rejection.to_string())
+            .fallback(|_: &Context| "404".to_string())
+            .build();
+        assert_eq!(
+            router.handle(Context("acme.example.com"), Method::GET, "/users/1"),
+            "user 1 of acme"
+        );
+        assert_eq!(
+            router.handle(Context("example.com"), Method::GET, "/users/1"),
+            "user 1"
+        );
+        assert_eq!(
+            router.handle(Context("example.com"), Method::GET, "/users/x"),
+            "Invalid param `user_id` = `x`: invalid digit found in string"
+        );
+        assert_eq!(
+            router.handle(Context("acme.example.com"), Method::GET, "/users/x"),
+            "Invalid param `user_id` = `x`: invalid digit found in string"
+        );
+        let routes = router.routes();
+        assert_eq!(routes[0].host, Some("{tenant}.example.com".to_string()));
+        assert_eq!(routes[0].params[0].name, "tenant");
+        assert_eq!(routes[0].params[1].ty, "u32");
+        assert_eq!(routes[1].host, None);
+    }
+
+    #[test]
+    fn test_guards() {
+        let is_admin = |user: &&'static str| match *user {
+            "admin" => Ok(()),
+            _ => Err(Rejection::Custom {
+                status: 403,
+                message: "Admins only".to_string(),
+            }),
+        };
+        let is_beta = |user: &&'static str| user.starts_with("beta");
+        let get_user = |_: &&'static str, id: u32| format!("get_user({})", id);
+        let get_user_beta = |_: &&'static str, id: u32| format!("get_user_beta({})", id);
+        let router = Router::builder()
+            .route(Method::GET, "/users/{user_id}", get_user_beta)
+            .guard(is_beta)
+            .route(Method::GET, "/users/{user_id}", get_user)
+            .route(Method::DELETE, "/users/{user_id}", get_user)
+            .guard(is_beta)
+            .guard(is_admin)
+            .rejection(|_: &&'static str, rejection: Rejection| rejection.to_string())
+            .fallback(|_: &&'static str| "404".to_string())
+            .build();
+        assert_eq!(
+            router.handle("beta", Method::GET, "/users/1"),
+            "get_user_beta(1)"
+        );
+        assert_eq!(router.handle("bob", Method::GET, "/users/1"), "get_user(1)");
+        assert_eq!(router.handle("bob", Method::DELETE, "/users/1"), "404");
+        assert_eq!(
+            router.handle("beta", Method::DELETE, "/users/1"),
+            "Admins only"
+        );
+        // guards run before params are converted
+        assert_eq!(
+            router.handle("beta", Method::DELETE, "/users/x"),
+            "Admins only"
+        );
+    }
+
+    #[test]
+    fn test_header_routing() {
+        struct Headers(&'static str, &'static str);
+
+        impl RequestHeaders for Headers {
+            fn header(&self, name: &str) -> Option<&str> {
+                match name.eq_ignore_ascii_case(self.0) {
+                    true => Some(self.1),
+                    false => None,
+                }
+            }
+        }
+
+        let get_users = |_: &Headers| "users".to_string();
+        let get_users_v2 = |_: &Headers, version: ApiVersion| format!("users v{}", version.0);
+        let get_acme_users = |_: &Headers| "acme users".to_string();
+        let router = Router::builder()
+            .route(Method::GET, "/users", get_users_v2)
+            .version(2)
+            .route(Method::GET, "/users", get_acme_users)
+            .header("x-tenant", "acme")
+            .route(Method::GET, "/users", get_users)
+            .fallback(|_: &Headers| "404".to_string())
+            .build();
+        let handle = |name, value| router.handle(Headers(name, value), Method::GET, "/users");
+        assert_eq!(handle("x-api-version", "v2"), "users v2");
+        assert_eq!(handle("accept", "application/vnd.acme.v2+json"), "users v2");
+        assert_eq!(handle("x-api-version", "1"), "users");
+        assert_eq!(handle("X-Tenant", "acme"), "acme users");
+        assert_eq!(handle("x-tenant", "other"), "users");
+    }
+
+    #[test]
+    fn test_content_negotiation() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Headers(&'static str, &'static str);
+
+        impl RequestHeaders for Headers {
+            fn header(&self, name: &str) -> Option<&str> {
+                match name.eq_ignore_ascii_case(self.0) {
+                    true => Some(self.1),
+                    false => None,
+                }
+            }
+        }
+
+        let get_csv = |_: &Headers| "csv".to_string();
+        let get_json = |_: &Headers| "json".to_string();
+        let post_json = |_: &Headers| "post json".to_string();
+        let router = Router::builder()
+            .route(Method::GET, "/users", get_csv)
+            .produces("text/csv")
+            .route(Method::GET, "/users", get_json)
+            .produces("application/json")
+            .route(Method::POST, "/users", post_json)
+            .consumes("application/json")
+            .rejection(|_: &Headers, rejection: Rejection| rejection.status().to_string())
+            .fallback(|_: &Headers| "404".to_string())
+            .build();
+        let handle = |method, name, value| router.handle(Headers(name, value), method, "/users");
+        assert_eq!(handle(Method::GET, "accept", "text/csv"), "csv");
+        assert_eq!(
+            handle(Method::GET, "accept", "text/csv;q=0.5, application/*"),
+            "json"
+        );
+        assert_eq!(
+            handle(Method::GET, "accept", "application/xml, text/*;q=0.1"),
+            "csv"
+        );
+        assert_eq!(handle(Method::GET, "x-other", ""), "csv");
+        assert_eq!(handle(Method::GET, "accept", "application/xml"), "406");
+        assert_eq!(
+            handle(
+                Method::POST,
+                "content-type",
+                "application/json; charset=utf-8"
+            ),
+            "post json"
+        );
+        assert_eq!(handle(Method::POST, "content-type", "text/csv"), "415");
+        assert_eq!(handle(Method::DELETE, "content-type", "text/csv"), "404");
+
+        // routes without media types are not tried again
+        let guarded = Arc::new(AtomicUsize::new(0));
+        let counter = guarded.clone();
+        let router = Router::builder()
+            .route(Method::GET, "/users", get_json)
+            .guard(move |_: &Headers| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Guard::Skip
+            })
+            .route(Method::GET, "/users", get_csv)
+            .produces("text/csv")
+            .route(Method::GET, "/users", get_json)
+            .produces("application/json")
+            .fallback(|_: &Headers| "404".to_string())
+            .build();
+        let headers = Headers("accept", "application/json;q=0.5, application/xml");
+        assert_eq!(router.handle(headers, Method::GET, "/users"), "json");
+        assert_eq!(guarded.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_redirects() {
+        let get_user = |_: &(), id: u32| format!("user {}", id);
+        let get_file = |_: &(), tenant: String, path: String| format!("{} of {}", path, tenant);
+        let files = Router::builder()
+            .redirect(Method::GET, "/old/{*path}", 301, "/files/{path}")
+            .fallback(|_: &()| "404".to_string())
+            .build();
+        let router = Router::builder()
+            .redirect(Method::GET, "/old-users/{id}", 308, "/users/{id}")
+            .alias("/legacy/users/{user_id}")
+            .route(Method::GET, "/users/{id}", get_user)
+            .alias("/people/{id}")
+            .route(Method::GET, "/{tenant}/files/{*path}", get_file)
+            .mount("/static", files)
+            .fallback(|_: &()| "404".to_string())
+            .build();
+        assert_eq!(
+            router.handle((), Method::GET, "/old-users/1"),
+            "308 /users/1"
+        );
+        assert_eq!(
+            router.handle((), Method::GET, "/legacy/users/1"),
+            "308 /users/1"
+        );
+        assert_eq!(router.handle((), Method::GET, "/people/2"), "user 2");
+        assert_eq!(router.handle((), Method::GET, "/people/x"), "404");
+        assert_eq!(
+            router.handle((), Method::GET, "/static/old/a/b.txt"),
+            "301 /static/files/a/b.txt"
+        );
+        let open = Router::builder()
+            .redirect(Method::GET, "/old/{*path}", 301, "/{path}")
+            .fallback(|_: &()| "404".to_string())
+            .build();
+        assert_eq!(
+            open.handle((), Method::GET, "/old//evil.com"),
+            "301 /evil.com"
+        );
+        let routes = router.routes();
+        assert_eq!(routes[1].path, "/legacy/users/{user_id}");
+        assert_eq!(routes[1].redirect, Some(Redirect::new(308, "/users/{id}")));
+        assert_eq!(routes[3].path, "/people/{id}");
+        assert_eq!(routes[3].handler, routes[2].handler);
+
+        // targets of routers mounted under a prefix with params are relative to it
+        let users = Router::builder()
+            .redirect(Method::GET, "/old/{id}", 308, "/users/{id}")
+            .redirect(Method::GET, "/{id}/home", 302, "https://example.com/{id}")
+            .fallback(|_: &()| "404".to_string())
+            .build();
+        let router = Router::builder()
+            .mount("/tenants/{tenant}", users)
+            .fallback(|_: &()| "404".to_string())
+            .build();
+        assert_eq!(
+            router.handle((), Method::GET, "/tenants/acme/old/1"),
+            "308 /tenants/acme/users/1"
+        );
+        assert_eq!(
+            router.handle((), Method::GET, "/tenants/acme/1/home"),
+            "302 https://example.com/1"
+        );
+        let routes = router.routes();
+        assert_eq!(
+            routes[0].redirect,
+            Some(Redirect::new(308, "/tenants/{tenant}/users/{id}"))
+        );
+        assert_eq!(routes[0].params.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "should have the same number of params")]
+    fn test_alias_params() {
+        Router::builder()
+            .route(Method::GET, "/users/{id}", |_: &(), id: u32| id.to_string())
+            .alias("/people")
+            .fallback(|_: &()| "404".to_string())
+            .build();
+    }
+
+    #[test]
+    fn test_deprecation() {
+        let sunset = |_: &(), route: &RouteInfo, next: Next<(), String>| match route.deprecation {
+            Some(ref deprecation) => format!("{} until {:?}", next.run(&()), deprecation.sunset),
+            None => next.run(&()),
+        };
+        let get_users = |_: &()| "users".to_string();
+        let router = Router::builder()
+            .route(Method::GET, "/v1/users", get_users)
+            .alias("/v1/people")
+            .deprecated(Deprecation::new().sunset("2027-01-01"))
+            .route(Method::GET, "/v2/users", get_users)
+            .middleware(sunset)
+            .fallback(|_: &()| "404".to_string())
+            .build();
+        assert_eq!(
+            router.handle((), Method::GET, "/v1/people"),
+            "users until Some(\"2027-01-01\")"
+        );
+        assert_eq!(router.handle((), Method::GET, "/v2/users"), "users");
+        let routes = router.routes();
+        assert_eq!(
+            routes[0].deprecation,
+            Some(Deprecation::new().sunset("2027-01-01"))
+        );
+        assert_eq!(routes[2].deprecation, None);
+    }
+
+    #[test]
+    fn test_metrics() {
+        use futures::executor::block_on;
+        use std::future::ready;
+
+        let metrics = Arc::new(MetricsRegistry::new());
+        let get_user = |_: &(), id: u32| format!("user {}", id);
+        let router = Router::builder()
+            .route(Method::GET, "/users/{id}", get_user)
+            .route(Method::GET, "/admin", |_: &()| "admin".to_string())
+            .guard(|_: &()| Guard::Reject(Rejection::MissingApiVersion))
+            .metrics(metrics.clone())
+            .fallback(|_: &()| "404".to_string())
+            .build();
+        router.handle((), Method::GET, "/users/1");
+        router.handle((), Method::GET, "/users/2");
+        router.handle((), Method::GET, "/users/x");
+        router.handle((), Method::DELETE, "/users/1");
+        router.handle((), Method::GET, "/admin");
+        router.handle((), Method::GET, "/missing");
+        let count = |method, route, outcome| metrics.count(method, route, outcome);
+        let route = Some("/users/{id}");
+        assert_eq!(count(Method::GET, route, Outcome::Handled), 2);
+        assert_eq!(count(Method::GET, route, Outcome::BadParam), 1);
+        assert_eq!(count(Method::DELETE, None, Outcome::MethodNotAllowed), 1);
+        assert_eq!(count(Method::GET, Some("/admin"), Outcome::Rejected), 1);
+        assert_eq!(count(Method::GET, None, Outcome::Fallback), 1);
+
+        let metrics = Arc::new(MetricsRegistry::new());
+        let router: Router<(), BoxFuture<String>> = Router::builder()
+            .route_async(Method::GET, "/users/{id}", |_: (), id: u32| {
+                ready(format!("user {}", id))
+            })
+            .metrics_async(metrics.clone())
+            .fallback_async(|_: ()| ready("404".to_string()))
+            .build();
+        let future = router.handle((), Method::GET, "/users/1");
+        assert_eq!(metrics.count(Method::GET, route, Outcome::Handled), 0);
+        assert_eq!(block_on(future), "user 1");
+        assert_eq!(metrics.count(Method::GET, route, Outcome::Handled), 1);
+    }
+
+    #[test]
+    fn test_explain() {
+        let get_user = |_: &(), id: u32| format!("user {}", id);
+        let router = Router::builder()
+            .route(Method::GET, "/users", get_users)
+            .route(Method::GET, "/users/{id}", get_user)
+            .route_methods(
+                &[Method::PUT, Method::PATCH],
+                "/users/{id}",
+                |_: &(), _, id: u32| format!("put {}", id),
+            )
+            .route(Method::GET, "/admin/users/{id}", get_user)
+            .guard(|_: &()| Guard::Skip)
+            .redirect(Method::GET, "/people/{id}", 308, "/users/{id}")
+            .mount(
+                "/teams/{team}",
+                Router::builder()
+                    .route(
+                        Method::GET,
+                        "/members/{id}",
+                        |_: &(), _: String, id: u32| format!("member {}", id),
+                    )
+                    .fallback(fallback),
+            )
+            .fallback(fallback)
+            .build();
+
+        let explanation = router.explain(Method::GET, "/users/x");
+        assert_eq!(explanation.routes.len(), 6);
+        let verdicts: Vec<_> = explanation
+            .routes
+            .iter()
+            .map(|route| (route.method_matches, route.path_matches, route.matches()))
+            .collect();
+        assert_eq!(
+            verdicts,
+            vec![
+                (true, false, false),
+                (true, true, false),
+                (false, true, false),
+                (true, false, false),
+                (true, false, false),
+                (true, false, false),
+            ]
+        );
+        let error = explanation.routes[1].param_error.as_ref().unwrap();
+        assert_eq!((error.name(), error.value()), ("id", "x"));
+        assert_eq!(explanation.routes[2].method, "PUT | PATCH");
+        assert!(explanation.matched().is_none());
+
+        let explanation = router.explain(Method::PATCH, "/users/12");
+        assert_eq!(explanation.matched().unwrap().method, "PUT | PATCH");
+
+        let explanation = router.explain(Method::GET, "/admin/users/12");
+        let matched = explanation.matched().unwrap();
+        assert_eq!(matched.path, "/admin/users/{id}");
+        assert!(matched.conditional);
+
+        let explanation = router.explain(Method::GET, "/people/x");
+        assert_eq!(explanation.matched().unwrap().handler, "redirect");
+
+        let explanation = router.explain(Method::GET, "/teams/a/members/x");
+        let error = explanation.routes[5].param_error.as_ref().unwrap();
+        assert_eq!((error.name(), error.value()), ("id", "x"));
+        let explanation = router.explain(Method::GET, "/teams/a/members/1");
+        assert_eq!(
+            explanation.matched().unwrap().path,
+            "/teams/{team}/members/{id}"
+        );
+
+        // explanations agree with dispatch for routes without conditions
+        for &(method, path) in &[
+            (Method::GET, "/users"),
+            (Method::GET, "/users/1"),
+            (Method::GET, "/users/x"),
+            (Method::DELETE, "/users/1"),
+            (Method::PUT, "/users/1"),
+            (Method::GET, "/teams/a/members/1"),
+            (Method::GET, "/teams/a/members/x"),
+            (Method::GET, "/missing"),
+        ] {
+            let matched = router.explain(method, path).matched().is_some();
+            assert_eq!(
+                matched,
+                router.handle((), method, path) != "404",
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_middleware() {
+        let logging = |user: &&'static str, route: &RouteInfo, next: Next<&'static str, String>| {
+            let params = next.params();
+            format!(
+                "{} {} {:?} [{}]",
+                route.method,
+                route.path,
+                params,
+                next.run(user)
+            )
+        };
+        let auth = |user: &&'static str, route: &RouteInfo, next: Next<&'static str, String>| {
+            match *user {
+                "admin" => next.run(user),
+                _ => format!("{} {} is forbidden", route.params[0].name, next.params()[0]),
+            }
+        };
+        let impersonate =
+            |_: &&'static str, _: &RouteInfo, next: Next<&'static str, String>| next.run(&"alice");
+        let get_user = |user: &&'static str, id: u32| format!("user {} for {}", id, user);
+        let not_found = |_: &&'static str| "404".to_string();
+
+        let admin = Router::builder()
+            .middleware(auth)
+            .route(Method::DELETE, "/users/{user_id}", get_user)
+            .fallback(not_found)
+            .build();
+        let router = Router::builder()
+            .route(Method::GET, "/users/{user_id}", get_user)
+            .with(impersonate)
+            .any("/proxy", |_: &&'static str, method: Method| {
+                method.to_string()
+            })
+            .mount("/admin", admin)
+            .middleware(logging)
+            .fallback(not_found)
+            .build();
+        assert_eq!(
+            router.handle("bob", Method::GET, "/users/1"),
+            "GET /users/{user_id} [\"1\"] [user 1 for alice]"
+        );
+        assert_eq!(
+            router.handle("bob", Method::DELETE, "/admin/users/2"),
+            "DELETE /admin/users/{user_id} [\"2\"] [user_id 2 is forbidden]"
+        );
+        assert_eq!(
+            router.handle("admin", Method::DELETE, "/admin/users/1"),
+            "DELETE /admin/users/{user_id} [\"1\"] [user 1 for admin]"
+        );
+        assert_eq!(
+            router.handle("bob", Method::extension("PURGE"), "/proxy"),
+            "PURGE /proxy [] [PURGE]"
+        );
+        assert_eq!(router.handle("bob", Method::GET, "/users/x"), "404");
+    }
+
+    #[test]
+    fn test_async() {
+        use futures::executor::block_on;
+        use std::future::ready;
+
+        let get_user = |tenant: &'static str, id: u32| ready(format!("user {} of {}", id, tenant));
+        let router = Router::builder()
+            .route_async(Method::GET, "/users/{user_id}", get_user)
+            .fallback_async(|_| ready("404".to_string()))
+            .build();
+        assert_eq!(
+            block_on(router.handle("acme", Method::GET, "/users/12")),
+            "user 12 of acme"
+        );
+        assert_eq!(
+            block_on(router.handle("acme", Method::GET, "/users/x")),
+            "404"
+        );
+        assert_eq!(router.routes()[0].params[0].ty, "u32");
+
+        let router: Router<&str, BoxFuture<String>> = Router::builder()
+            .route_async(Method::GET, "/users/{name}", |_, name: &str| {
+                ready(format!("user {}", name))
+            })
+            .fallback_async(|_| ready("404".to_string()))
+            .build();
+        assert_eq!(
+            block_on(router.handle("acme", Method::GET, "/users/alice")),
+            "user alice"
+        );
+    }
+}
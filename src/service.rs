@@ -0,0 +1,385 @@
+use handler::BoxFuture;
+use http::Request;
+use method::{InvalidMethod, Method};
+use middleware::Next;
+use pattern::Pattern;
+use request::RequestContext;
+use router::{short_type_name, Router, RouterBuilder};
+use std::convert::TryFrom;
+use std::future::{self, Future};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Layer, Service, ServiceExt};
+
+/// Raw params of the matched route, added to request extensions
+/// of the requests passed to route services
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathParams(Vec<(String, String)>);
+
+impl PathParams {
+    /// Raw value of a param, e.g. `get("user_id")` for `/users/{user_id}`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|param| param.0 == name)
+            .map(|param| param.1.as_str())
+    }
+
+    /// Names and raw values of params in the order they appear in the path
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|param| (param.0.as_str(), param.1.as_str()))
+    }
+}
+
+/// `tower::Service` serving `http::Request<B>` with a `Router`.
+///
+/// Handlers return futures resolving to `Result<T, E>`, e.g. `BoxFuture<Result<Response<Body>, Error>>`.
+/// Requests with a method that can't be represented as `Method` fail with `E::from(InvalidMethod)`.
+///
+/// The service is cheap to clone and can be wrapped with any tower middleware:
+///
+/// ```rust
+/// let service = ServiceBuilder::new()
+///     .timeout(Duration::from_secs(10))
+///     .service(router.into_service(state));
+/// ```
+pub struct RouterService<S, B, R> {
+    router: Arc<Router<RequestContext<S, B>, R>>,
+    state: S,
+}
+
+impl<S, B, R> Router<RequestContext<S, B>, R> {
+    /// Turns the router into a `tower::Service`. `state` is cloned for every request.
+    pub fn into_service(self, state: S) -> RouterService<S, B, R> {
+        RouterService {
+            router: Arc::new(self),
+            state,
+        }
+    }
+}
+
+impl<S: Clone, B, R> Clone for RouterService<S, B, R> {
+    fn clone(&self) -> Self {
+        RouterService {
+            router: self.router.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<S, B, R, T, E> Service<Request<B>> for RouterService<S, B, R>
+where
+    S: Clone,
+    R: Future<Output = Result<T, E>> + Send + 'static,
+    T: Send + 'static,
+    E: From<InvalidMethod> + Send + 'static,
+{
+    type Response = T;
+    type Error = E;
+    type Future = BoxFuture<Result<T, E>>;
+
+    fn poll_ready(&mut self, _: &mut Context) -> Poll<Result<(), E>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<B>) -> BoxFuture<Result<T, E>> {
+        match self.router.handle_request(self.state.clone(), request) {
+            Ok(future) => Box::pin(future),
+            Err(e) => Box::pin(future::ready(Err(E::from(e)))),
+        }
+    }
+}
+
+/// `tower::Layer` that routes requests with a `Router` and passes the ones no route
+/// matches to the inner service, e.g. to put a router in front of an existing service.
+///
+/// The inner service takes the place of the fallback of the router, fallbacks of mounted
+/// routers still answer paths under their prefix. Requests with a method that can't be
+/// represented as `Method` go to the inner service as well.
+///
+/// ```rust
+/// let service = ServiceBuilder::new()
+///     .layer(router.into_layer(state))
+///     .service(legacy_service);
+/// ```
+pub struct RouterLayer<S, B, R> {
+    router: Arc<Router<RequestContext<S, B>, R>>,
+    state: S,
+}
+
+impl<S, B, R> Router<RequestContext<S, B>, R> {
+    /// Turns the router into a `tower::Layer`. `state` is cloned for every request.
+    pub fn into_layer(self, state: S) -> RouterLayer<S, B, R> {
+        RouterLayer {
+            router: Arc::new(self),
+            state,
+        }
+    }
+}
+
+impl<S: Clone, B, R> Clone for RouterLayer<S, B, R> {
+    fn clone(&self) -> Self {
+        RouterLayer {
+            router: self.router.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<S: Clone, B, R, I> Layer<I> for RouterLayer<S, B, R> {
+    type Service = Routed<S, B, R, I>;
+
+    fn layer(&self, inner: I) -> Routed<S, B, R, I> {
+        Routed {
+            router: self.router.clone(),
+            state: self.state.clone(),
+            inner,
+        }
+    }
+}
+
+/// Service created by `RouterLayer`, see its docs
+pub struct Routed<S, B, R, I> {
+    router: Arc<Router<RequestContext<S, B>, R>>,
+    state: S,
+    inner: I,
+}
+
+impl<S: Clone, B, R, I: Clone> Clone for Routed<S, B, R, I> {
+    fn clone(&self) -> Self {
+        Routed {
+            router: self.router.clone(),
+            state: self.state.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S, B, R, I, T, E> Service<Request<B>> for Routed<S, B, R, I>
+where
+    S: Clone,
+    B: Send + 'static,
+    R: Future<Output = Result<T, E>> + Send + 'static,
+    I: Service<Request<B>, Response = T, Error = E> + Clone + Send + 'static,
+    I::Future: Send + 'static,
+    T: Send + 'static,
+    E: Send + 'static,
+{
+    type Response = T;
+    type Error = E;
+    type Future = BoxFuture<Result<T, E>>;
+
+    // the inner service is cloned for every request it gets and polled for readiness then
+    fn poll_ready(&mut self, _: &mut Context) -> Poll<Result<(), E>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<B>) -> BoxFuture<Result<T, E>> {
+        let method = match Method::try_from(request.method()) {
+            Ok(method) => method,
+            Err(_) => return Box::pin(self.inner.clone().oneshot(request)),
+        };
+        // the path is copied because the context takes ownership of the request
+        let path = request.uri().path().to_string();
+        let context = RequestContext::new(self.state.clone(), request);
+        match self.router.route(context, method, &path, false) {
+            Ok(future) => Box::pin(future),
+            Err(context) => Box::pin(self.inner.clone().oneshot(context.into_inner().1)),
+        }
+    }
+}
+
+impl<S, B, T, E> RouterBuilder<RequestContext<S, B>, BoxFuture<Result<T, E>>>
+where
+    S: 'static,
+    B: Clone + Send + 'static,
+    T: 'static,
+    E: 'static,
+{
+    /// Adds a route handled by a `tower::Service`, e.g. a handler wrapped with middleware.
+    ///
+    /// The service is called with a copy of the request, so the body should be
+    /// buffered (e.g. `Bytes` or `String`). Params of the route are available
+    /// as `PathParams` in request extensions.
+    ///
+    /// ### Panics
+    /// If the route is mounted under a prefix with params.
+    pub fn service<V>(self, method: Method, path: &str, service: V) -> Self
+    where
+        V: Service<Request<B>, Response = T, Error = E> + Clone + Send + Sync + 'static,
+        V::Future: Send + 'static,
+    {
+        let names = Pattern::new(path).params().to_vec();
+        let param_types = vec!["String"; names.len()];
+        self.add_route(
+            Some(vec![method]),
+            path,
+            param_types,
+            short_type_name::<V>(),
+            Arc::new(move |context, _, params, chain| {
+                let mut params = Some(
+                    names
+                        .iter()
+                        .cloned()
+                        .zip(params.iter().map(|value| value.to_string()))
+                        .collect(),
+                );
+                Some(Ok(chain(
+                    context,
+                    Next::new(&mut |context| {
+                        let mut request =
+                            Request::from_parts(context.parts().clone(), context.body().clone());
+                        let params = params.take().expect("Next is run once");
+                        request.extensions_mut().insert(PathParams(params));
+                        Box::pin(service.clone().oneshot(request)) as BoxFuture<Result<T, E>>
+                    }),
+                )))
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use std::fmt;
+    use tower::{service_fn, ServiceBuilder};
+
+    #[derive(Debug, PartialEq)]
+    struct Error(String);
+
+    impl From<InvalidMethod> for Error {
+        fn from(e: InvalidMethod) -> Error {
+            Error(e.to_string())
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(&self.0)
+        }
+    }
+
+    type Context = RequestContext<&'static str, String>;
+    type ServerFuture = BoxFuture<Result<String, Error>>;
+
+    fn get_users(context: &Context) -> ServerFuture {
+        Box::pin(future::ready(Ok(format!("users of {}", context.state()))))
+    }
+
+    fn not_found(context: &Context) -> ServerFuture {
+        Box::pin(future::ready(Ok(format!("not found {}", context.path()))))
+    }
+
+    fn request(method: &str, uri: &str) -> Request<String> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .body("body".to_string())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_into_service() {
+        let router = Router::builder()
+            .route(Method::GET, "/users", get_users)
+            .fallback(not_found)
+            .build();
+        let service = ServiceBuilder::new()
+            .map_response(|response: String| response.to_uppercase())
+            .service(router.into_service("acme"));
+        assert_eq!(
+            block_on(service.clone().oneshot(request("GET", "/users"))),
+            Ok("USERS OF ACME".to_string())
+        );
+        assert_eq!(
+            block_on(service.clone().oneshot(request("GET", "/posts"))),
+            Ok("NOT FOUND /POSTS".to_string())
+        );
+        assert_eq!(
+            block_on(service.oneshot(request(&"X".repeat(32), "/users"))),
+            Err(Error(format!("Invalid http method `{}`", "X".repeat(32))))
+        );
+    }
+
+    #[test]
+    fn test_into_layer() {
+        let users = Router::builder()
+            .route(Method::GET, "/", get_users)
+            .fallback(not_found)
+            .build();
+        let router = Router::builder()
+            .route(Method::GET, "/users", get_users)
+            .mount("/admin", users)
+            .fallback(not_found)
+            .build();
+        let legacy = service_fn(|request: Request<String>| {
+            future::ready(Ok::<_, Error>(format!(
+                "legacy {} {}",
+                request.method(),
+                request.uri().path()
+            )))
+        });
+        let service = ServiceBuilder::new()
+            .layer(router.into_layer("acme"))
+            .service(legacy);
+        assert_eq!(
+            block_on(service.clone().oneshot(request("GET", "/users"))),
+            Ok("users of acme".to_string())
+        );
+        assert_eq!(
+            block_on(service.clone().oneshot(request("GET", "/posts"))),
+            Ok("legacy GET /posts".to_string())
+        );
+        assert_eq!(
+            block_on(service.clone().oneshot(request("POST", "/users"))),
+            Ok("legacy POST /users".to_string())
+        );
+        assert_eq!(
+            block_on(service.clone().oneshot(request("GET", "/admin/posts"))),
+            Ok("not found /admin/posts".to_string())
+        );
+        let method = "X".repeat(32);
+        assert_eq!(
+            block_on(service.oneshot(request(&method, "/users"))),
+            Ok(format!("legacy {} /users", method))
+        );
+    }
+
+    #[test]
+    fn test_route_service() {
+        let get_user = service_fn(|request: Request<String>| {
+            let params = request.extensions().get::<PathParams>().unwrap();
+            future::ready(Ok::<_, Error>(format!(
+                "user {} of {}, {}",
+                params.get("user_id").unwrap(),
+                params.get("team_id").unwrap(),
+                request.body()
+            )))
+        });
+        let router = Router::builder()
+            .route(Method::GET, "/users", get_users)
+            .service(
+                Method::GET,
+                "/teams/{team_id}/users/{user_id}",
+                ServiceBuilder::new()
+                    .map_response(|response: String| format!("<{}>", response))
+                    .service(get_user),
+            )
+            .fallback(not_found)
+            .build();
+        assert_eq!(router.routes()[1].params[1].name, "user_id");
+        let service = router.into_service("acme");
+        assert_eq!(
+            block_on(service.clone().oneshot(request("GET", "/teams/1/users/2"))),
+            Ok("<user 2 of 1, body>".to_string())
+        );
+        assert_eq!(
+            block_on(service.oneshot(request("POST", "/teams/1/users/2"))),
+            Ok("not found /teams/1/users/2".to_string())
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use extract::Rejection;
+use handler::BoxFuture;
+use method::Method;
+use tracing::field::Empty;
+use tracing::{debug, info_span, Instrument as _, Span};
+
+// Value recorded instead of the value of a redacted param
+const REDACTED: &str = "[redacted]";
+
+// Span of one dispatch, the route fields are recorded once a route is about to handle the request
+pub(crate) fn dispatch_span(method: Method) -> Span {
+    info_span!(
+        "dispatch",
+        http.method = %method,
+        http.route = Empty,
+        handler = Empty,
+        params = Empty
+    )
+}
+
+// Carries the dispatch span into the output of a router, see `instrument`
+pub(crate) type Instrument<R> = fn(R, Span) -> R;
+
+// Output of sync routers is ready, so the span closes with the dispatch
+pub(crate) fn keep<R>(output: R, _: Span) -> R {
+    output
+}
+
+// Futures of async routers run in the dispatch span, which closes once they complete
+pub(crate) fn instrument<T: 'static>(future: BoxFuture<T>, span: Span) -> BoxFuture<T> {
+    Box::pin(future.instrument(span))
+}
+
+// Records the route that handles the request on the current span.
+// Params are formatted as `name=value`, so nothing is allocated if the span is disabled
+pub(crate) fn record_route<'a, F, I, S>(
+    template: &str,
+    handler: &str,
+    names: F,
+    values: I,
+    redacted: &[S],
+) where
+    F: FnOnce() -> Vec<String>,
+    I: IntoIterator<Item = &'a str>,
+    S: AsRef<str>,
+{
+    let span = Span::current();
+    if span.is_disabled() {
+        return;
+    }
+    let params: Vec<String> = names()
+        .iter()
+        .zip(values)
+        .map(
+            |(name, value)| match redacted.iter().any(|redacted| redacted.as_ref() == name) {
+                true => format!("{}={}", name, REDACTED),
+                false => format!("{}={}", name, value),
+            },
+        )
+        .collect();
+    span.record("http.route", template);
+    span.record("handler", handler);
+    span.record("params", params.join(", ").as_str());
+}
+
+// Events of `router!`, the same as those of `Router::builder()`
+pub(crate) fn guard_skipped(template: &str) {
+    debug!(route = template, "route skipped by a guard");
+}
+
+pub(crate) fn media_skipped(template: &str) {
+    debug!(route = template, "route skipped, media types don't fit");
+}
+
+// Result of a route that decides the request, unless another route matches
+// after an invalid param
+pub(crate) fn route_result<R>(template: &str, result: &Option<Result<R, Rejection>>) {
+    match *result {
+        Some(Ok(_)) => debug!(route = template, "route matched"),
+        Some(Err(Rejection::InvalidParam(ref error))) => {
+            debug!(route = template, error = %error, "route skipped, invalid param")
+        }
+        Some(Err(ref rejection)) => {
+            debug!(route = template, rejection = %rejection, "request rejected")
+        }
+        None => (),
+    }
+}
+
+pub(crate) fn fallback() {
+    debug!("no route matched, calling the fallback");
+}
+
+#[cfg(test)]
+mod tests {
+    use guard::Guard;
+    use router::Router;
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{subscriber, Event, Metadata, Subscriber};
+    use tracing_core::span::Current;
+
+    use super::*;
+
+    // Collects the fields of spans and events, as `name=value`
+    #[derive(Clone, Default)]
+    struct Collector {
+        spans: Arc<Mutex<Vec<Vec<String>>>>,
+        events: Arc<Mutex<Vec<String>>>,
+        metadata: Arc<Mutex<Vec<&'static Metadata<'static>>>>,
+        entered: Arc<Mutex<Vec<u64>>>,
+    }
+
+    struct Fields<'a>(&'a mut Vec<String>);
+
+    impl<'a> Visit for Fields<'a> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.push(format!("{}={}", field.name(), value));
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    impl Subscriber for Collector {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes) -> Id {
+            let mut fields = Vec::new();
+            span.record(&mut Fields(&mut fields));
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(fields);
+            self.metadata.lock().unwrap().push(span.metadata());
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record) {
+            let mut spans = self.spans.lock().unwrap();
+            values.record(&mut Fields(&mut spans[span.into_u64() as usize - 1]));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event) {
+            let mut fields = Vec::new();
+            event.record(&mut Fields(&mut fields));
+            self.events.lock().unwrap().push(fields.join(" "));
+        }
+
+        fn enter(&self, span: &Id) {
+            self.entered.lock().unwrap().push(span.into_u64());
+        }
+
+        fn exit(&self, _: &Id) {
+            self.entered.lock().unwrap().pop();
+        }
+
+        fn current_span(&self) -> Current {
+            match self.entered.lock().unwrap().last() {
+                Some(&id) => {
+                    let metadata = self.metadata.lock().unwrap()[id as usize - 1];
+                    Current::new(Id::from_u64(id), metadata)
+                }
+                None => Current::none(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_tracing() {
+        let get_invite = |_: &(), id: u32, token: String| format!("invite {} {}", id, token);
+        let router = Router::builder()
+            .route(Method::GET, "/admin", |_: &()| "admin".to_string())
+            .guard(|_: &()| Guard::Skip)
+            .route(Method::GET, "/invites/{id}/{token}", get_invite)
+            .redact("token")
+            .fallback(|_: &()| "404".to_string())
+            .build();
+        let collector = Collector::default();
+        subscriber::with_default(collector.clone(), || {
+            assert_eq!(
+                router.handle((), Method::GET, "/invites/1/secret"),
+                "invite 1 secret"
+            );
+            assert_eq!(router.handle((), Method::GET, "/invites/x/secret"), "404");
+            assert_eq!(router.handle((), Method::GET, "/admin"), "404");
+        });
+
+        let spans = collector.spans.lock().unwrap();
+        assert_eq!(
+            spans[0],
+            vec![
+                "http.method=GET",
+                "http.route=/invites/{id}/{token}",
+                "handler={{closure}}",
+                "params=id=1, token=[redacted]",
+            ]
+        );
+        let events = collector.events.lock().unwrap();
+        assert_eq!(
+            *events,
+            vec![
+                "message=route matched route=/invites/{id}/{token}",
+                "message=route skipped, invalid param route=/invites/{id}/{token} \
+                 error=Invalid param `id` = `x`: invalid digit found in string",
+                "message=route skipped by a guard route=/admin",
+                "message=no route matched, calling the fallback",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_async_span() {
+        use futures::executor::block_on;
+        use futures::future::lazy;
+
+        // id of the span the future runs in
+        let current = |_: (), _: u32| lazy(|_| Span::current().id().map(|id| id.into_u64()));
+        let router = Router::builder()
+            .route_async(Method::GET, "/users/{id}", current)
+            .fallback_async(|_: ()| lazy(|_| None))
+            .build();
+        let collector = Collector::default();
+        subscriber::with_default(collector.clone(), || {
+            let future = router.handle((), Method::GET, "/users/1");
+            assert_eq!(Span::current().id(), None);
+            assert_eq!(block_on(future), Some(1));
+        });
+        let spans = collector.spans.lock().unwrap();
+        assert_eq!(spans[0][1], "http.route=/users/{id}");
+    }
+
+    #[test]
+    fn test_macro_span() {
+        use futures::executor::block_on;
+        use futures::future::lazy;
+
+        fn get_user(_: &(), user_id: u32) -> String {
+            format!("user {}", user_id)
+        }
+        fn not_found(_: &()) -> String {
+            "404".to_string()
+        }
+        fn get_user_async(_: (), _: u32) -> impl std::future::Future<Output = Option<u64>> {
+            lazy(|_| Span::current().id().map(|id| id.into_u64()))
+        }
+        fn not_found_async(_: ()) -> impl std::future::Future<Output = Option<u64>> {
+            lazy(|_| None)
+        }
+
+        let router = router!(
+            GET /users/{user_id: u32} => get_user,
+            _ => not_found,
+        );
+        let router_async = router!(async
+            GET /users/{user_id: u32} => get_user_async,
+            _ => not_found_async,
+        );
+        let collector = Collector::default();
+        subscriber::with_default(collector.clone(), || {
+            assert_eq!(router((), Method::GET, "/users/1"), "user 1");
+            assert_eq!(router((), Method::GET, "/missing"), "404");
+            let future = router_async((), Method::GET, "/users/1");
+            assert_eq!(block_on(future), Some(3));
+        });
+        let spans = collector.spans.lock().unwrap();
+        assert_eq!(
+            spans[0],
+            vec![
+                "http.method=GET",
+                "http.route=/users/{user_id}",
+                "handler=get_user",
+                "params=user_id=1",
+            ]
+        );
+        assert_eq!(spans[1], vec!["http.method=GET"]);
+        assert_eq!(spans[2][2], "handler=get_user_async");
+    }
+
+    #[test]
+    fn test_macro_tracing() {
+        fn get_admin(_: &()) -> String {
+            "admin".to_string()
+        }
+        fn skip(_: &()) -> Guard {
+            Guard::Skip
+        }
+        fn get_invite(_: &(), id: u32, token: String) -> String {
+            format!("invite {} {}", id, token)
+        }
+        fn not_found(_: &()) -> String {
+            "404".to_string()
+        }
+
+        let router = router!(
+            redact(token),
+            GET /admin => get_admin if skip,
+            GET /invites/{id: u32}/{token: String} => get_invite,
+            _ => not_found,
+        );
+        let collector = Collector::default();
+        subscriber::with_default(collector.clone(), || {
+            assert_eq!(
+                router((), Method::GET, "/invites/1/secret"),
+                "invite 1 secret"
+            );
+            assert_eq!(router((), Method::GET, "/invites/x/secret"), "404");
+            assert_eq!(router((), Method::GET, "/admin"), "404");
+        });
+
+        let spans = collector.spans.lock().unwrap();
+        assert_eq!(
+            spans[0],
+            vec![
+                "http.method=GET",
+                "http.route=/invites/{id}/{token}",
+                "handler=get_invite",
+                "params=id=1, token=[redacted]",
+            ]
+        );
+        let events = collector.events.lock().unwrap();
+        assert_eq!(
+            *events,
+            vec![
+                "message=route matched route=/invites/{id}/{token}",
+                "message=route skipped, invalid param route=/invites/{id}/{token} \
+                 error=Invalid param `id` = `x`: invalid digit found in string",
+                "message=route skipped by a guard route=/admin",
+                "message=no route matched, calling the fallback",
+            ]
+        );
+        // redacted params don't change the routes
+        let routes = routes!(
+            redact(token),
+            GET /invites/{id: u32}/{token: String} => get_invite,
+            _ => not_found,
+        );
+        assert_eq!(routes[0].path, "/invites/{id}/{token}");
+    }
+}
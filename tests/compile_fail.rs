@@ -0,0 +1,7 @@
+extern crate trybuild;
+
+#[test]
+fn test_compile_fail() {
+    let tests = trybuild::TestCases::new();
+    tests.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,17 @@
+#[macro_use]
+extern crate http_router;
+
+fn get_users(_: &()) -> String {
+    "users".to_string()
+}
+
+fn not_found(_: &()) -> String {
+    "not found".to_string()
+}
+
+fn main() {
+    let _router = router!(
+        GET /users => get_users version 2,
+        _ => not_found,
+    );
+}
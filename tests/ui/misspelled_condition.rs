@@ -0,0 +1,22 @@
+#[macro_use]
+extern crate http_router;
+
+fn get_users(_: &()) -> String {
+    "users".to_string()
+}
+
+fn get_posts(_: &()) -> String {
+    "posts".to_string()
+}
+
+fn not_found(_: &()) -> String {
+    "not found".to_string()
+}
+
+fn main() {
+    let _router = router!(
+        GET /users => get_users wehre version = 2,
+        GET /posts => get_posts,
+        _ => not_found,
+    );
+}